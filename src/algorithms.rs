@@ -1,4 +1,5 @@
 use std::str::FromStr;
+use std::fmt;
 
 
 /// A hashing algorithm.
@@ -19,8 +20,11 @@ use std::str::FromStr;
 /// assert_eq!(checksums::Algorithm::from_str("BLAKE2B"), Ok(checksums::Algorithm::BLAKE2B));
 ///
 /// assert_eq!(checksums::Algorithm::from_str("MD5"), Ok(checksums::Algorithm::MD5));
+///
+/// assert_eq!(checksums::Algorithm::SHA2256.to_string(), "SHA256");
+/// assert_eq!(checksums::Algorithm::from_str("SHA256"), Ok(checksums::Algorithm::SHA2256));
 /// ```
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Algorithm {
     SHA1,
     /// SHA2-224
@@ -96,15 +100,15 @@ impl FromStr for Algorithm {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match &s.replace("_", "-").to_lowercase()[..] {
             "sha-1" | "sha1" => Ok(Algorithm::SHA1),
-            "sha2256" | "sha2-256" | "sha-2-256" => Ok(Algorithm::SHA2256),
-            "sha2224" | "sha2-224" | "sha-2-224" => Ok(Algorithm::SHA2224),
-            "sha2384" | "sha2-384" | "sha-2-384" => Ok(Algorithm::SHA2384),
-            "sha2" | "sha-2" | "sha2512" | "sha2-512" | "sha-2-512" => Ok(Algorithm::SHA2512),
+            "sha256" | "sha2256" | "sha2-256" | "sha-2-256" => Ok(Algorithm::SHA2256),
+            "sha224" | "sha2224" | "sha2-224" | "sha-2-224" => Ok(Algorithm::SHA2224),
+            "sha384" | "sha2384" | "sha2-384" | "sha-2-384" => Ok(Algorithm::SHA2384),
+            "sha2" | "sha-2" | "sha512" | "sha2512" | "sha2-512" | "sha-2-512" => Ok(Algorithm::SHA2512),
             "sha3256" | "sha3-256" | "sha-3-256" => Ok(Algorithm::SHA3256),
             "sha3" | "sha-3" | "sha3512" | "sha3-512" | "sha-3-512" => Ok(Algorithm::SHA3512),
             "blake" => Ok(Algorithm::BLAKE),
-            "blake2" | "blake2b" => Ok(Algorithm::BLAKE2B),
-            "blake2s" => Ok(Algorithm::BLAKE2S),
+            "blake2" | "blake2b" | "blake2b-512" => Ok(Algorithm::BLAKE2B),
+            "blake2s" | "blake2s-256" => Ok(Algorithm::BLAKE2S),
             "blake3" => Ok(Algorithm::BLAKE3),
             "crc64" => Ok(Algorithm::CRC64),
             "crc32c" |
@@ -124,3 +128,33 @@ impl FromStr for Algorithm {
         }
     }
 }
+
+/// Formats the algorithm as its BSD-style tag, as used by `shasum --tag`, `openssl dgst` et al.
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Algorithm::SHA1 => "SHA1",
+            Algorithm::SHA2224 => "SHA224",
+            Algorithm::SHA2256 => "SHA256",
+            Algorithm::SHA2384 => "SHA384",
+            Algorithm::SHA2512 => "SHA512",
+            Algorithm::SHA3256 => "SHA3-256",
+            Algorithm::SHA3512 => "SHA3-512",
+            Algorithm::BLAKE => "BLAKE",
+            Algorithm::BLAKE2B => "BLAKE2b",
+            Algorithm::BLAKE2S => "BLAKE2s",
+            Algorithm::BLAKE3 => "BLAKE3",
+            Algorithm::CRC64 => "CRC64",
+            Algorithm::CRC32 => "CRC32",
+            Algorithm::CRC32C => "CRC32C",
+            Algorithm::CRC16 => "CRC16",
+            Algorithm::CRC8 => "CRC8",
+            Algorithm::MD5 => "MD5",
+            Algorithm::MD6128 => "MD6-128",
+            Algorithm::MD6256 => "MD6-256",
+            Algorithm::MD6512 => "MD6-512",
+            Algorithm::WHIRLPOOL => "WHIRLPOOL",
+            Algorithm::XOR8 => "XOR8",
+        })
+    }
+}
@@ -11,17 +11,29 @@ pub enum Error {
     HashesFileParsingFailure,
     /// The specified amount of files do not match.
     NFilesDiffer(i32),
+    /// Reading or writing the hashes file failed.
+    HashesFileIoFailure,
+    /// The specified amount of files couldn't be read.
+    NFilesUnreadable(i32),
+    /// The hashes file's signature is missing or doesn't match.
+    SignatureInvalid,
 }
 
 impl Error {
     /// Get the executable exit value from an `Error` instance.
+    ///
+    /// Exit statuses are truncated to a byte, so the numbers of files are capped to keep the ranges from overlapping:
+    /// 4-99 for files that didn't match, 101-199 for ones that couldn't be read, and 200 for an invalid signature.
     pub fn exit_value(&self) -> i32 {
         match *self {
             Error::NoError => 0,
             Error::OptionParsingError => 1,
             Error::HashLengthDiffers => 2,
             Error::HashesFileParsingFailure => 3,
-            Error::NFilesDiffer(i) => i + 3,
+            Error::NFilesDiffer(i) => i.min(96) + 3,
+            Error::HashesFileIoFailure => 100,
+            Error::NFilesUnreadable(i) => i.min(99) + 100,
+            Error::SignatureInvalid => 200,
         }
     }
 }
@@ -33,6 +45,9 @@ impl From<i32> for Error {
             1 => Error::OptionParsingError,
             2 => Error::HashLengthDiffers,
             3 => Error::HashesFileParsingFailure,
+            100 => Error::HashesFileIoFailure,
+            i @ 101..=199 => Error::NFilesUnreadable(i - 100),
+            200 => Error::SignatureInvalid,
             i => Error::NFilesDiffer(i - 3),
         }
     }
@@ -2,9 +2,10 @@ use self::super::hash_string;
 use blake::Blake;
 
 
-hash_func_write!(Blake::new(512).unwrap(),
-                 |mut blake: Blake| {
-                     let mut result = [0; 64];
-                     blake.finalise(&mut result);
-                     hash_string(&result)
-                 });
+hash_func!(Blake::new(512).unwrap(),
+           |blake: &mut Blake, buffer: &[u8]| blake.update(buffer),
+           |mut blake: Blake| {
+               let mut result = [0; 64];
+               blake.finalise(&mut result);
+               hash_string(&result)
+           });
@@ -4,3 +4,7 @@ use blake2::{Blake2b, Digest};
 hash_func!(Blake2b::new(),
            |blake: &mut Blake2b, buffer: &[u8]| blake.update(buffer),
            |blake: Blake2b| hash_string(&blake.finalize()));
+
+keyed_hash_func!(key => Blake2b::with_params(key, &[], &[]),
+                 |blake: &mut Blake2b, buffer: &[u8]| blake.update(buffer),
+                 |blake: Blake2b| hash_string(&blake.finalize()));
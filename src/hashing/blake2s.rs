@@ -4,3 +4,7 @@ use blake2::{Blake2s, Digest};
 hash_func!(Blake2s::new(),
            |blake: &mut Blake2s, buffer: &[u8]| blake.update(buffer),
            |blake: Blake2s| hash_string(&blake.finalize()));
+
+keyed_hash_func!(key => Blake2s::with_params(key, &[], &[]),
+                 |blake: &mut Blake2s, buffer: &[u8]| blake.update(buffer),
+                 |blake: Blake2s| hash_string(&blake.finalize()));
@@ -3,3 +3,11 @@ use self::super::hash_string;
 hash_func!(blake3::Hasher::new(),
            |blake: &mut blake3::Hasher, buffer: &[u8]| { blake.update(buffer); },
            |blake: blake3::Hasher| hash_string(blake.finalize().as_bytes()));
+
+keyed_hash_func!(key => {
+                     let mut blake_key = [0; 32];
+                     blake_key.copy_from_slice(key);
+                     blake3::Hasher::new_keyed(&blake_key)
+                 },
+                 |blake: &mut blake3::Hasher, buffer: &[u8]| { blake.update(buffer); },
+                 |blake: blake3::Hasher| hash_string(blake.finalize().as_bytes()));
@@ -1,5 +1,6 @@
 use self::super::hash_string;
 use md5::{Md5, Digest};
 
-hash_func_write!(Md5::new(),
-                 |ctx: Md5| hash_string(&*ctx.finalize()));
+hash_func!(Md5::new(),
+           |ctx: &mut Md5, buffer: &[u8]| ctx.update(buffer),
+           |ctx: Md5| hash_string(&*ctx.finalize()));
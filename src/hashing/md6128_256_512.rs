@@ -5,12 +5,13 @@ macro_rules! make_md_mod {
             use md6::Md6;
 
 
-            hash_func_write!(Md6::new($bytesize * 8).unwrap(),
-                             |mut md6: Md6| {
-                                 let mut result = [0; $bytesize];
-                                 md6.finalise(&mut result);
-                                 hash_string(&result)
-                             });
+            hash_func!(Md6::new($bytesize * 8).unwrap(),
+                       |md6: &mut Md6, buffer: &[u8]| md6.update(buffer),
+                       |mut md6: Md6| {
+                           let mut result = [0; $bytesize];
+                           md6.finalise(&mut result);
+                           hash_string(&result)
+                       });
         }
     }
 }
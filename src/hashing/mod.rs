@@ -1,46 +1,26 @@
 macro_rules! hash_func {
     ($ctx:expr, $update:expr, $convert:expr) => {
-        use std::io::Read;
-
-
-        pub fn hash<R: Read>(reader: &mut R) -> String {
-            let mut buffer = vec![0; 4096];
-
-            let mut ctx = $ctx;
-            loop {
-                let read = reader.read(&mut buffer[..]).unwrap();
-
-                if read == 0 {
-                    break;
-                }
-
-                $update(&mut ctx, &buffer[..read]);
-            }
-
-            $convert(ctx)
+        pub fn hasher() -> Box<dyn (::hashing::Hasher)> {
+            Box::new(::hashing::FnHasher::new($ctx, $update, $convert))
         }
     }
 }
 
-macro_rules! hash_func_write {
-    ($ctx:expr, $convert:expr) => {
-        use std::io::{self, Read};
-
-
-        pub fn hash<R: Read>(reader: &mut R) -> String {
-            let mut ctx = $ctx;
-            io::copy(reader, &mut ctx).unwrap();
-            $convert(ctx)
+macro_rules! keyed_hash_func {
+    ($key:ident => $ctx:expr, $update:expr, $convert:expr) => {
+        pub fn keyed_hasher($key: &[u8]) -> Box<dyn (::hashing::Hasher)> {
+            Box::new(::hashing::FnHasher::new($ctx, $update, $convert))
         }
     }
 }
 
 
 use super::Algorithm;
+use std::collections::BTreeMap;
 use std::path::Path;
+use std::io::{self, Read};
 use std::fmt::Write;
 use std::fs::File;
-use std::io::Read;
 
 mod md5;
 mod xor8;
@@ -58,39 +38,241 @@ mod md6128_256_512;
 mod sha1_2256_2224_2384_2512;
 
 
-/// Hash the specified file using the specified hashing algorithm.
-pub fn hash_file(path: &Path, algo: Algorithm) -> String {
-    hash_reader(&mut File::open(path).unwrap(), algo)
+/// An incremental hash of a byte stream.
+pub trait Hasher {
+    /// Feed the next chunk of the stream to the hash.
+    fn update(&mut self, data: &[u8]);
+
+    /// Get the upper-case hash of all data fed so far.
+    fn finish(self: Box<Self>) -> String;
 }
 
-/// Hash the specified byte stream using the specified hashing algorithm.
-pub fn hash_reader<R: Read>(data: &mut R, algo: Algorithm) -> String {
+struct FnHasher<C, U, F> {
+    ctx: C,
+    update: U,
+    convert: F,
+}
+
+impl<C, U: Fn(&mut C, &[u8]), F: Fn(C) -> String> FnHasher<C, U, F> {
+    fn new(ctx: C, update: U, convert: F) -> FnHasher<C, U, F> {
+        FnHasher {
+            ctx,
+            update,
+            convert,
+        }
+    }
+}
+
+impl<C, U: Fn(&mut C, &[u8]), F: Fn(C) -> String> Hasher for FnHasher<C, U, F> {
+    fn update(&mut self, data: &[u8]) {
+        (self.update)(&mut self.ctx, data)
+    }
+
+    fn finish(self: Box<Self>) -> String {
+        let this = *self;
+        (this.convert)(this.ctx)
+    }
+}
+
+/// RFC 2104 HMAC over another hasher.
+struct HmacHasher {
+    inner: Box<dyn Hasher>,
+    outer: Box<dyn Hasher>,
+}
+
+impl HmacHasher {
+    fn new(algo: Algorithm, block_size: usize, key: &[u8]) -> HmacHasher {
+        let mut block_key = if key.len() > block_size {
+            let mut key_hasher = hasher(algo);
+            key_hasher.update(key);
+            hash_bytes(&key_hasher.finish())
+        } else {
+            key.to_vec()
+        };
+        block_key.resize(block_size, 0);
+
+        let mut inner = hasher(algo);
+        inner.update(&block_key.iter().map(|b| b ^ 0x36).collect::<Vec<_>>());
+        let mut outer = hasher(algo);
+        outer.update(&block_key.iter().map(|b| b ^ 0x5C).collect::<Vec<_>>());
+
+        HmacHasher { inner, outer }
+    }
+}
+
+impl Hasher for HmacHasher {
+    fn update(&mut self, data: &[u8]) {
+        self.inner.update(data)
+    }
+
+    fn finish(self: Box<Self>) -> String {
+        let mut outer = self.outer;
+        outer.update(&hash_bytes(&self.inner.finish()));
+        outer.finish()
+    }
+}
+
+
+/// Get a fresh hasher for the specified hashing algorithm.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::{Algorithm, hasher};
+/// let mut hasher = hasher(Algorithm::CRC32);
+/// hasher.update(b"check");
+/// hasher.update(b"sums");
+/// assert_eq!(hasher.finish(), "0B0ADA8E".to_string());
+/// ```
+pub fn hasher(algo: Algorithm) -> Box<dyn Hasher> {
     match algo {
-        Algorithm::SHA1 => sha1_2256_2224_2384_2512::sha1::hash(data),
-        Algorithm::SHA2224 => sha1_2256_2224_2384_2512::sha2224::hash(data),
-        Algorithm::SHA2256 => sha1_2256_2224_2384_2512::sha2256::hash(data),
-        Algorithm::SHA2384 => sha1_2256_2224_2384_2512::sha2384::hash(data),
-        Algorithm::SHA2512 => sha1_2256_2224_2384_2512::sha2512::hash(data),
-        Algorithm::SHA3256 => sha3256_3512::sha3256::hash(data),
-        Algorithm::SHA3512 => sha3256_3512::sha3512::hash(data),
-        Algorithm::BLAKE => blake::hash(data),
-        Algorithm::BLAKE2B => blake2b::hash(data),
-        Algorithm::BLAKE2S => blake2s::hash(data),
-        Algorithm::BLAKE3 => blake3::hash(data),
-        Algorithm::CRC64 => crc32_64::crc64::hash(data),
-        Algorithm::CRC32 => crc32_64::crc32::hash(data),
-        Algorithm::CRC32C => crc32c::hash(data),
-        Algorithm::CRC16 => crc16::hash(data),
-        Algorithm::CRC8 => crc8::hash(data),
-        Algorithm::MD5 => md5::hash(data),
-        Algorithm::MD6128 => md6128_256_512::md6128::hash(data),
-        Algorithm::MD6256 => md6128_256_512::md6256::hash(data),
-        Algorithm::MD6512 => md6128_256_512::md6512::hash(data),
-        Algorithm::WHIRLPOOL => whirlpool::hash(data),
-        Algorithm::XOR8 => xor8::hash(data),
+        Algorithm::SHA1 => sha1_2256_2224_2384_2512::sha1::hasher(),
+        Algorithm::SHA2224 => sha1_2256_2224_2384_2512::sha2224::hasher(),
+        Algorithm::SHA2256 => sha1_2256_2224_2384_2512::sha2256::hasher(),
+        Algorithm::SHA2384 => sha1_2256_2224_2384_2512::sha2384::hasher(),
+        Algorithm::SHA2512 => sha1_2256_2224_2384_2512::sha2512::hasher(),
+        Algorithm::SHA3256 => sha3256_3512::sha3256::hasher(),
+        Algorithm::SHA3512 => sha3256_3512::sha3512::hasher(),
+        Algorithm::BLAKE => blake::hasher(),
+        Algorithm::BLAKE2B => blake2b::hasher(),
+        Algorithm::BLAKE2S => blake2s::hasher(),
+        Algorithm::BLAKE3 => blake3::hasher(),
+        Algorithm::CRC64 => crc32_64::crc64::hasher(),
+        Algorithm::CRC32 => crc32_64::crc32::hasher(),
+        Algorithm::CRC32C => crc32c::hasher(),
+        Algorithm::CRC16 => crc16::hasher(),
+        Algorithm::CRC8 => crc8::hasher(),
+        Algorithm::MD5 => md5::hasher(),
+        Algorithm::MD6128 => md6128_256_512::md6128::hasher(),
+        Algorithm::MD6256 => md6128_256_512::md6256::hasher(),
+        Algorithm::MD6512 => md6128_256_512::md6512::hasher(),
+        Algorithm::WHIRLPOOL => whirlpool::hasher(),
+        Algorithm::XOR8 => xor8::hasher(),
     }
 }
 
+/// Get a fresh hasher for the specified hashing algorithm, keyed with the specified key,
+/// or the reason why the algorithm can't be keyed with it.
+///
+/// The SHA algorithms are turned into HMACs, with keys of any length,
+/// and BLAKE2B, BLAKE2S and BLAKE3 use their own keyed modes, with keys of up to 64, up to 32 and exactly 32 bytes, respectively.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::{Algorithm, keyed_hasher};
+/// let mut hasher = keyed_hasher(Algorithm::SHA2256, b"Jefe").unwrap();
+/// hasher.update(b"what do ya want for nothing?");
+/// assert_eq!(hasher.finish(), "5BDCC146BF60754E6A042426089575C75A003F089D2739839DEC58B964EC3843".to_string());
+///
+/// assert!(keyed_hasher(Algorithm::BLAKE3, b"Jefe").is_err());
+/// assert!(keyed_hasher(Algorithm::CRC32, b"Jefe").is_err());
+/// ```
+pub fn keyed_hasher(algo: Algorithm, key: &[u8]) -> Result<Box<dyn Hasher>, String> {
+    let hmac_block_size = match algo {
+        Algorithm::SHA1 | Algorithm::SHA2224 | Algorithm::SHA2256 => 64,
+        Algorithm::SHA2384 | Algorithm::SHA2512 => 128,
+        Algorithm::SHA3256 => 136,
+        Algorithm::SHA3512 => 72,
+        Algorithm::BLAKE2B if key.len() <= 64 => return Ok(blake2b::keyed_hasher(key)),
+        Algorithm::BLAKE2S if key.len() <= 32 => return Ok(blake2s::keyed_hasher(key)),
+        Algorithm::BLAKE3 if key.len() == 32 => return Ok(blake3::keyed_hasher(key)),
+        Algorithm::BLAKE2B => return Err(format!("{} needs a key of up to 64 bytes, not {}", algo, key.len())),
+        Algorithm::BLAKE2S => return Err(format!("{} needs a key of up to 32 bytes, not {}", algo, key.len())),
+        Algorithm::BLAKE3 => return Err(format!("{} needs a 32-byte key, not {}", algo, key.len())),
+        _ => return Err(format!("{} can't be keyed", algo)),
+    };
+
+    Ok(Box::new(HmacHasher::new(algo, hmac_block_size, key)))
+}
+
+/// Get an ID of the specified key, safe to record alongside the hashes made with it.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::key_id;
+/// assert_eq!(key_id(b"Jefe").len(), 16);
+/// assert_ne!(key_id(b"Jefe"), key_id(b"Jeff"));
+/// ```
+pub fn key_id(key: &[u8]) -> String {
+    hash_string(&::blake3::derive_key("checksums 2021-01-01 key ID", key)[..8])
+}
+
+/// Hash the specified file using the specified hashing algorithm.
+///
+/// Fails if the file couldn't be opened or read.
+pub fn hash_file(path: &Path, algo: Algorithm) -> io::Result<String> {
+    hash_reader(&mut File::open(path)?, algo)
+}
+
+/// Hash the specified file using all of the specified hashing algorithms, reading it only once.
+///
+/// Fails if the file couldn't be opened or read.
+pub fn hash_file_multiple(path: &Path, algos: &[Algorithm]) -> io::Result<BTreeMap<Algorithm, String>> {
+    hash_reader_multiple(&mut File::open(path)?, algos)
+}
+
+/// Hash the specified byte stream using the specified hashing algorithm.
+///
+/// Fails if reading from the stream fails.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::{Algorithm, hash_reader};
+/// assert_eq!(hash_reader(&mut &b"checksums"[..], Algorithm::CRC32).unwrap(), "0B0ADA8E".to_string());
+/// ```
+pub fn hash_reader<R: Read>(data: &mut R, algo: Algorithm) -> io::Result<String> {
+    hash_reader_multiple(data, &[algo]).map(|mut hashes| hashes.remove(&algo).unwrap())
+}
+
+/// Hash the specified byte stream using all of the specified hashing algorithms,
+/// feeding each chunk read to every one of them.
+///
+/// Fails if reading from the stream fails.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::{Algorithm, hash_reader_multiple};
+/// let hashes = hash_reader_multiple(&mut &b"checksums"[..], &[Algorithm::CRC32, Algorithm::CRC16]).unwrap();
+/// assert_eq!(hashes[&Algorithm::CRC32], "0B0ADA8E".to_string());
+/// assert_eq!(hashes[&Algorithm::CRC16], "0BC4".to_string());
+/// ```
+pub fn hash_reader_multiple<R: Read>(data: &mut R, algos: &[Algorithm]) -> io::Result<BTreeMap<Algorithm, String>> {
+    hash_reader_with(data, algos.iter().map(|&algo| (algo, hasher(algo))).collect())
+}
+
+/// Hash the specified file using all of the specified hashing algorithms keyed with the specified key, reading it only once.
+///
+/// Fails if the file couldn't be opened or read, or if any of the algorithms can't be keyed with the key.
+pub fn hash_file_multiple_keyed(path: &Path, algos: &[Algorithm], key: &[u8]) -> io::Result<BTreeMap<Algorithm, String>> {
+    hash_reader_multiple_keyed(&mut File::open(path)?, algos, key)
+}
+
+/// Hash the specified byte stream using all of the specified hashing algorithms keyed with the specified key,
+/// as made by `keyed_hasher()`.
+///
+/// Fails if reading from the stream fails, or if any of the algorithms can't be keyed with the key.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::{Algorithm, hash_reader_multiple_keyed};
+/// let hashes = hash_reader_multiple_keyed(&mut &b"what do ya want for nothing?"[..], &[Algorithm::SHA1], b"Jefe").unwrap();
+/// assert_eq!(hashes[&Algorithm::SHA1], "EFFCDF6AE5EB2FA2D27416D5F184DF9C259A7C79".to_string());
+///
+/// assert!(hash_reader_multiple_keyed(&mut &b""[..], &[Algorithm::SHA1, Algorithm::CRC32], b"Jefe").is_err());
+/// ```
+pub fn hash_reader_multiple_keyed<R: Read>(data: &mut R, algos: &[Algorithm], key: &[u8]) -> io::Result<BTreeMap<Algorithm, String>> {
+    let hashers = algos.iter()
+        .map(|&algo| keyed_hasher(algo, key).map(|hasher| (algo, hasher)))
+        .collect::<Result<_, _>>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    hash_reader_with(data, hashers)
+}
+
 /// Create a hash string out of its raw bytes.
 ///
 /// # Examples
@@ -106,3 +288,27 @@ pub fn hash_string(bytes: &[u8]) -> String {
     }
     result
 }
+
+
+fn hash_reader_with<R: Read>(data: &mut R, mut hashers: Vec<(Algorithm, Box<dyn Hasher>)>) -> io::Result<BTreeMap<Algorithm, String>> {
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let read = match data.read(&mut buffer[..]) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        for &mut (_, ref mut hasher) in &mut hashers {
+            hasher.update(&buffer[..read]);
+        }
+    }
+
+    Ok(hashers.into_iter().map(|(algo, hasher)| (algo, hasher.finish())).collect())
+}
+
+fn hash_bytes(hash: &str) -> Vec<u8> {
+    (0..hash.len()).step_by(2).map(|i| u8::from_str_radix(&hash[i..i + 2], 16).unwrap()).collect()
+}
@@ -24,21 +24,62 @@
 //! |> write_hashes()
 //! ```
 //!
+//! Duplicate finding
+//!
+//! ```plaintext
+//! Options
+//! |> find_duplicates()
+//! |> link_duplicates()
+//! |> write_duplicates()
+//! ```
+//!
+//! Hashes file comparison
+//!
+//! ```plaintext
+//! Options
+//! |> read_hashes()
+//! |> retain_common_algorithms()
+//! |> compare_hashes()
+//! |> write_hash_comparison_results()
+//! ```
+//!
+//! Directory tree comparison
+//!
+//! ```plaintext
+//! Options
+//! |> create_sizes()
+//! |> compare_sizes()
+//! |> create_hashes()
+//! |> compare_hashes()
+//! |> write_hash_comparison_results()
+//! ```
+//!
 //! # Executable manpage
 //!
 //! Exit values and possible errors:
 //!
 //! ```text
-//! 1   - option parsing error
-//! 2   - hash lengths differ between selected and saved
-//! 3   - failed to parse hashes file
-//! N+3 - N files didn't match
+//! 1     - option parsing error, or selected algorithm or key not in hashes file header
+//! 2     - hash lengths differ between selected and saved
+//! 3     - failed to parse hashes file
+//! N+3   - N files didn't match, up to 99
+//! 100   - failed to read or write hashes file
+//! N+100 - N files couldn't be read or linked, none didn't match, up to 199
+//! 200   - hashes file signature missing or doesn't match
 //! ```
 //!
 //! ## SYNOPSIS
 //!
 //! [`checksums`](https://github.com/nabijaczleweli/checksums) [OPTIONS] [DIRECTORY]
 //!
+//! [`checksums`](https://github.com/nabijaczleweli/checksums) [OPTIONS] &lt;FILE&gt;...
+//!
+//! [`checksums`](https://github.com/nabijaczleweli/checksums) dupes [OPTIONS] [DIRECTORY]
+//!
+//! [`checksums`](https://github.com/nabijaczleweli/checksums) diff [OPTIONS] &lt;OLD&gt; &lt;NEW&gt;
+//!
+//! [`checksums`](https://github.com/nabijaczleweli/checksums) compare [OPTIONS] &lt;DIR_A&gt; &lt;DIR_B&gt;
+//!
 //! ## DESCRIPTION
 //!
 //! Tool for making/verifying checksums of directory trees.
@@ -53,13 +94,72 @@
 //! -a --algorithm &lt;algorithm&gt;
 //!
 //! ```text
-//! Set the hashing algorithm to use, case-insensitive.
+//! Set the hashing algorithm(s) to use, case-insensitive. Default: SHA1.
 //!
 //! Supported algorithms: SHA1, SHA2-256, SHA2-512, SHA3-256, SHA3-512, BLAKE,
 //!                       BLAKE2B, BLAKE2S, BLAKE3, CRC8, CRC16, CRC32, CRC64,
 //!                       MD5, MD6-128, MD6-256, MD6-512, XOR8
 //!
 //! BLAKE2 is equivalent to BLAKE2B for compatibility.
+//!
+//! Multiple algorithms can be specified, comma-separated or by repeating the
+//! option; each file is read once and hashed with all of them, in a column or
+//! line per algorithm.
+//!
+//! When verifying, untagged lines are read as holding a hash for each
+//! specified algorithm, in order. Tagged lines are checked with all of their
+//! algorithms, or only the specified ones, if any were.
+//!
+//! Created hashes files start with a "# checksums" header line recording the
+//! algorithms and other parameters; when verifying, untagged lines are read
+//! with the algorithms from the header instead, and specifying any algorithm
+//! not in it is an error.
+//! ```
+//!
+//! --format &lt;format&gt;
+//!
+//! ```text
+//! Set the layout of the created hashes file, case-insensitive. Default: checksums.
+//!
+//! Supported formats: checksums - "name  HASH", aligned with spaces,
+//!                    coreutils - "hash  name", as read by sha256sum -c et al.,
+//!                    bsd       - "ALGO (name) = hash", as read by shasum -c.
+//!
+//! The layout of a verified hashes file is detected automatically.
+//!
+//! Lines in the bsd format record their algorithm, overriding `--algorithm`,
+//! so files hashed with different algorithms can be verified together.
+//!
+//! The coreutils format can only hold a single algorithm.
+//!
+//! Ignored files are only recorded in the checksums format, as placeholders,
+//! since the other formats have no way to mark them.
+//!
+//! In all formats, names containing backslashes, CRs, LFs or bytes that aren't
+//! valid UTF-8 are escaped like coreutils do, as "\\", "\r" and "\n", and "\xHH"
+//! for each invalid byte, and the line is prefixed with a backslash.
+//! In the checksums format, so are names containing tabs, as "\t", ending with
+//! whitespace, escaped as "\xHH", or starting with "#".
+//! coreutils' *sum programs don't understand "\xHH" escapes.
+//!
+//! Verification results show such names with invalid bytes replaced with "�".
+//! ```
+//!
+//! --output-format &lt;format&gt;
+//!
+//! ```text
+//! Set the presentation of verification results, case-insensitive.
+//! Default: text.
+//!
+//! Supported formats: text   - human-readable, wrapped to 80 columns,
+//!                    json   - a single JSON object with "results" and
+//!                             "summary" or "error",
+//!                    ndjson - one JSON object per result, each on its own
+//!                             line, then "summary" or "error", all written
+//!                             once the whole tree's been checked.
+//!
+//! The progress bar is written to stderr with json and ndjson.
+//! No meaning in `--create` and `--update` modes.
 //! ```
 //!
 //! -c --create
@@ -72,7 +172,10 @@
 //!
 //! Will fail if the output file already exists and `--force` is not specified.
 //!
-//! Exclusive with `--verify`. Overrides `--verify`.
+//! The hashes are written to ".`FILE`.checksums-tmp" beside the output file first,
+//! which only replaces it once complete, so an interrupted run leaves it as it was.
+//!
+//! Exclusive with `--verify` and `--update`. Overrides them.
 //! ```
 //!
 //! -v --verify
@@ -80,13 +183,61 @@
 //! ```text
 //! Verify directory hashes. Default.
 //!
-//! Exclusive with `--create`. Overrides `--create`.
+//! The recursion depth and symlink following recorded in the input file are
+//! used, unless `--depth`, `--recursive` or `--[no-]follow-symlinks` are specified.
+//!
+//! Exclusive with `--create` and `--update`. Overrides them.
+//! ```
+//!
+//! -u --update
+//!
+//! ```text
+//! Update directory hashes in the output file, rather than verifying them.
+//!
+//! Files added since are hashed and added, removed ones dropped, and moved ones
+//! renamed; files that changed keep their saved hashes unless
+//! `--rehash-changed` is specified. Ignore placeholders are always kept.
+//! The format and algorithms of the output file are kept, too, as are the
+//! recursion depth and symlink following recorded in it, unless `--depth`,
+//! `--recursive` or `--[no-]follow-symlinks` are specified.
+//!
+//! The changes are written out, followed by a summary.
+//!
+//! Exclusive with `--create` and `--verify`. Overrides them.
+//! ```
+//!
+//! --rehash-changed
+//!
+//! ```text
+//! Save the current hashes of files that changed in `--update` mode.
+//!
+//! Without it, changed files count as not matching.
+//! ```
+//!
+//! --listed-only
+//!
+//! ```text
+//! Verify only the files listed in the input file, instead of walking
+//! `DIRECTORY`, which is much faster if they're a small part of it.
+//!
+//! Files that don't exist anymore are reported as removed.
+//! `--depth` and ignore files have no effect.
+//!
+//! Exclusive with `--create` and `--update`.
+//! ```
+//!
+//! --report-unlisted
+//!
+//! ```text
+//! With `--listed-only`, also walk `DIRECTORY` and report the files not in the
+//! input file as added, without reading them.
 //! ```
 //!
 //! -d --depth &lt;depth&gt;
 //!
 //! ```text
-//! Set max recursion depth to `depth`. Default: 0.
+//! Set max recursion depth to `depth`. Default: 0, or the one recorded in the
+//! input file when verifying or updating.
 //!
 //! Exclusive with `--recursive`. Overrides `--recursive`.
 //! ```
@@ -111,16 +262,56 @@
 //! Don't recurse down symlinks.
 //! ```
 //!
-//! -i --ignore &lt;filename[,filename2][,filename3][,filenameN]...&gt;...
+//! -i --ignore &lt;pattern&gt;...
+//!
+//! ```text
+//! Ignore files and directories matching gitignore-style pattern(s).
+//! Default: none.
+//!
+//! Patterns containing a non-trailing slash are relative to DIRECTORY, others
+//! match at any level; a trailing slash matches only directories, "**" matches
+//! any number of directories, and a leading "!" re-includes previously ignored
+//! paths.
+//!
+//! Ignored files are marked as such, ignored directories aren't descended into.
+//!
+//! Accepted multiple times.
+//! ```
+//!
+//! --exclude-from &lt;file&gt;
+//!
+//! ```text
+//! Ignore files and directories matching the patterns in file, one per line,
+//! as in a .gitignore file. Default: none.
+//!
+//! Applied before the patterns from `--ignore`.
+//!
+//! Accepted multiple times.
+//! ```
+//!
+//! --include &lt;pattern&gt;
 //!
 //! ```text
-//! Add filename(s) to ignored files list. Default: none.
+//! Only hash files matching gitignore-style pattern(s), or in directories
+//! matching them. Default: all files.
 //!
-//! Ignored files are marked as such.
+//! Other files are omitted entirely.
 //!
 //! Accepted multiple times.
 //! ```
 //!
+//! --ignore-files
+//!
+//! ```text
+//! Honour .gitignore, .ignore and .checksumsignore files in each walked
+//! directory, in ascending order of precedence.
+//!
+//! Patterns in deeper directories take precedence over ones in their parents,
+//! and `--ignore` and `--exclude-from` patterns over all of them.
+//!
+//! Specify this when verifying as well, like `--ignore`.
+//! ```
+//!
 //! --force
 //!
 //! ```text
@@ -138,10 +329,262 @@
 //! No/empty value: # of CPU threads. -1: Infinite
 //! ```
 //!
+//! --cache [file]
+//!
+//! ```text
+//! Keep a cache of file sizes, modification and status change times, inode
+//! numbers and hashes in `file`. Default: none, "`outfile`.cache" if specified
+//! without a value.
+//!
+//! Files whose metadata didn't change since the last `--create` are not read,
+//! and their cached hashes are used instead.
+//!
+//! Only used in `--create` mode; `--verify` always reads every file.
+//! ```
+//!
+//! --paranoid
+//!
+//! ```text
+//! Read every file, even if it's unchanged according to the `--cache`.
+//!
+//! The cache is still updated.
+//! ```
+//!
+//! --directories
+//!
+//! ```text
+//! Also record directories, including empty ones, in the hashes file, and report
+//! added and removed ones.
+//!
+//! Directories are recorded on "# directory" lines after the hashes, which
+//! coreutils' *sum programs skip, and the header notes they were recorded.
+//! All directories that aren't ignored are, even if `--include` leaves out
+//! everything in them.
+//! Verifying and updating check them if they were recorded or this is specified.
+//!
+//! Added and removed directories aren't mismatches.
+//! ```
+//!
+//! --tree-digests
+//!
+//! ```text
+//! Also record a digest of each directory in the hashes file, Merkle tree-style,
+//! and print the one of the whole tree, so two trees can be compared by a single
+//! value. Verifying and `diff` report the directories whose digests differ, made
+//! from the other file's hashes if only one of the files recorded them.
+//!
+//! A directory's digest is the hash, with the first algorithm, of, for each of
+//! its children, in name order: its name, followed by a "/" for directories,
+//! a NUL, its hash or digest, and a LF. Ignored files aren't included, and only
+//! directories with hashed files in them have digests.
+//!
+//! Digests are recorded on "# digest" lines after the hashes, which coreutils'
+//! *sum programs skip, the whole tree's under ".". Updating records them again
+//! if they were recorded.
+//! ```
+//!
+//! --metadata [kinds]
+//!
+//! ```text
+//! Also record file metadata in the hashes file, and report changes to it
+//! separately from content changes, comma-separated, case-insensitive.
+//! No or empty value: all of them.
+//!
+//! Supported kinds: mode   - permission bits, in octal,
+//!                  owner  - "uid:gid",
+//!                  mtime  - modification time,
+//!                  target - where a symlink points,
+//!                  xattrs - count and hash of extended attributes.
+//!
+//! Metadata is recorded on "# metadata" lines after the hashes, which coreutils'
+//! *sum programs skip. The files symlinks point to are described, unless
+//! `--no-follow-symlinks` is specified, in which case symlinks are recorded too,
+//! but only checked for changed metadata, not reported as added or removed.
+//!
+//! Verifying and updating use the kinds recorded in the hashes file by default.
+//! Use with `=` or before `DIRECTORY` when specified without a value.
+//! ```
+//!
+//! --check &lt;digest&gt;
+//!
+//! ```text
+//! Check the single file given as DIRECTORY, or standard input if "-", against
+//! `digest`, as hashed with the first `--algorithm`, instead of printing it.
+//!
+//! The result is written like when verifying, in `--output-format`, and exits
+//! with 4 if the file doesn't match.
+//! ```
+//!
+//! --archive
+//!
+//! ```text
+//! Hash the members of the tar, tar.gz or zip archive `DIRECTORY`, recognised by
+//! its extension, as described below, instead of the archive itself.
+//! ```
+//!
+//! --nested-archives
+//!
+//! ```text
+//! With `--archive`, walk the tar, tar.gz and zip archives inside `DIRECTORY`
+//! like directories, naming their members "`inner.tar`/`member`", instead of
+//! hashing them. Nested zip archives are copied to the temporary directory first.
+//! ```
+//!
+//! --key-file &lt;file&gt;
+//!
+//! ```text
+//! Make keyed hashes, with the contents of `file`, byte-for-byte, as the key.
+//!
+//! The SHA algorithms are turned into HMACs, and BLAKE2B, BLAKE2S and BLAKE3 use
+//! their own keyed modes, with keys of up to 64, up to 32 and exactly 32 bytes,
+//! respectively. Other algorithms can't be keyed.
+//!
+//! An ID of the key is recorded in the hashes file header; verifying or
+//! updating it requires the same key, and fails with 1 without it.
+//!
+//! Exclusive with `--cache`.
+//! ```
+//!
+//! --sign &lt;key&gt;
+//!
+//! ```text
+//! Sign the created or updated hashes file with the minisign secret key in
+//! `key`, saving the signature next to it, as "`outfile`.minisig".
+//!
+//! The key must not be password-protected, as made by `minisign -G -W`.
+//! The signature can be checked with `minisign -V` as well as
+//! `--verify-signature`.
+//!
+//! The signature file is marked as ignored in the hashes file.
+//!
+//! Only used in `--create` and `--update` modes.
+//! ```
+//!
+//! --verify-signature &lt;key&gt;
+//!
+//! ```text
+//! Check the hashes file's signature, "`infile`.minisig", against the minisign
+//! public key in `key`, before verifying or updating.
+//!
+//! Fails with 200 if the signature is missing or doesn't match.
+//!
+//! Not used in `--create` mode.
+//! ```
+//!
 //! [DIRECTORY]
 //!
 //! ```text
 //! Directory to create/verify hash for. Default: current workdir.
+//!
+//! Files, or "-" for standard input, are instead hashed on their own, and their
+//! hashes printed one per line in `--format`, with the name as given, without
+//! a hashes file. Multiple files can be given, but not alongside a directory.
+//!
+//! With `--archive`, can also be a tar, tar.gz or zip archive, whose members are
+//! hashed as if it was a directory they were extracted to, without extracting
+//! them, so they can be checked against hashes of the original tree. The default
+//! hashes file is then "`DIRECTORY`.hash", beside it. Without it, archives are
+//! hashed on their own, like other files.
+//!
+//! Members are read one after another, and written in the order a directory's
+//! files would be once all are. Regular files are hashed, hardlinks get the hashes
+//! of what they link to, and symlinks aren't followed. Members with
+//! paths leading outside the archive are reported as unreadable. Metadata is
+//! what the archive recorded: owners only in tarballs, modification times to the
+//! second, and in zip archives only from extended timestamps, since DOS ones are
+//! in local time, and no extended attributes. Directories are recorded even if only
+//! their contents have members. Ignore files in the archive aren't honoured.
+//!
+//! Exclusive with `--cache` and `--listed-only`.
+//! ```
+//!
+//! ## SUBCOMMANDS
+//!
+//! dupes [OPTIONS] [DIRECTORY]
+//!
+//! ```text
+//! Find groups of identical files in `DIRECTORY`, instead of making or checking
+//! hashes, reading as little of them as possible: only files of the same size
+//! have their first 4 KiB hashed, and only ones whose first 4 KiB match are
+//! hashed whole.
+//!
+//! Hardlinks to the same file are read once, listed together, and not counted
+//! as duplicates of each other. Empty files are skipped.
+//!
+//! Takes `--depth`, `--recursive`, `--follow-symlinks`, `--no-follow-symlinks`,
+//! `--ignore`, `--exclude-from`, `--include`, `--ignore-files` and `--jobs`,
+//! specified after "dupes", as well as:
+//!
+//!   -a --algorithm <algorithm> - the one algorithm to tell files apart with.
+//!                                Default: BLAKE3.
+//!   --output-format <format>   - text, json or ndjson, as above.
+//!                                Default: text.
+//!   --link <kind>              - replace each file with a link to the first
+//!                                one in its group, after comparing them
+//!                                byte-for-byte.
+//!
+//! Supported link kinds: hard    - hardlinks, which share the first file's
+//!                                 metadata,
+//!                       reflink - copy-on-write clones, which keep their
+//!                                 permissions, on filesystems that support
+//!                                 them, like Btrfs and XFS.
+//!
+//! Files are linked via a temporary name beside them, so ones that can't be
+//! linked are left as they were. Files that couldn't be read or linked count
+//! towards the N+100 exit value.
+//!
+//! A directory named "dupes" has to be specified as "./dupes".
+//! ```
+//!
+//! diff [OPTIONS] &lt;OLD&gt; &lt;NEW&gt;
+//!
+//! ```text
+//! Compare two hashes files with each other, without reading the files they
+//! list, e.g. on a machine without them: `OLD` is checked against like when
+//! verifying, and `NEW` takes the place of the current hashes. The files can be
+//! in different formats.
+//!
+//! Added, removed, moved, copied and changed files are reported like when
+//! verifying, as are directories if both files recorded them, and metadata that
+//! both files recorded. Each file's own entry is skipped.
+//!
+//! Only the algorithms both files have are compared. Fails with 1 if they have
+//! none in common, or were made with different `--key-file`s, or only one of them
+//! with one.
+//!
+//! Takes `--output-format`, specified after "diff", as well as:
+//!
+//!   -a --algorithm <algorithm>... - the algorithms of lines not specifying
+//!                                   theirs in files without a header.
+//!                                   Default: SHA1.
+//!
+//! A directory named "diff" has to be specified as "./diff".
+//! ```
+//!
+//! compare [OPTIONS] &lt;DIR_A&gt; &lt;DIR_B&gt;
+//!
+//! ```text
+//! Compare two directory trees with each other, without a hashes file, e.g. to
+//! check a copy or a mirror: `DIR_A` takes the place of the saved hashes, and
+//! `DIR_B` of the current ones, so files only in `DIR_B` are reported as added.
+//!
+//! Both trees are hashed at once, splitting the `--jobs` threads between them, or
+//! one after the other with a single job; only the progress of `DIR_A` is shown.
+//!
+//! Takes `--depth`, `--recursive`, `--follow-symlinks`, `--no-follow-symlinks`,
+//! `--ignore`, `--exclude-from`, `--include`, `--ignore-files` and `--jobs`,
+//! specified after "compare", as well as:
+//!
+//!   -a --algorithm <algorithm>... - the algorithms to hash both trees with.
+//!                                   Default: SHA1.
+//!   --key-file <key-file>         - key the hashes of both trees, as above.
+//!   --output-format <format>      - text, json or ndjson, as above.
+//!                                   Default: text.
+//!   --size-first                  - compare file sizes first, and don't hash
+//!                                   files whose sizes differ, reporting them
+//!                                   as not matching right away.
+//!
+//! A directory named "compare" has to be specified as "./compare".
 //! ```
 //!
 //! ## EXAMPLES
@@ -155,15 +598,27 @@
 //!
 //! *infile* defaults to "`DIRECTORY`.hash"
 //!
+//! Files whose contents were saved under another name are reported as moved, or
+//! as copied if the original is still present; neither counts as a mismatch.
+//!
 //! Example output:
 //!   File added: "file_that_was_not_here_before"
 //!   File removed: "file_that_was_here_before_but_not_now"
+//!   File moved: "old_name"
+//!     to: "new_name"
+//!   File copied: "copy_of_file"
+//!     from: "file_that_did_not_change"
 //!   File ignored: "file_specified_with_ignore_now_or_during_creation"
+//!   Directory added: "directory_that_was_not_here_before"
+//!   Directory removed: "empty_directory_that_was_here_before"
 //!
 //!   File "file_that_did_not_change" matches
 //!   File "changed_file" doesn't match
 //!     Was: 8313958F86F7B15D4775D12886D479C1CFAAA111
 //!     Is : FCFC1548B30B5ACB25A7421D068E12F07DF74DCC
+//!   File "file_made_executable" mode changed
+//!     Was: 644
+//!     Is : 755
 //! ```
 //!
 //! `examples` `-c` [`-f` *outfile*] [`--force`]
@@ -195,6 +650,82 @@
 //!   File "file" matches
 //! ```
 //!
+//! `checksums` `dupes` [`-r`] [`--link` *kind*]
+//!
+//! ```text
+//! Find identical files in the current directory tree, biggest first, and
+//! replace them with links of *kind*, if specified.
+//!
+//! Example output:
+//!   Identical files, 10000 bytes each:
+//!     Hash: 0543C1B0843CCE6798C428FF81DBF8E29EC0871A7160925E53E8F2019D03BBDE
+//!     "big_file"
+//!     "copy_of_big_file"
+//!     Same file: "hardlink_to_copy_of_big_file"
+//!
+//!   1 groups, 10000 bytes duplicated, 0 files linked
+//! ```
+//!
+//! `checksums` `diff` *old.hash* *new.hash*
+//!
+//! ```text
+//! Compare last month's hashes of a tree with this month's.
+//!
+//! Example output:
+//!   File added: "new_file"
+//!   File moved: "old_name"
+//!     to: "new_name"
+//!
+//!   File "file_that_did_not_change" matches
+//!   File "changed_file" doesn't match
+//!     Was: 8313958F86F7B15D4775D12886D479C1CFAAA111
+//!     Is : FCFC1548B30B5ACB25A7421D068E12F07DF74DCC
+//! ```
+//!
+//! `checksums` `compare` [`--size-first`] . *backup*
+//!
+//! ```text
+//! Check that a backup is a faithful copy of the current directory tree.
+//!
+//! Example output:
+//!   File removed: "file_not_copied_yet"
+//!
+//!   File "file_that_was_copied" matches
+//!   File "truncated_file" doesn't match
+//!     Was: 10000 bytes
+//!     Is : 4096 bytes
+//! ```
+//!
+//! `checksums` `-r` `--archive` `-f` *tree.hash* *release.tar.gz*
+//!
+//! ```text
+//! Verify a tarball against the hashes of the tree it was made from, without
+//! extracting it.
+//!
+//! Example output:
+//!   File "README.md" matches
+//!   File "src/main.rs" matches
+//! ```
+//!
+//! `checksums` `-a` BLAKE3 `--format` coreutils *file* `-`
+//!
+//! ```text
+//! Print the BLAKE3 hashes of file and of standard input, as b3sum would.
+//!
+//! Example output:
+//!   8c5b2bd6ab03a8ba6f53b2c1a6f0c8ab8b4e0b97dc4be8fbd5e4b0d8a5a7f1e2  file
+//!   af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262  -
+//! ```
+//!
+//! `checksums` `-a` SHA2-256 `--check` *digest* *image.iso*
+//!
+//! ```text
+//! Check a downloaded image against its published SHA2-256 digest.
+//!
+//! Example output:
+//!   File "image.iso" matches
+//! ```
+//!
 //! # Special thanks
 //!
 //! To all who support further development on [Patreon](https://patreon.com/nabijaczleweli), in particular:
@@ -204,6 +735,8 @@
 //!   * Jasper Bekkers
 
 
+#[macro_use]
+extern crate serde_json;
 extern crate md5;
 extern crate md6;
 extern crate pbr;
@@ -214,18 +747,25 @@ extern crate crc8;
 extern crate crc16;
 extern crate blake;
 extern crate regex;
+extern crate reflink_copy;
+extern crate flate2;
 extern crate blake2;
 extern crate blake3;
+extern crate base64;
 extern crate crc32c;
 extern crate shaman;
-extern crate futures;
 extern crate walkdir;
+extern crate ignore;
 extern crate num_cpus;
 extern crate once_cell;
 extern crate tabwriter;
 extern crate whirlpool;
 extern crate tiny_keccak;
-extern crate futures_cpupool;
+extern crate tar;
+extern crate zip;
+#[cfg(unix)]
+extern crate xattr;
+extern crate ed25519_dalek;
 
 mod error;
 mod hashing;
@@ -237,5 +777,5 @@ pub mod util;
 
 pub use hashing::*;
 pub use error::Error;
-pub use options::Options;
+pub use options::{Options, Subcommand};
 pub use algorithms::Algorithm;
@@ -1,7 +1,26 @@
 extern crate checksums;
 
+use std::collections::{BTreeSet, BTreeMap};
 use std::process::exit;
-use std::io::{stdout, stderr};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::mem;
+use std::io::{self, Write, stdout, stderr};
+
+
+/// The state of the tree a hashes file is verified or updated against.
+struct Found {
+    hashes: checksums::ops::Hashes,
+    /// Reasons files couldn't be hashed.
+    errors: BTreeMap<OsString, io::Error>,
+    metadata: checksums::ops::Metadata,
+    /// Reasons the metadata of files couldn't be read.
+    metadata_errors: BTreeMap<OsString, io::Error>,
+    directories: BTreeSet<OsString>,
+    /// Files not in the hashes file, found with `--report-unlisted`.
+    unlisted: Vec<OsString>,
+}
 
 
 fn main() {
@@ -11,28 +30,680 @@ fn main() {
 
 fn actual_main() -> i32 {
     let opts = checksums::Options::parse();
+    let mut filter = match checksums::ops::FileFilter::new(&opts.dir, &opts.ignored_files, &opts.included_files, opts.ignore_files) {
+        Ok(filter) => filter,
+        Err(error) => {
+            eprintln!("{}", error);
+            return checksums::Error::OptionParsingError.exit_value();
+        }
+    };
+
+    match opts.subcommand {
+        Some(checksums::Subcommand::Dupes { link }) => return dupes_main(&opts, &filter, link),
+        Some(checksums::Subcommand::Diff { ref old, ref new }) => return diff_main(&opts, old, new),
+        Some(checksums::Subcommand::Compare { ref other, size_first }) => return compare_main(&opts, filter, other, size_first),
+        None => {}
+    }
+    if let Some(ref inputs) = opts.inputs {
+        return inputs_main(&opts, inputs);
+    }
+
+    let sign_key = match opts.sign {
+        Some(ref key_file) => {
+            match checksums::ops::SecretKey::read(key_file) {
+                Ok(key) => Some(key),
+                Err(error) => {
+                    eprintln!("{}: {}", key_file.display(), error);
+                    return checksums::Error::OptionParsingError.exit_value();
+                }
+            }
+        }
+        None => None,
+    };
+    let signature_file = checksums::ops::signature_file(&opts.file);
+    if sign_key.is_some() || opts.verify_signature.is_some() {
+        filter.ignore_path(signature_file.1.clone());
+    }
+
+    if opts.verify || opts.update {
+        verify_main(&opts, &filter, sign_key.as_ref(), &signature_file)
+    } else {
+        create_main(&opts, filter, sign_key.as_ref(), &signature_file)
+    }
+}
+
+fn create_main(opts: &checksums::Options, mut filter: checksums::ops::FileFilter, sign_key: Option<&checksums::ops::SecretKey>,
+               signature_file: &(OsString, PathBuf))
+               -> i32 {
+    let mut cache = match opts.cache {
+        Some(ref cache_file) if !opts.paranoid => {
+            match checksums::ops::read_cache(&cache_file.1) {
+                Ok(cache) => Some(cache),
+                Err(error) => {
+                    eprintln!("{}: {}", cache_file.0.to_string_lossy(), error);
+                    return checksums::Error::HashesFileIoFailure.exit_value();
+                }
+            }
+        }
+        Some(_) => Some(BTreeMap::new()),
+        None => None,
+    };
+
+    if !check_key(opts.key.as_deref(), &opts.algorithms) {
+        return checksums::Error::OptionParsingError.exit_value();
+    }
+
+    let header = checksums::ops::HashesHeader {
+        key_id: opts.key.as_ref().map(|key| checksums::key_id(key)),
+        directories: opts.directories,
+        ..checksums::ops::HashesHeader::new(opts.algorithms.clone(), opts.depth, opts.follow_symlinks)
+    };
+    let mut out = match checksums::ops::HashesWriter::create(&opts.file, &header, opts.format) {
+        Ok(out) => out,
+        Err(error) => {
+            eprintln!("{}: {}", opts.file.0.to_string_lossy(), error);
+            return checksums::Error::HashesFileIoFailure.exit_value();
+        }
+    };
+
+    // Don't read the output and cache files while they're being written, and mark the cache as ignored even if it didn't exist yet
+    filter.ignore_path(opts.file.1.clone());
+    filter.ignore_path(checksums::ops::hashes_temp_file(&opts.file).1);
+    let dir = &opts.dir;
+    if let Some(cache_file) = opts.cache.as_ref().filter(|c| c.1.starts_with(dir)) {
+        filter.ignore_path(cache_file.1.clone());
+        out.mark_ignored(checksums::util::relative_name(&opts.dir, &cache_file.1));
+    }
+    if sign_key.is_some() && signature_file.1.starts_with(dir) {
+        out.mark_ignored(checksums::util::relative_name(&opts.dir, &signature_file.1));
+    }
+
+    let mut errors = BTreeMap::new();
+    let mut write_result = Ok(());
+    // Archive members come in archive order, so they're only written and digested once they're all in, in walk order
+    let mut digester = if opts.tree_digests && opts.archive.is_none() {
+        Some(checksums::ops::TreeDigester::new(opts.algorithms[0]))
+    } else {
+        None
+    };
+    let mut archive_hashes = BTreeMap::new();
+    let sink = |fname: OsString, result: io::Result<_>| match result {
+        Ok(hashes) => {
+            if opts.archive.is_some() {
+                archive_hashes.insert(fname, hashes);
+            } else {
+                if let Some(ref mut digester) = digester {
+                    digester.add(&fname, &hashes);
+                }
+                if write_result.is_ok() {
+                    write_result = out.write(&fname, &hashes);
+                }
+            }
+        }
+        Err(error) => {
+            errors.insert(fname, error);
+        }
+    };
+    match opts.archive {
+        Some(archive) => {
+            checksums::ops::stream_archive_hashes(&opts.dir, archive, &opts.algorithms, opts.key.as_deref(), walk_options(opts, &filter), stdout(), sink)
+        }
+        None => {
+            checksums::ops::stream_hashes(&opts.dir,
+                                          &opts.algorithms,
+                                          opts.key.as_deref(),
+                                          checksums::ops::WalkOptions { cache: cache.as_mut(), ..walk_options(opts, &filter) },
+                                          stdout(),
+                                          sink)
+        }
+    }
+    let mut archive_files: Vec<_> = archive_hashes.iter().collect();
+    archive_files.sort_by(|l, r| Path::new(l.0).cmp(Path::new(r.0)));
+    for (fname, hashes) in archive_files {
+        if write_result.is_ok() {
+            write_result = out.write(fname, hashes);
+        }
+    }
+    if opts.directories {
+        let directories = match opts.archive {
+            Some(archive) => checksums::ops::create_archive_directories(&opts.dir, archive, &filter, opts.depth, opts.nested_archives),
+            None => checksums::ops::create_directories(&opts.dir, &filter, opts.depth, opts.follow_symlinks),
+        };
+        for dname in directories {
+            if write_result.is_ok() {
+                write_result = out.write_directory(&dname);
+            }
+        }
+    }
+    let digests = match digester {
+        Some(digester) => Some(digester.finish()),
+        None if opts.tree_digests => Some(checksums::ops::tree_digests(&archive_hashes, opts.algorithms[0])),
+        None => None,
+    };
+    let root_digest = digests.map(|digests| {
+        for (dname, digest) in &digests {
+            if write_result.is_ok() {
+                write_result = out.write_digest(dname, opts.algorithms[0], digest);
+            }
+        }
+        digests[OsStr::new(".")].clone()
+    });
+    if let Some(ref kinds) = opts.metadata {
+        let (metadata, metadata_errors) = match opts.archive {
+            Some(archive) => checksums::ops::create_archive_metadata(&opts.dir, archive, &filter, kinds, opts.depth, opts.nested_archives),
+            None => checksums::ops::create_metadata(&opts.dir, &filter, kinds, opts.depth, opts.follow_symlinks),
+        };
+        for (file, error) in metadata_errors {
+            errors.entry(file).or_insert(error);
+        }
+        for (fname, values) in &metadata {
+            if write_result.is_ok() {
+                write_result = out.write_metadata(fname, values);
+            }
+        }
+    }
+    let mut result = checksums::ops::write_hashing_errors(&mut stderr(), &errors);
+
+    if let Err(error) = write_result.and_then(|_| out.finish()) {
+        eprintln!("{}: {}", opts.file.0.to_string_lossy(), error);
+        result = checksums::Error::HashesFileIoFailure;
+    } else if let Some(key) = sign_key {
+        if let Err(error) = checksums::ops::sign_hashes(&opts.file, key) {
+            eprintln!("{}: {}", signature_file.0.to_string_lossy(), error);
+            result = checksums::Error::HashesFileIoFailure;
+        }
+    }
+    if let Some(root_digest) = root_digest {
+        println!("\nRoot digest: {} {}", opts.algorithms[0], root_digest);
+    }
+    if let (Some(cache_file), Some(cache)) = (opts.cache.as_ref(), cache) {
+        if let Err(error) = checksums::ops::write_cache(&cache_file.1, &cache) {
+            eprintln!("{}: {}", cache_file.0.to_string_lossy(), error);
+            result = checksums::Error::HashesFileIoFailure;
+        }
+    }
+    result.exit_value()
+}
+
+fn verify_main(opts: &checksums::Options, filter: &checksums::ops::FileFilter, sign_key: Option<&checksums::ops::SecretKey>,
+               signature_file: &(OsString, PathBuf))
+               -> i32 {
+    if let Some(ref key_file) = opts.verify_signature {
+        let key = match checksums::ops::PublicKey::read(key_file) {
+            Ok(key) => key,
+            Err(error) => {
+                eprintln!("{}: {}", key_file.display(), error);
+                return checksums::Error::OptionParsingError.exit_value();
+            }
+        };
+        if let Err(error) = checksums::ops::verify_hashes_signature(&mut stderr(), &opts.file, &key) {
+            return error.exit_value();
+        }
+    }
+
+    let mut loaded = match checksums::ops::read_hashes_file(&mut stderr(), &opts.file, &opts.algorithms) {
+        Ok(loaded) => loaded,
+        Err(error) => return error.exit_value(),
+    };
+    if let Some(ref header) = loaded.header {
+        if !check_header(opts, header) {
+            return checksums::Error::OptionParsingError.exit_value();
+        }
+    }
+
+    // Check only the chosen subset of the algorithms in the hashes file
+    if opts.verify && opts.algorithms_specified {
+        for file_hashes in loaded.hashes.values_mut() {
+            file_hashes.retain(|algo, _| opts.algorithms.contains(algo));
+        }
+    }
+
+    // Keep the algorithm order of the header, so the untagged hashes still line up with it
+    let loaded_algos: BTreeSet<_> = loaded.hashes.values().flat_map(|h| h.keys().cloned()).collect();
+    let mut algos: Vec<_> = loaded.header.iter().flat_map(|h| h.algorithms.iter().cloned()).filter(|algo| loaded_algos.contains(algo)).collect();
+    algos.extend(loaded_algos.into_iter().filter(|algo| !algos.contains(algo)).collect::<Vec<_>>());
+    if algos.is_empty() {
+        algos = opts.algorithms.clone();
+    }
+    if !check_key(opts.key.as_deref(), &algos) {
+        return checksums::Error::OptionParsingError.exit_value();
+    }
+
+    // Walk the tree the way the hashes file was made, unless chosen explicitly
+    let (depth, follow_symlinks) = match loaded.header {
+        Some(ref header) => {
+            (if opts.depth_specified { opts.depth } else { header.depth },
+             if opts.follow_symlinks_specified {
+                 opts.follow_symlinks
+             } else {
+                 header.follow_symlinks
+             })
+        }
+        None => (opts.depth, opts.follow_symlinks),
+    };
+    let record_directories = opts.directories || loaded.header.as_ref().map(|h| h.directories).unwrap_or(false);
+    let walk = checksums::ops::WalkOptions {
+        depth,
+        follow_symlinks,
+        ..walk_options(opts, filter)
+    };
+    let mut found = find_tree(opts, &loaded, &algos, walk, record_directories);
+
+    // Files that couldn't be read this time are neither removed nor added
+    let unreadable: checksums::ops::Hashes = found.errors.keys().filter_map(|file| loaded.hashes.remove_entry(file)).collect();
+    for (file, error) in mem::take(&mut found.metadata_errors) {
+        found.errors.entry(file).or_insert(error);
+    }
+
+    if opts.update {
+        let header = checksums::ops::HashesHeader {
+            key_id: opts.key.as_ref().map(|key| checksums::key_id(key)),
+            directories: record_directories,
+            ..checksums::ops::HashesHeader::new(algos, depth, follow_symlinks)
+        };
+        return update_main(opts, &header, loaded, unreadable, found, sign_key, signature_file);
+    }
+
+    let directory_compare_results = checksums::ops::compare_directories(&found.directories, &loaded.directories);
+    let metadata_compare_results = checksums::ops::compare_metadata(&found.metadata, &loaded.metadata);
+    // The hashes file was left out of the digests when it was written
+    found.hashes.remove(&opts.file.0);
+    let digest_compare_results: Vec<_> = loaded.digests
+        .iter()
+        .filter(|(algo, _)| algos.contains(algo))
+        .flat_map(|(&algo, loaded_digests)| checksums::ops::compare_digests(&checksums::ops::tree_digests(&found.hashes, algo), loaded_digests))
+        .collect();
+    let unlisted = found.unlisted;
+    let compare_result = checksums::ops::compare_hashes(&opts.file.0, found.hashes, loaded.hashes).map(|(mut compare_results, mut file_compare_results)| {
+        compare_results.extend(unlisted.into_iter().filter(|file| *file != opts.file.0).map(checksums::ops::CompareResult::FileAdded));
+        compare_results.extend(directory_compare_results);
+        compare_results.extend(digest_compare_results);
+        file_compare_results.extend(metadata_compare_results);
+        (compare_results, file_compare_results)
+    });
+
+    write_comparison_results(opts, &found.errors, compare_result).exit_value()
+}
+
+fn update_main(opts: &checksums::Options, header: &checksums::ops::HashesHeader, loaded: checksums::ops::HashesFile, unreadable: checksums::ops::Hashes,
+               found: Found, sign_key: Option<&checksums::ops::SecretKey>, signature_file: &(OsString, PathBuf))
+               -> i32 {
+    let checksums::ops::HashesFile { format,
+                                     hashes: loaded_hashes,
+                                     directories: loaded_directories,
+                                     digests: loaded_digests,
+                                     metadata: mut loaded_metadata,
+                                     .. } = loaded;
+    let directory_compare_results = checksums::ops::compare_directories(&found.directories, &loaded_directories);
+    let hashing_result = checksums::ops::write_hashing_errors(&mut stderr(), &found.errors);
+
+    let algo = header.algorithms[0];
+    let mut root_digest = None;
+    let update_result = match checksums::ops::update_hashes(&opts.file.0, found.hashes, loaded_hashes, opts.rehash_changed) {
+        Ok((mut updated_hashes, mut compare_results, file_compare_results)) => {
+            // Keep the hashes and metadata of files that couldn't be read this time
+            updated_hashes.extend(unreadable);
+            let mut metadata = found.metadata;
+            metadata.extend(found.errors.keys().filter_map(|file| loaded_metadata.remove_entry(file)));
+
+            let mut digests = BTreeMap::new();
+            if opts.tree_digests || !loaded_digests.is_empty() {
+                let algo_digests = checksums::ops::tree_digests(&updated_hashes, algo);
+                root_digest = Some(algo_digests[OsStr::new(".")].clone());
+                digests.insert(algo, algo_digests);
+            }
+
+            if let Err(error) = checksums::ops::write_hashes(&opts.file, header, format, updated_hashes, &found.directories, &digests, &metadata) {
+                eprintln!("{}: {}", opts.file.0.to_string_lossy(), error);
+                return checksums::Error::HashesFileIoFailure.exit_value();
+            }
+            if let Some(key) = sign_key {
+                if let Err(error) = checksums::ops::sign_hashes(&opts.file, key) {
+                    eprintln!("{}: {}", signature_file.0.to_string_lossy(), error);
+                    return checksums::Error::HashesFileIoFailure.exit_value();
+                }
+            }
+            compare_results.extend(directory_compare_results);
+            Ok((compare_results, file_compare_results))
+        }
+        Err(error) => Err(error),
+    };
+
+    let result = match checksums::ops::write_hash_update_results(&mut stdout(), &mut stderr(), update_result, opts.rehash_changed) {
+        checksums::Error::NoError => hashing_result,
+        update_result => update_result,
+    };
+    if let Some(root_digest) = root_digest {
+        println!("Root digest: {} {}", algo, root_digest);
+    }
+    result.exit_value()
+}
+
+/// Hash the tree walked as specified by `walk` with the given algorithms, and find the metadata, directories and unlisted files
+/// to check against the `loaded` hashes file, as chosen in `opts`.
+fn find_tree(opts: &checksums::Options, loaded: &checksums::ops::HashesFile, algos: &[checksums::Algorithm], walk: checksums::ops::WalkOptions,
+             record_directories: bool)
+             -> Found {
+    let checksums::ops::WalkOptions { filter, depth, follow_symlinks, .. } = walk;
+
+    // Keep machine-readable output clean of the progress bar
+    let mut pb_out: Box<dyn Write> = match opts.output_format {
+        checksums::ops::OutputFormat::Text => Box::new(stdout()),
+        _ => Box::new(stderr()),
+    };
+
+    // Check the metadata kinds recorded in the hashes file, unless chosen explicitly
+    let metadata_kinds: Vec<_> = match opts.metadata {
+        Some(ref kinds) => kinds.clone(),
+        None => loaded.metadata.values().flat_map(|m| m.keys().cloned()).collect::<BTreeSet<_>>().into_iter().collect(),
+    };
+
+    let (hashes, errors) = if opts.listed_only {
+        checksums::ops::create_listed_hashes(&opts.dir, &loaded.hashes, filter, algos, opts.key.as_deref(), opts.jobs, &mut pb_out)
+    } else {
+        match opts.archive {
+            Some(archive) => checksums::ops::create_archive_hashes(&opts.dir, archive, algos, opts.key.as_deref(), walk, &mut pb_out),
+            None => checksums::ops::create_hashes(&opts.dir, algos, opts.key.as_deref(), walk, &mut pb_out),
+        }
+    };
+    let (metadata, metadata_errors) = if metadata_kinds.is_empty() {
+        (BTreeMap::new(), BTreeMap::new())
+    } else if opts.listed_only {
+        checksums::ops::create_listed_metadata(&opts.dir, &loaded.metadata, filter, &metadata_kinds, follow_symlinks)
+    } else if let Some(archive) = opts.archive {
+        checksums::ops::create_archive_metadata(&opts.dir, archive, filter, &metadata_kinds, depth, opts.nested_archives)
+    } else {
+        checksums::ops::create_metadata(&opts.dir, filter, &metadata_kinds, depth, follow_symlinks)
+    };
+    let directories = if !record_directories {
+        BTreeSet::new()
+    } else if opts.listed_only {
+        loaded.directories.iter().filter(|dir| opts.dir.join(dir).is_dir()).cloned().collect()
+    } else if let Some(archive) = opts.archive {
+        checksums::ops::create_archive_directories(&opts.dir, archive, filter, depth, opts.nested_archives)
+    } else {
+        checksums::ops::create_directories(&opts.dir, filter, depth, follow_symlinks)
+    };
+    let unlisted = if opts.report_unlisted {
+        checksums::ops::find_unlisted_files(&opts.dir, &loaded.hashes, filter, depth, follow_symlinks)
+    } else {
+        vec![]
+    };
+    // Progress bar separator
+    writeln!(pb_out).unwrap();
+
+    Found {
+        hashes,
+        errors,
+        metadata,
+        metadata_errors,
+        directories,
+        unlisted,
+    }
+}
+
+fn dupes_main(opts: &checksums::Options, filter: &checksums::ops::FileFilter, link: Option<checksums::ops::LinkKind>) -> i32 {
+    // Keep machine-readable output clean of the progress bar
+    let mut pb_out: Box<dyn Write> = match opts.output_format {
+        checksums::ops::OutputFormat::Text => Box::new(stdout()),
+        _ => Box::new(stderr()),
+    };
+    let (groups, errors) =
+        checksums::ops::find_duplicates(&opts.dir, filter, opts.algorithms[0], opts.depth, opts.follow_symlinks, opts.jobs, &mut pb_out);
+
+    let mut links = BTreeMap::new();
+    if let Some(kind) = link {
+        for group in &groups {
+            links.extend(checksums::ops::link_duplicates(&opts.dir, group, kind));
+        }
+    }
+
+    match opts.output_format {
+        checksums::ops::OutputFormat::Text => {
+            let hashing_result = checksums::ops::write_hashing_errors(&mut stderr(), &errors);
+            match (hashing_result, checksums::ops::write_duplicates(&mut stdout(), &groups, &links)) {
+                (checksums::Error::NFilesUnreadable(unreadable), checksums::Error::NFilesUnreadable(unlinked)) => {
+                    checksums::Error::NFilesUnreadable(unreadable + unlinked)
+                }
+                (checksums::Error::NoError, result) | (result, _) => result,
+            }
+        }
+        output_format => {
+            checksums::ops::write_duplicates_json(&mut stdout(), &errors, &groups, &links, output_format == checksums::ops::OutputFormat::Ndjson)
+        }
+    }
+    .exit_value()
+}
+
+fn diff_main(opts: &checksums::Options, old: &(OsString, PathBuf), new: &(OsString, PathBuf)) -> i32 {
+    let checksums::ops::HashesFile { header: old_header,
+                                     hashes: mut old_hashes,
+                                     directories: old_directories,
+                                     digests: old_digests,
+                                     metadata: old_metadata,
+                                     .. } =
+        match checksums::ops::read_hashes_file(&mut stderr(), old, &opts.algorithms) {
+            Ok(read) => read,
+            Err(error) => return error.exit_value(),
+        };
+    let checksums::ops::HashesFile { header: new_header,
+                                     hashes: mut new_hashes,
+                                     directories: new_directories,
+                                     digests: new_digests,
+                                     metadata: new_metadata,
+                                     .. } =
+        match checksums::ops::read_hashes_file(&mut stderr(), new, &opts.algorithms) {
+            Ok(read) => read,
+            Err(error) => return error.exit_value(),
+        };
+
+    // Files without a header weren't keyed
+    let key_mismatch = match (old_header.as_ref().and_then(|h| h.key_id.as_ref()), new_header.as_ref().and_then(|h| h.key_id.as_ref())) {
+        (Some(old_key), Some(new_key)) if old_key != new_key => {
+            Some(format!("{} and {} were hashed with different keys", old.0.to_string_lossy(), new.0.to_string_lossy()))
+        }
+        (Some(_), None) => Some(format!("{} was hashed with a key, but {} wasn't", old.0.to_string_lossy(), new.0.to_string_lossy())),
+        (None, Some(_)) => Some(format!("{} was hashed with a key, but {} wasn't", new.0.to_string_lossy(), old.0.to_string_lossy())),
+        _ => None,
+    };
+    if let Some(key_mismatch) = key_mismatch {
+        eprintln!("{}", key_mismatch);
+        return checksums::Error::OptionParsingError.exit_value();
+    }
+    let common_algos = checksums::ops::retain_common_algorithms(&mut old_hashes, &mut new_hashes);
+    if common_algos.is_empty() && !old_hashes.is_empty() && !new_hashes.is_empty() {
+        eprintln!("{} and {} have no hashing algorithm in common", old.0.to_string_lossy(), new.0.to_string_lossy());
+        return checksums::Error::OptionParsingError.exit_value();
+    }
+    // Each hashes file lists itself as ignored
+    old_hashes.remove(&old.0);
+    new_hashes.remove(&new.0);
+
+    let directory_compare_results = if old_header.map(|h| h.directories).unwrap_or(false) && new_header.map(|h| h.directories).unwrap_or(false) {
+        checksums::ops::compare_directories(&new_directories, &old_directories)
+    } else {
+        vec![]
+    };
+    // Digests only one of the files recorded are made from the other's hashes
+    let digest_compare_results: Vec<_> = common_algos.into_iter()
+        .filter(|algo| old_digests.contains_key(algo) || new_digests.contains_key(algo))
+        .flat_map(|algo| {
+            let old_algo_digests = old_digests.get(&algo).cloned().unwrap_or_else(|| checksums::ops::tree_digests(&old_hashes, algo));
+            let new_algo_digests = new_digests.get(&algo).cloned().unwrap_or_else(|| checksums::ops::tree_digests(&new_hashes, algo));
+            checksums::ops::compare_digests(&new_algo_digests, &old_algo_digests)
+        })
+        .collect();
+    let metadata_compare_results = checksums::ops::compare_metadata(&new_metadata, &old_metadata);
+    let compare_result = checksums::ops::compare_hashes(OsStr::new(""), new_hashes, old_hashes).map(|(mut compare_results, mut file_compare_results)| {
+        compare_results.extend(directory_compare_results);
+        compare_results.extend(digest_compare_results);
+        file_compare_results.extend(metadata_compare_results);
+        (compare_results, file_compare_results)
+    });
+
+    write_comparison_results(opts, &BTreeMap::new(), compare_result).exit_value()
+}
+
+fn compare_main(opts: &checksums::Options, mut filter: checksums::ops::FileFilter, other: &Path, size_first: bool) -> i32 {
+    if !check_key(opts.key.as_deref(), &opts.algorithms) {
+        return checksums::Error::OptionParsingError.exit_value();
+    }
+    let mut other_filter = match checksums::ops::FileFilter::new(other, &opts.ignored_files, &opts.included_files, opts.ignore_files) {
+        Ok(filter) => filter,
+        Err(error) => {
+            eprintln!("{}", error);
+            return checksums::Error::OptionParsingError.exit_value();
+        }
+    };
+
+    // Files whose sizes differ are ignored, so they're not read
+    let size_compare_results = if size_first {
+        let sizes = checksums::ops::create_sizes(&opts.dir, &filter, opts.depth, opts.follow_symlinks);
+        let other_sizes = checksums::ops::create_sizes(other, &other_filter, opts.depth, opts.follow_symlinks);
+        let results = checksums::ops::compare_sizes(&other_sizes, &sizes);
+        for res in &results {
+            if let checksums::ops::CompareFileResult::SizeDiffers { ref file, .. } = *res {
+                filter.ignore_path(opts.dir.join(file));
+                other_filter.ignore_path(other.join(file));
+            }
+        }
+        results
+    } else {
+        vec![]
+    };
+
+    // Keep machine-readable output clean of the progress bar
+    let mut pb_out: Box<dyn Write> = match opts.output_format {
+        checksums::ops::OutputFormat::Text => Box::new(stdout()),
+        _ => Box::new(stderr()),
+    };
+    // Split the jobs between the trees, hashing them one after the other if there's only one
+    let other_jobs = opts.jobs / 2;
+    let walk = checksums::ops::WalkOptions { jobs: opts.jobs - other_jobs, ..walk_options(opts, &filter) };
+    let other_walk = checksums::ops::WalkOptions { jobs: other_jobs.max(1), ..walk_options(opts, &other_filter) };
+    let ((mut hashes, mut errors), (mut other_hashes, other_errors)) = if other_jobs == 0 {
+        (checksums::ops::create_hashes(&opts.dir, &opts.algorithms, opts.key.as_deref(), walk, &mut pb_out),
+         checksums::ops::create_hashes(other, &opts.algorithms, opts.key.as_deref(), other_walk, io::sink()))
+    } else {
+        thread::scope(|scope| {
+            let other_hashes = scope.spawn(|| checksums::ops::create_hashes(other, &opts.algorithms, opts.key.as_deref(), other_walk, io::sink()));
+            let hashes = checksums::ops::create_hashes(&opts.dir, &opts.algorithms, opts.key.as_deref(), walk, &mut pb_out);
+            (hashes, other_hashes.join().unwrap())
+        })
+    };
+    for (file, error) in other_errors {
+        errors.entry(file).or_insert(error);
+    }
+    for res in &size_compare_results {
+        if let checksums::ops::CompareFileResult::SizeDiffers { ref file, .. } = *res {
+            hashes.remove(file);
+            other_hashes.remove(file);
+        }
+    }
+
+    let compare_result = checksums::ops::compare_hashes(OsStr::new(""), other_hashes, hashes).map(|(compare_results, mut file_compare_results)| {
+        file_compare_results.extend(size_compare_results);
+        (compare_results, file_compare_results)
+    });
+
+    write_comparison_results(opts, &errors, compare_result).exit_value()
+}
+
+fn inputs_main(opts: &checksums::Options, inputs: &[OsString]) -> i32 {
+    if !check_key(opts.key.as_deref(), &opts.algorithms) {
+        return checksums::Error::OptionParsingError.exit_value();
+    }
+    let hashes: Vec<_> = inputs.iter().map(|input| (input.clone(), checksums::ops::hash_input(input, &opts.algorithms, opts.key.as_deref()))).collect();
+
+    let expected = match opts.check {
+        Some(ref expected) => expected,
+        None => return checksums::ops::write_input_hashes(&mut stdout(), &mut stderr(), opts.format, &opts.algorithms, hashes).exit_value(),
+    };
+    let mut errors = BTreeMap::new();
+    let mut file_compare_results = vec![];
+    for (input, result) in hashes {
+        match result.and_then(|mut hashes| hashes.remove(&opts.algorithms[0]).ok_or_else(|| io::Error::other(format!("no {} hash", opts.algorithms[0])))) {
+            Ok(hash) => {
+                file_compare_results.push(if hash == *expected {
+                    checksums::ops::CompareFileResult::FileMatches(input)
+                } else {
+                    checksums::ops::CompareFileResult::FileDiffers {
+                        file: input,
+                        was_hash: expected.clone(),
+                        new_hash: hash,
+                    }
+                });
+            }
+            Err(error) => {
+                errors.insert(input, error);
+            }
+        }
+    }
+
+    write_comparison_results(opts, &errors, Ok((vec![], file_compare_results))).exit_value()
+}
+
+/// Write the reasons files couldn't be hashed, and the comparison results, in the chosen output format.
+fn write_comparison_results(opts: &checksums::Options, errors: &BTreeMap<OsString, io::Error>,
+                            compare_result: Result<(Vec<checksums::ops::CompareResult>, Vec<checksums::ops::CompareFileResult>), checksums::ops::CompareError>)
+                            -> checksums::Error {
+    match opts.output_format {
+        checksums::ops::OutputFormat::Text => {
+            let hashing_result = checksums::ops::write_hashing_errors(&mut stderr(), errors);
+            match checksums::ops::write_hash_comparison_results(&mut stdout(), &mut stderr(), compare_result) {
+                checksums::Error::NoError => hashing_result,
+                compare_result => compare_result,
+            }
+        }
+        output_format => {
+            checksums::ops::write_hash_comparison_results_json(&mut stdout(), errors, compare_result, output_format == checksums::ops::OutputFormat::Ndjson)
+        }
+    }
+}
+
+/// Check that the hashes file was made with the chosen key, if any, and all of the chosen algorithms, if they were.
+fn check_header(opts: &checksums::Options, header: &checksums::ops::HashesHeader) -> bool {
+    let key_id = opts.key.as_ref().map(|key| checksums::key_id(key));
+    if header.key_id != key_id {
+        eprintln!("{}: {}",
+                  opts.file.0.to_string_lossy(),
+                  match (&header.key_id, &key_id) {
+                      (Some(made_with), None) => format!("Made with key {}, pass it with --key-file", made_with),
+                      (Some(made_with), Some(key_id)) => format!("Made with key {}, not {}", made_with, key_id),
+                      (None, _) => "Not made with a key".to_string(),
+                  });
+        return false;
+    }
+    if opts.algorithms_specified {
+        if let Some(algo) = opts.algorithms.iter().find(|algo| !header.algorithms.contains(algo)) {
+            eprintln!("{}: Made with {}, not {}",
+                      opts.file.0.to_string_lossy(),
+                      header.algorithms.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", "),
+                      algo);
+            return false;
+        }
+    }
+    true
+}
+
+fn check_key(key: Option<&[u8]>, algos: &[checksums::Algorithm]) -> bool {
+    if let Some(key) = key {
+        for &algo in algos {
+            if let Err(error) = checksums::keyed_hasher(algo, key) {
+                eprintln!("--key-file: {}", error);
+                return false;
+            }
+        }
+    }
+    true
+}
 
-    let hashes = checksums::ops::create_hashes(&opts.dir,
-                                               opts.ignored_files,
-                                               opts.algorithm,
-                                               opts.depth,
-                                               opts.follow_symlinks,
-                                               opts.jobs,
-                                               stdout(),
-                                               &mut stderr());
-    if opts.verify {
-        // Progress bar separator
-        println!("");
-
-        match checksums::ops::read_hashes(&mut stderr(), &opts.file) {
-            Ok(loaded_hashes) => {
-                let compare_result = checksums::ops::compare_hashes(&opts.file.0, hashes, loaded_hashes);
-                checksums::ops::write_hash_comparison_results(&mut stdout(), &mut stderr(), compare_result)
-            }
-            Err(rval) => rval,
-        }.exit_value()
-    } else {
-        checksums::ops::write_hashes(&opts.file, opts.algorithm, hashes);
-        0
+fn walk_options<'a>(opts: &checksums::Options, filter: &'a checksums::ops::FileFilter) -> checksums::ops::WalkOptions<'a> {
+    checksums::ops::WalkOptions {
+        depth: opts.depth,
+        follow_symlinks: opts.follow_symlinks,
+        jobs: opts.jobs,
+        nested_archives: opts.nested_archives,
+        ..checksums::ops::WalkOptions::new(filter)
     }
 }
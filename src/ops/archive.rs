@@ -0,0 +1,457 @@
+use self::super::super::{Algorithm, hash_reader_multiple, hash_reader_multiple_keyed};
+use self::super::{FileFilter, Metadata, MetadataKind, PipelineFile, WalkOptions, hash_pipeline, placeholders};
+use std::io::{self, ErrorKind as IoErrorKind, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use self::super::super::util::backslash_escape;
+use std::collections::{BTreeSet, BTreeMap, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::path::{Component, Path, PathBuf};
+use std::fs::{self, File, OpenOptions};
+use flate2::read::GzDecoder;
+use std::{env, process};
+use tar;
+use zip;
+
+
+/// A kind of archive whose members can be hashed as if they were files in a directory.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::ArchiveFormat;
+/// # use std::path::Path;
+/// assert_eq!(ArchiveFormat::from_path(Path::new("release.tar")), Some(ArchiveFormat::Tar));
+/// assert_eq!(ArchiveFormat::from_path(Path::new("release.TGZ")), Some(ArchiveFormat::TarGz));
+/// assert_eq!(ArchiveFormat::from_path(Path::new("dir/release.zip")), Some(ArchiveFormat::Zip));
+/// assert_eq!(ArchiveFormat::from_path(Path::new("release.gz")), None);
+/// ```
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ArchiveFormat {
+    /// An uncompressed tarball.
+    Tar,
+    /// A gzip-compressed tarball.
+    TarGz,
+    /// A zip archive.
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Recognise an archive by its extension: `.tar`, `.tar.gz` or `.tgz`, or `.zip`, regardless of case.
+    pub fn from_path(path: &Path) -> Option<ArchiveFormat> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+
+/// Create subpath->algorithm->hash mappings for the members of the archive at a given path, walking it as specified by `walk`,
+/// as if it was a directory they were extracted to, without extracting them.
+///
+/// This collects the results of `stream_archive_hashes()`.
+pub fn create_archive_hashes<Wo>(path: &Path, format: ArchiveFormat, algos: &[Algorithm], key: Option<&[u8]>, walk: WalkOptions, pb_out: Wo)
+                                 -> (BTreeMap<OsString, BTreeMap<Algorithm, String>>, BTreeMap<OsString, io::Error>)
+    where Wo: Write
+{
+    let mut hashes = BTreeMap::new();
+    let mut errors = BTreeMap::new();
+
+    stream_archive_hashes(path, format, algos, key, walk, pb_out, |fname, result| match result {
+        Ok(file_hashes) => {
+            hashes.insert(fname, file_hashes);
+        }
+        Err(error) => {
+            errors.insert(fname, error);
+        }
+    });
+
+    (hashes, errors)
+}
+
+/// Hash the members of the archive at a given path like `create_archive_hashes()`, but pass each one's name and hashes,
+/// or the reason why it couldn't be read, to `sink` as soon as they're available, in archive order, like `stream_hashes()`.
+///
+/// Members are read one after another, straight out of the archive, so `walk.jobs` and `walk.cache` aren't used.
+/// Regular files are hashed, hardlinks get the hashes of the member they link to, and everything else is skipped.
+/// If `walk.nested_archives`, members that are archives themselves are walked like directories instead of being hashed.
+///
+/// Members that aren't included by the filter are omitted, and ones ignored by it, or under ignored directories, are marked as such;
+/// per-directory ignore files aren't honoured. Hardlinks to omitted members are omitted, too, and ones to ignored members marked as ignored.
+/// The hashes of the last `MAX_LINK_TARGETS` members are kept for hardlinks to them; ones to earlier members read the archive again.
+/// Symlinks aren't followed, even if they point to other members.
+/// Members with absolute paths or paths leading outside the archive aren't read, and are passed with an error,
+/// as is the archive itself, under its file name, or a nested archive, if it stops being readable midway.
+pub fn stream_archive_hashes<Wo, S>(path: &Path, format: ArchiveFormat, algos: &[Algorithm], key: Option<&[u8]>, walk: WalkOptions, pb_out: Wo, mut sink: S)
+    where Wo: Write,
+          S: FnMut(OsString, io::Result<BTreeMap<Algorithm, String>>)
+{
+    let WalkOptions { filter, depth, nested_archives, .. } = walk;
+    let walk = move |emit: &mut dyn FnMut(PipelineFile)| {
+        // Hardlinks come after what they link to, and don't have its contents
+        let mut linked = LinkTargets::default();
+        walk_archive(path, format, depth, nested_archives, &mut |name, member| {
+            let mut member = match member {
+                Ok(member) => member,
+                Err(error) => return emit(PipelineFile::Done(name, None, Err(error))),
+            };
+            let result = match member.kind {
+                MemberKind::File | MemberKind::Hardlink(_) if !filter.is_included(&path.join(&name), false) => return,
+                MemberKind::Hardlink(ref target) if !filter.is_included(&path.join(target), false) || !is_within_depth(target, depth) => return,
+                MemberKind::File | MemberKind::Hardlink(_) if filter.is_file_ignored(&path.join(&name)) => Ok(placeholders(algos)),
+                MemberKind::Hardlink(ref target) if filter.is_file_ignored(&path.join(target)) => Ok(placeholders(algos)),
+                MemberKind::File => {
+                    match key {
+                        Some(key) => hash_reader_multiple_keyed(&mut member.data, algos, key),
+                        None => hash_reader_multiple(&mut member.data, algos),
+                    }
+                }
+                MemberKind::Hardlink(ref target) => {
+                    match linked.get(target) {
+                        Some(hashes) => Ok(hashes.clone()),
+                        None => hash_archive_member(path, format, depth, nested_archives, target, algos, key),
+                    }
+                }
+                _ => return,
+            };
+            if let Ok(ref hashes) = result {
+                linked.insert(name.clone(), hashes.clone());
+            }
+            emit(PipelineFile::Done(name, None, result));
+        });
+    };
+    hash_pipeline(algos, key, 1, pb_out, walk, |filename, _, result| sink(filename, result));
+}
+
+/// Max number of members whose hashes `stream_archive_hashes()` keeps for hardlinks to them.
+pub const MAX_LINK_TARGETS: usize = 4096;
+
+
+/// Create subpath->kind->value mappings for the members of the archive at a given path using the given metadata kinds
+/// up to a given depth, walking it the same way `stream_archive_hashes()` does, as recorded in the archive.
+///
+/// Symlinks are included, as for `create_metadata()`. Owners are only recorded in tarballs, and extended attributes never are;
+/// modification times are recorded to the second, and only from extended timestamps in zip archives, since their DOS ones are in local time.
+///
+/// Members that couldn't be read are omitted, and returned alongside the mappings with the reason why.
+pub fn create_archive_metadata(path: &Path, format: ArchiveFormat, filter: &FileFilter, kinds: &[MetadataKind], depth: Option<usize>, nested: bool)
+                               -> (Metadata, BTreeMap<OsString, io::Error>) {
+    let mut metadata = BTreeMap::new();
+    let mut errors = BTreeMap::new();
+    walk_archive(path, format, depth, nested, &mut |name, member| {
+        let member = match member {
+            Ok(member) => member,
+            Err(error) => {
+                errors.insert(name, error);
+                return;
+            }
+        };
+        if member.kind == MemberKind::Directory || member.kind == MemberKind::Other {
+            return;
+        }
+        let file = path.join(&name);
+        if filter.is_file_ignored(&file) || !filter.is_included(&file, false) {
+            return;
+        }
+
+        let mut values = BTreeMap::new();
+        for &kind in kinds {
+            let value = match kind {
+                MetadataKind::Mode => member.mode.map(|mode| format!("{:o}", mode & 0o7777)),
+                MetadataKind::Owner => member.owner.map(|(uid, gid)| format!("{}:{}", uid, gid)),
+                MetadataKind::Mtime => member.mtime.map(|mtime| format!("{}.{:09}", mtime, 0)),
+                MetadataKind::Target => {
+                    match member.kind {
+                        MemberKind::Symlink(ref target) => Some(backslash_escape(target)),
+                        _ => None,
+                    }
+                }
+                MetadataKind::Xattrs => None,
+            };
+            if let Some(value) = value {
+                values.insert(kind, value);
+            }
+        }
+        metadata.insert(name, values);
+    });
+
+    (metadata, errors)
+}
+
+/// Create a set of subpaths of the directories in the archive at a given path up to a given depth,
+/// walking it the same way `stream_archive_hashes()` does.
+///
+/// Directories are recorded whether the archive has members for them or only for their contents,
+/// and nested archives are recorded as directories.
+/// Directories ignored by `filter`, or under ignored ones, are omitted.
+pub fn create_archive_directories(path: &Path, format: ArchiveFormat, filter: &FileFilter, depth: Option<usize>, nested: bool) -> BTreeSet<OsString> {
+    let mut directories = BTreeSet::new();
+    walk_archive(path, format, depth, nested, &mut |name, member| {
+        let is_dir = member.map(|m| m.kind == MemberKind::Directory).unwrap_or(false);
+        for dir in Path::new(&name).ancestors().skip(if is_dir { 0 } else { 1 }).filter(|dir| !dir.as_os_str().is_empty()) {
+            if !filter.is_file_ignored(&path.join(dir)) && !filter.is_ignored(&path.join(dir), true) {
+                directories.insert(dir.as_os_str().to_os_string());
+            }
+        }
+    });
+    directories
+}
+
+
+/// The hashes of the last `MAX_LINK_TARGETS` members, for hardlinks to them.
+#[derive(Default)]
+struct LinkTargets {
+    hashes: BTreeMap<OsString, BTreeMap<Algorithm, String>>,
+    order: VecDeque<OsString>,
+}
+
+impl LinkTargets {
+    fn get(&self, name: &OsStr) -> Option<&BTreeMap<Algorithm, String>> {
+        self.hashes.get(name)
+    }
+
+    fn insert(&mut self, name: OsString, hashes: BTreeMap<Algorithm, String>) {
+        if self.hashes.insert(name.clone(), hashes).is_none() {
+            self.order.push_back(name);
+        }
+        if self.order.len() > MAX_LINK_TARGETS {
+            let oldest = self.order.pop_front().unwrap();
+            self.hashes.remove(&oldest);
+        }
+    }
+}
+
+/// Hash the regular file member `target` of the archive at `path` by walking it again, for hardlinks to members no longer kept.
+fn hash_archive_member(path: &Path, format: ArchiveFormat, depth: Option<usize>, nested: bool, target: &OsStr, algos: &[Algorithm], key: Option<&[u8]>)
+                       -> io::Result<BTreeMap<Algorithm, String>> {
+    let mut result = None;
+    walk_archive(path, format, depth, nested, &mut |name, member| match member {
+        Ok(mut member) => {
+            if result.is_none() && name == target && member.kind == MemberKind::File {
+                result = Some(match key {
+                    Some(key) => hash_reader_multiple_keyed(&mut member.data, algos, key),
+                    None => hash_reader_multiple(&mut member.data, algos),
+                });
+            }
+        }
+        Err(error) => {
+            if result.is_none() && name == target {
+                result = Some(Err(error));
+            }
+        }
+    });
+    result.unwrap_or_else(|| Err(io::Error::new(IoErrorKind::NotFound, "hardlink to a member that wasn't hashed")))
+}
+
+/// A member of an archive, with the metadata recorded for it.
+struct Member<'r> {
+    path: PathBuf,
+    kind: MemberKind,
+    mode: Option<u32>,
+    owner: Option<(u64, u64)>,
+    mtime: Option<u64>,
+    data: &'r mut dyn Read,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MemberKind {
+    File,
+    Directory,
+    /// With its target.
+    Symlink(PathBuf),
+    /// With the subpath of the member it links to.
+    Hardlink(OsString),
+    Other,
+}
+
+/// Pass the members of the archive at `path` up to `depth` to `visit`, with their subpaths, in archive order,
+/// walking nested archives if `nested`, and members that couldn't be read with the reason why.
+fn walk_archive(path: &Path, format: ArchiveFormat, depth: Option<usize>, nested: bool, visit: &mut dyn FnMut(OsString, io::Result<Member>)) {
+    let result = File::open(path).and_then(|file| {
+        let mut on_member = |member: Member| visit_member(member, OsStr::new(""), depth, nested, visit);
+        match format {
+            ArchiveFormat::Zip => read_zip_members(file, &mut on_member),
+            format => read_members(file, format, &mut on_member),
+        }
+    });
+    if let Err(error) = result {
+        visit(path.file_name().unwrap_or_default().to_os_string(), Err(error));
+    }
+}
+
+fn visit_member(member: Member, prefix: &OsStr, depth: Option<usize>, nested: bool, visit: &mut dyn FnMut(OsString, io::Result<Member>)) -> io::Result<()> {
+    let name = match member_name(prefix, &member.path) {
+        Some(name) => name,
+        None => {
+            let mut name = prefix.to_os_string();
+            name.push(member.path.as_os_str());
+            visit(name, Err(io::Error::new(IoErrorKind::InvalidData, "path leads outside the archive")));
+            return Ok(());
+        }
+    };
+    if name == prefix || !is_within_depth(&name, depth) {
+        return Ok(());
+    }
+
+    let member = Member {
+        kind: match member.kind {
+            MemberKind::Hardlink(target) => MemberKind::Hardlink(member_name(prefix, Path::new(&target)).unwrap_or_default()),
+            kind => kind,
+        },
+        ..member
+    };
+    match ArchiveFormat::from_path(Path::new(&name)).filter(|_| nested && member.kind == MemberKind::File) {
+        Some(format) => {
+            let mut nested_prefix = name.clone();
+            nested_prefix.push("/");
+            let result = read_members(member.data, format, &mut |member: Member| visit_member(member, &nested_prefix, depth, nested, visit));
+            if let Err(error) = result {
+                visit(name, Err(error));
+            }
+        }
+        None => visit(name, Ok(member)),
+    }
+    Ok(())
+}
+
+fn is_within_depth(name: &OsStr, depth: Option<usize>) -> bool {
+    depth.map(|depth| Path::new(name).components().count() <= depth + 1).unwrap_or(true)
+}
+
+/// Join the components of a member's path to `prefix` with slashes, or `None` if it's absolute or leads outside the archive.
+fn member_name(prefix: &OsStr, path: &Path) -> Option<OsString> {
+    let mut name = prefix.to_os_string();
+    let mut first = true;
+    for component in path.components() {
+        match component {
+            Component::Normal(component) => {
+                if !first {
+                    name.push("/");
+                }
+                name.push(component);
+                first = false;
+            }
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(name)
+}
+
+fn read_members<R: Read>(mut reader: R, format: ArchiveFormat, on_member: &mut dyn FnMut(Member) -> io::Result<()>) -> io::Result<()> {
+    match format {
+        ArchiveFormat::Tar => read_tar_members(reader, on_member),
+        ArchiveFormat::TarGz => read_tar_members(GzDecoder::new(reader), on_member),
+        ArchiveFormat::Zip => {
+            // Zip archives are read from the end, so nested ones have to be copied out
+            let spilled = SpilledArchive::new(&mut reader)?;
+            read_zip_members(&spilled.file, on_member)
+        }
+    }
+}
+
+/// A nested archive copied out to a file in the temporary directory, removed once dropped.
+struct SpilledArchive {
+    file: File,
+    path: PathBuf,
+}
+
+impl SpilledArchive {
+    fn new<R: Read>(reader: &mut R) -> io::Result<SpilledArchive> {
+        static SPILLED: AtomicUsize = AtomicUsize::new(0);
+        let path = env::temp_dir().join(format!("checksums-nested-{}-{}.tmp", process::id(), SPILLED.fetch_add(1, AtomicOrdering::Relaxed)));
+        let mut spilled = SpilledArchive {
+            file: OpenOptions::new().read(true).write(true).create_new(true).open(&path)?,
+            path,
+        };
+        io::copy(reader, &mut spilled.file)?;
+        spilled.file.seek(SeekFrom::Start(0))?;
+        Ok(spilled)
+    }
+}
+
+impl Drop for SpilledArchive {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn read_tar_members<R: Read>(reader: R, on_member: &mut dyn FnMut(Member) -> io::Result<()>) -> io::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let header = entry.header();
+        let entry_type = header.entry_type();
+        let kind = if entry_type.is_file() || entry_type == tar::EntryType::Continuous || entry_type.is_gnu_sparse() {
+            MemberKind::File
+        } else if entry_type.is_dir() {
+            MemberKind::Directory
+        } else if entry_type.is_symlink() {
+            MemberKind::Symlink(entry.link_name()?.unwrap_or_default().into_owned())
+        } else if entry_type.is_hard_link() {
+            MemberKind::Hardlink(entry.link_name()?.unwrap_or_default().into_owned().into_os_string())
+        } else {
+            MemberKind::Other
+        };
+        let (mode, owner, mtime) = (header.mode().ok(), header.uid().ok().and_then(|uid| header.gid().ok().map(|gid| (uid, gid))), header.mtime().ok());
+
+        on_member(Member {
+            path: entry.path()?.into_owned(),
+            kind,
+            mode,
+            owner,
+            mtime,
+            data: &mut entry,
+        })?;
+    }
+    Ok(())
+}
+
+fn read_zip_members<R: Read + Seek>(reader: R, on_member: &mut dyn FnMut(Member) -> io::Result<()>) -> io::Result<()> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let mode = file.unix_mode();
+        let kind = if file.is_dir() {
+            MemberKind::Directory
+        } else if mode.map(|mode| mode & 0o170000 == 0o120000).unwrap_or(false) {
+            let mut target = String::new();
+            file.read_to_string(&mut target)?;
+            MemberKind::Symlink(target.into())
+        } else {
+            MemberKind::File
+        };
+        // The DOS timestamp is in local time, of whatever time zone the archive was made in
+        let mtime = extended_mtime(file.extra_data());
+
+        on_member(Member {
+            path: PathBuf::from(file.name()),
+            kind,
+            mode,
+            owner: None,
+            mtime,
+            data: &mut file,
+        })?;
+    }
+    Ok(())
+}
+
+/// The modification time in a zip member's extended timestamp extra field (0x5455), if it has one.
+fn extended_mtime(mut extra: &[u8]) -> Option<u64> {
+    while extra.len() >= 4 {
+        let (id, len) = (u16::from_le_bytes([extra[0], extra[1]]), u16::from_le_bytes([extra[2], extra[3]]) as usize);
+        let data = extra.get(4..4 + len)?;
+        if id == 0x5455 && data.len() >= 5 && data[0] & 1 != 0 {
+            let mtime = i32::from_le_bytes([data[1], data[2], data[3], data[4]]);
+            return if mtime >= 0 { Some(mtime as u64) } else { None };
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
@@ -0,0 +1,152 @@
+use self::super::super::util::{backslash_escape, backslash_unescape};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use self::super::super::Algorithm;
+use std::collections::BTreeMap;
+use std::fs::{File, Metadata};
+use std::ffi::OsString;
+use once_cell::sync::Lazy;
+use std::str::FromStr;
+use std::path::Path;
+use regex::Regex;
+
+
+/// Metadata of a file that changes whenever its contents do.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct FileStamp {
+    /// Size in bytes.
+    pub size: u64,
+    /// Modification time, in seconds and nanoseconds since the epoch.
+    pub mtime: (i64, u32),
+    /// Status change time, in seconds and nanoseconds since the epoch.
+    ///
+    /// Creation time on non-Unix platforms.
+    pub ctime: (i64, u32),
+    /// Inode number, or `0` on non-Unix platforms.
+    pub inode: u64,
+}
+
+/// A file's previously computed hashes, and the metadata it had at the time.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct CacheEntry {
+    /// Metadata of the file when it was hashed.
+    pub stamp: FileStamp,
+    /// Hashes of the file's contents.
+    pub hashes: BTreeMap<Algorithm, String>,
+}
+
+
+impl FileStamp {
+    /// Get the stamp of a file with the specified metadata.
+    #[cfg(unix)]
+    pub fn from_metadata(meta: &Metadata) -> FileStamp {
+        use std::os::unix::fs::MetadataExt;
+
+        FileStamp {
+            size: meta.len(),
+            mtime: (meta.mtime(), meta.mtime_nsec() as u32),
+            ctime: (meta.ctime(), meta.ctime_nsec() as u32),
+            inode: meta.ino(),
+        }
+    }
+
+    /// Get the stamp of a file with the specified metadata.
+    #[cfg(not(unix))]
+    pub fn from_metadata(meta: &Metadata) -> FileStamp {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        fn since_epoch(time: io::Result<SystemTime>) -> (i64, u32) {
+            time.ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| (d.as_secs() as i64, d.subsec_nanos())).unwrap_or((0, 0))
+        }
+
+        FileStamp {
+            size: meta.len(),
+            mtime: since_epoch(meta.modified()),
+            ctime: since_epoch(meta.created()),
+            inode: 0,
+        }
+    }
+}
+
+impl CacheEntry {
+    /// Get the cached hashes for all of `algos`, if the file's current stamp is the same as the cached one.
+    pub fn hashes_for(&self, stamp: &FileStamp, algos: &[Algorithm]) -> Option<BTreeMap<Algorithm, String>> {
+        if self.stamp == *stamp {
+            algos.iter().map(|algo| self.hashes.get(algo).map(|hash| (*algo, hash.clone()))).collect()
+        } else {
+            None
+        }
+    }
+}
+
+
+/// Read a hash cache saved with `write_cache()` from the specified path.
+///
+/// A nonexistent cache file is treated as empty, and malformed lines are skipped,
+/// since the files they describe will simply be hashed again.
+pub fn read_cache(path: &Path) -> io::Result<BTreeMap<OsString, CacheEntry>> {
+    static LINE_RGX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\d+) (-?\d+)\.(\d+) (-?\d+)\.(\d+) (\d+) ([^ ]+) (.+)$").unwrap());
+
+    let mut cache = BTreeMap::new();
+
+    let in_file = match File::open(path) {
+        Ok(f) => BufReader::new(f),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(cache),
+        Err(e) => return Err(e),
+    };
+    for line in in_file.lines() {
+        let line = line?;
+        if let Some((fname, entry)) = LINE_RGX.captures(&line).and_then(|c| parse_cache_line(&c)) {
+            cache.insert(fname, entry);
+        }
+    }
+
+    Ok(cache)
+}
+
+/// Serialise the specified hash cache to the specified path.
+///
+/// Each line holds the size, modification time, status change time, inode number, comma-separated `ALGO:HASH` pairs,
+/// and the backslash-escaped name of one file.
+pub fn write_cache(path: &Path, cache: &BTreeMap<OsString, CacheEntry>) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+
+    for (fname, entry) in cache {
+        writeln!(out,
+                 "{} {}.{:09} {}.{:09} {} {} {}",
+                 entry.stamp.size,
+                 entry.stamp.mtime.0,
+                 entry.stamp.mtime.1,
+                 entry.stamp.ctime.0,
+                 entry.stamp.ctime.1,
+                 entry.stamp.inode,
+                 entry.hashes.iter().map(|(algo, hash)| format!("{}:{}", algo, hash)).collect::<Vec<_>>().join(","),
+                 backslash_escape(fname))?;
+    }
+
+    out.flush()
+}
+
+
+fn parse_cache_line(c: &::regex::Captures) -> Option<(OsString, CacheEntry)> {
+    let hashes = c[7]
+        .split(',')
+        .map(|ah| {
+            let mut ah = ah.splitn(2, ':');
+            match (ah.next().and_then(|a| Algorithm::from_str(a).ok()), ah.next()) {
+                (Some(algo), Some(hash)) => Some((algo, hash.to_string())),
+                _ => None,
+            }
+        })
+        .collect::<Option<_>>()?;
+
+    Some((backslash_unescape(&c[8])?,
+          CacheEntry {
+              stamp: FileStamp {
+                  size: c[1].parse().ok()?,
+                  mtime: (c[2].parse().ok()?, c[3].parse().ok()?),
+                  ctime: (c[4].parse().ok()?, c[5].parse().ok()?),
+                  inode: c[6].parse().ok()?,
+              },
+              hashes,
+          }))
+}
@@ -1,22 +1,57 @@
-use self::super::super::util::{vec_merge, mul_str};
-use std::collections::BTreeMap;
+use self::super::super::util::vec_merge;
+use self::super::super::Algorithm;
+use self::super::{Hashes, Digests, Metadata, MetadataKind};
+use std::collections::{BTreeSet, BTreeMap, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::mem;
 
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum CompareResult {
-    FileAdded(String),
-    FileRemoved(String),
-    FileIgnored(String),
+    FileAdded(OsString),
+    FileRemoved(OsString),
+    FileIgnored(OsString),
+    /// A file was removed, and one with the same hashes was added.
+    FileMoved {
+        from: OsString,
+        to: OsString,
+    },
+    /// A file with the same hashes as another loaded one was added.
+    FileCopied {
+        from: OsString,
+        to: OsString,
+    },
+    DirectoryAdded(OsString),
+    DirectoryRemoved(OsString),
+    /// A directory's digest, as made by `tree_digests()`, changed.
+    DigestDiffers {
+        dir: OsString,
+        was_digest: String,
+        new_digest: String,
+    },
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum CompareFileResult {
-    FileMatches(String),
+    FileMatches(OsString),
     FileDiffers {
-        file: String,
+        file: OsString,
         was_hash: String,
         new_hash: String,
     },
+    /// A file's size differs, so its contents weren't compared.
+    SizeDiffers {
+        file: OsString,
+        was_size: u64,
+        new_size: u64,
+    },
+    /// A kind of a file's metadata differs, regardless of its contents.
+    MetadataDiffers {
+        file: OsString,
+        kind: MetadataKind,
+        was: String,
+        is: String,
+    },
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -29,28 +64,50 @@ pub enum CompareError {
 
 
 /// Compare two provided hashes.
-pub fn compare_hashes(out_file: &str, mut current_hashes: BTreeMap<String, String>, mut loaded_hashes: BTreeMap<String, String>)
+///
+/// Only the algorithms present in both hashes of a file are compared.
+/// Files sharing none can't be verified, so they're reported as `FileDiffers`, with all their hashes prefixed with their algorithms.
+///
+/// Ignored files that weren't loaded are reported as `FileIgnored`, since only the `Checksums` format records them.
+///
+/// Removed files are paired with added ones with the same hashes into `FileMoved` results, in name order,
+/// and the remaining added files with the same hashes as any loaded one are reported as `FileCopied`.
+pub fn compare_hashes(out_file: &OsStr, mut current_hashes: BTreeMap<OsString, BTreeMap<Algorithm, String>>,
+                      mut loaded_hashes: BTreeMap<OsString, BTreeMap<Algorithm, String>>)
                       -> Result<(Vec<CompareResult>, Vec<CompareFileResult>), CompareError> {
-    let current_hashes_value_len = current_hashes.iter().next().unwrap().1.len();
-    let loaded_hashes_value_len = loaded_hashes.iter().next().unwrap().1.len();
-    if current_hashes_value_len != loaded_hashes_value_len {
-        return Err(CompareError::HashLengthDiffers {
-            previous_len: loaded_hashes_value_len,
-            current_len: current_hashes_value_len,
-        });
+    for (key, loaded_value) in &loaded_hashes {
+        if let Some(current_value) = current_hashes.get(key) {
+            for (algo, loaded_hash) in loaded_value {
+                if let Some(current_hash) = current_value.get(algo) {
+                    if current_hash.len() != loaded_hash.len() {
+                        return Err(CompareError::HashLengthDiffers {
+                            previous_len: loaded_hash.len(),
+                            current_len: current_hash.len(),
+                        });
+                    }
+                }
+            }
+        }
     }
-    let placeholder_value = mul_str("-", current_hashes_value_len);
     let mut file_compare_results = Vec::new();
 
     current_hashes.remove(out_file);
     loaded_hashes.remove(out_file);
 
+    let unrecorded_ignored: Vec<_> =
+        current_hashes.iter().filter(|&(key, value)| !loaded_hashes.contains_key(key) && is_ignored(value)).map(|(key, _)| key.clone()).collect();
+    for key in &unrecorded_ignored {
+        current_hashes.remove(key);
+    }
+
+    let moved_results = process_moves(&mut current_hashes, &mut loaded_hashes);
+
     let remove_results = process_ignores(|key, _, other| !other.contains_key(key),
                                          CompareResult::FileAdded,
                                          CompareResult::FileRemoved,
                                          &mut current_hashes,
                                          &mut loaded_hashes);
-    let ignore_results = process_ignores(|_, value, _| *value == placeholder_value,
+    let ignore_results = process_ignores(|_, value, _| is_ignored(value),
                                          CompareResult::FileIgnored,
                                          CompareResult::FileIgnored,
                                          &mut current_hashes,
@@ -63,29 +120,321 @@ pub fn compare_hashes(out_file: &str, mut current_hashes: BTreeMap<String, Strin
     if !current_hashes.is_empty() {
         for (key, loaded_value) in loaded_hashes {
             let current_value = &current_hashes[&key];
-            if *current_value == loaded_value {
-                file_compare_results.push(CompareFileResult::FileMatches(key));
-            } else {
+            if !loaded_value.keys().any(|algo| current_value.contains_key(algo)) {
                 file_compare_results.push(CompareFileResult::FileDiffers {
                     file: key,
-                    was_hash: loaded_value,
-                    new_hash: current_value.clone(),
+                    was_hash: tagged_hashes(&loaded_value),
+                    new_hash: tagged_hashes(current_value),
                 });
+                continue;
+            }
+            match loaded_value.into_iter().find(|(algo, loaded_hash)| current_value.get(algo).map(|h| h != loaded_hash).unwrap_or(false)) {
+                None => file_compare_results.push(CompareFileResult::FileMatches(key)),
+                Some((algo, loaded_hash)) => {
+                    file_compare_results.push(CompareFileResult::FileDiffers {
+                        file: key,
+                        was_hash: loaded_hash,
+                        new_hash: current_value[&algo].clone(),
+                    })
+                }
+            }
+        }
+    }
+
+    let ignore_results = vec_merge(ignore_results, unrecorded_ignored.into_iter().map(CompareResult::FileIgnored).collect());
+    Ok((vec_merge(vec_merge(moved_results, remove_results), ignore_results), file_compare_results))
+}
+
+/// Drop the hashes made with algorithms not used in both of the provided hashes, so that `compare_hashes()` compares
+/// files by the same algorithms on both sides, e.g. when comparing two hashes files.
+///
+/// Returns the remaining algorithms, which are none if the hashes have none in common.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::retain_common_algorithms;
+/// # use checksums::Algorithm;
+/// # use std::collections::BTreeMap;
+/// let mut lhs = BTreeMap::new();
+/// lhs.insert("file".into(), vec![(Algorithm::CRC32, "0B0ADA8E".to_string()), (Algorithm::CRC16, "0BC4".to_string())].into_iter().collect());
+/// let mut rhs = BTreeMap::new();
+/// rhs.insert("file".into(), vec![(Algorithm::CRC32, "0B0ADA8E".to_string())].into_iter().collect());
+///
+/// assert_eq!(retain_common_algorithms(&mut lhs, &mut rhs), vec![Algorithm::CRC32]);
+/// assert_eq!(lhs, rhs);
+/// ```
+pub fn retain_common_algorithms(lhs: &mut Hashes, rhs: &mut Hashes) -> Vec<Algorithm> {
+    let lhs_algos: BTreeSet<_> = lhs.values().flat_map(|h| h.keys().cloned()).collect();
+    let rhs_algos: BTreeSet<_> = rhs.values().flat_map(|h| h.keys().cloned()).collect();
+    let common: Vec<_> = lhs_algos.intersection(&rhs_algos).cloned().collect();
+
+    for file_hashes in lhs.values_mut().chain(rhs.values_mut()) {
+        file_hashes.retain(|algo, _| common.contains(algo));
+    }
+    common
+}
+
+/// Bring loaded hashes up to date with the current ones, based on their comparison with `compare_hashes()`.
+///
+/// Added and copied files are added with their current hashes, moved ones renamed, and removed ones dropped.
+/// Files whose hashes differ are rehashed only if `rehash_changed`, and keep their loaded hashes otherwise.
+///
+/// Ignore placeholders in the loaded hashes are kept as-is, and reported as `FileIgnored` even for files no longer present.
+/// Ignored files that weren't loaded are added with their placeholders.
+///
+/// Returns the updated hashes alongside the comparison results.
+pub fn update_hashes(out_file: &OsStr, current_hashes: Hashes, mut loaded_hashes: Hashes, rehash_changed: bool)
+                     -> Result<(Hashes, Vec<CompareResult>, Vec<CompareFileResult>), CompareError> {
+    let (mut compare_results, file_compare_results) = compare_hashes(out_file, current_hashes.clone(), loaded_hashes.clone())?;
+
+    for res in &mut compare_results {
+        match *res {
+            CompareResult::FileAdded(ref file) |
+            CompareResult::FileCopied { to: ref file, .. } => {
+                loaded_hashes.insert(file.clone(), current_hashes[file].clone());
+            }
+            CompareResult::FileRemoved(ref mut file) => {
+                if is_ignored(&loaded_hashes[&file[..]]) {
+                    *res = CompareResult::FileIgnored(mem::take(file));
+                } else {
+                    loaded_hashes.remove(&file[..]);
+                }
+            }
+            CompareResult::FileMoved { ref from, ref to } => {
+                loaded_hashes.remove(from);
+                loaded_hashes.insert(to.clone(), current_hashes[to].clone());
+            }
+            CompareResult::FileIgnored(ref file) => {
+                if !loaded_hashes.contains_key(file) {
+                    loaded_hashes.insert(file.clone(), current_hashes[file].clone());
+                }
+            }
+            CompareResult::DirectoryAdded(_) |
+            CompareResult::DirectoryRemoved(_) |
+            CompareResult::DigestDiffers { .. } => {}
+        }
+    }
+    if rehash_changed {
+        for fres in &file_compare_results {
+            if let CompareFileResult::FileDiffers { ref file, .. } = *fres {
+                loaded_hashes.insert(file.clone(), current_hashes[file].clone());
+            }
+        }
+    }
+
+    Ok((loaded_hashes, compare_results, file_compare_results))
+}
+
+/// Compare two provided sets of directories.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::{CompareResult, compare_directories};
+/// # use std::collections::BTreeSet;
+/// let current: BTreeSet<_> = vec!["a".into(), "b".into()].into_iter().collect();
+/// let loaded: BTreeSet<_> = vec!["b".into(), "c".into()].into_iter().collect();
+/// assert_eq!(compare_directories(&current, &loaded),
+///            vec![CompareResult::DirectoryAdded("a".into()), CompareResult::DirectoryRemoved("c".into())]);
+/// ```
+pub fn compare_directories(current_directories: &BTreeSet<OsString>, loaded_directories: &BTreeSet<OsString>) -> Vec<CompareResult> {
+    current_directories.difference(loaded_directories)
+        .cloned()
+        .map(CompareResult::DirectoryAdded)
+        .chain(loaded_directories.difference(current_directories).cloned().map(CompareResult::DirectoryRemoved))
+        .collect()
+}
+
+/// Compare two provided sets of directory digests, as made by `tree_digests()`.
+///
+/// Only directories present in both are compared, and only ones whose digests differ are reported.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::{CompareResult, compare_digests};
+/// # use std::collections::BTreeMap;
+/// let current: BTreeMap<_, _> = vec![(".".into(), "1A".to_string()), ("a".into(), "2B".to_string())].into_iter().collect();
+/// let loaded: BTreeMap<_, _> = vec![(".".into(), "3C".to_string()), ("b".into(), "4D".to_string())].into_iter().collect();
+/// assert_eq!(compare_digests(&current, &loaded),
+///            vec![CompareResult::DigestDiffers {
+///                     dir: ".".into(),
+///                     was_digest: "3C".to_string(),
+///                     new_digest: "1A".to_string(),
+///                 }]);
+/// ```
+pub fn compare_digests(current_digests: &Digests, loaded_digests: &Digests) -> Vec<CompareResult> {
+    loaded_digests.iter()
+        .filter_map(|(dir, was_digest)| match current_digests.get(dir) {
+            Some(new_digest) if new_digest != was_digest => {
+                Some(CompareResult::DigestDiffers {
+                    dir: dir.clone(),
+                    was_digest: was_digest.clone(),
+                    new_digest: new_digest.clone(),
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Compare two provided sets of file sizes, as made by `create_sizes()`.
+///
+/// Only files present in both are compared, and only ones whose sizes differ are reported.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::{CompareFileResult, compare_sizes};
+/// # use std::collections::BTreeMap;
+/// let current: BTreeMap<_, _> = vec![("a".into(), 10), ("b".into(), 20)].into_iter().collect();
+/// let loaded: BTreeMap<_, _> = vec![("a".into(), 10), ("b".into(), 21), ("c".into(), 30)].into_iter().collect();
+/// assert_eq!(compare_sizes(&current, &loaded),
+///            vec![CompareFileResult::SizeDiffers {
+///                     file: "b".into(),
+///                     was_size: 21,
+///                     new_size: 20,
+///                 }]);
+/// ```
+pub fn compare_sizes(current_sizes: &BTreeMap<OsString, u64>, loaded_sizes: &BTreeMap<OsString, u64>) -> Vec<CompareFileResult> {
+    loaded_sizes.iter()
+        .filter_map(|(file, &was_size)| match current_sizes.get(file) {
+            Some(&new_size) if new_size != was_size => {
+                Some(CompareFileResult::SizeDiffers {
+                    file: file.clone(),
+                    was_size,
+                    new_size,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Compare two provided sets of file metadata.
+///
+/// Only the kinds present in both sets of a file are compared, and only files present in both are:
+/// ones added or removed are reported by `compare_hashes()`.
+pub fn compare_metadata(current_metadata: &Metadata, loaded_metadata: &Metadata) -> Vec<CompareFileResult> {
+    let mut results = Vec::new();
+    for (file, loaded_values) in loaded_metadata {
+        if let Some(current_values) = current_metadata.get(file) {
+            for (kind, was) in loaded_values {
+                match current_values.get(kind) {
+                    Some(is) if is != was => {
+                        results.push(CompareFileResult::MetadataDiffers {
+                            file: file.clone(),
+                            kind: *kind,
+                            was: was.clone(),
+                            is: is.clone(),
+                        })
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    results
+}
+
+
+fn process_moves(ch: &mut BTreeMap<OsString, BTreeMap<Algorithm, String>>, lh: &mut BTreeMap<OsString, BTreeMap<Algorithm, String>>) -> Vec<CompareResult> {
+    let mut added: BTreeSet<OsString> = ch.iter().filter(|&(key, value)| !lh.contains_key(key) && !is_ignored(value)).map(|(key, _)| key.clone()).collect();
+    if added.is_empty() {
+        return vec![];
+    }
+
+    let mut results = Vec::new();
+    let mut moved = Vec::new();
+    {
+        let mut added_by_hash = HashMap::new();
+        for key in &added {
+            for (algo, hash) in &ch[key] {
+                added_by_hash.entry((*algo, &hash[..])).or_insert_with(Vec::new).push(key.clone());
+            }
+        }
+
+        for (from, loaded_value) in lh.iter().filter(|&(key, value)| !ch.contains_key(key) && !is_ignored(value)) {
+            let to = loaded_value.iter()
+                .next()
+                .and_then(|(algo, hash)| added_by_hash.get(&(*algo, &hash[..])))
+                .and_then(|candidates| candidates.iter().find(|to| added.contains(*to) && same_contents(loaded_value, &ch[*to])))
+                .cloned();
+            if let Some(to) = to {
+                added.remove(&to);
+                moved.push((from.clone(), to));
+            }
+        }
+    }
+
+    {
+        let mut loaded_by_hash = HashMap::new();
+        for (key, value) in lh.iter().filter(|&(_, value)| !is_ignored(value)) {
+            if let Some((algo, hash)) = value.iter().next() {
+                loaded_by_hash.entry((*algo, &hash[..])).or_insert_with(Vec::new).push(key);
+            }
+        }
+
+        for to in added {
+            let from = ch[&to]
+                .iter()
+                .filter_map(|(algo, hash)| loaded_by_hash.get(&(*algo, &hash[..])))
+                .flat_map(|candidates| candidates.iter())
+                .find(|from| same_contents(&lh[**from], &ch[&to]))
+                .map(|from| (*from).clone());
+            if let Some(from) = from {
+                ch.remove(&to);
+                results.push(CompareResult::FileCopied { from, to });
             }
         }
     }
 
-    Ok((vec_merge(remove_results, ignore_results), file_compare_results))
+    for (from, to) in moved {
+        ch.remove(&to);
+        lh.remove(&from);
+        results.push(CompareResult::FileMoved { from, to });
+    }
+
+    results
+}
+
+pub(crate) fn is_ignored(hashes: &BTreeMap<Algorithm, String>) -> bool {
+    // Files left without hashes by retain_common_algorithms() weren't ignored, they can't be compared
+    !hashes.is_empty() && hashes.values().all(|hash| hash.chars().all(|c| c == '-'))
+}
+
+fn tagged_hashes(hashes: &BTreeMap<Algorithm, String>) -> String {
+    if hashes.is_empty() {
+        "none".to_string()
+    } else {
+        hashes.iter().map(|(algo, hash)| format!("{} {}", algo, hash)).collect::<Vec<_>>().join(", ")
+    }
+}
+
+fn same_contents(loaded: &BTreeMap<Algorithm, String>, current: &BTreeMap<Algorithm, String>) -> bool {
+    let mut compared = false;
+    for (algo, loaded_hash) in loaded {
+        if let Some(current_hash) = current.get(algo) {
+            if current_hash != loaded_hash {
+                return false;
+            }
+            compared = true;
+        }
+    }
+    compared
 }
 
 
-fn process_ignores<F, Rc, Rl>(f: F, cres: Rc, lres: Rl, ch: &mut BTreeMap<String, String>, lh: &mut BTreeMap<String, String>) -> Vec<CompareResult>
-    where F: Fn(&str, &str, &BTreeMap<String, String>) -> bool,
-          Rc: Fn(String) -> CompareResult,
-          Rl: Fn(String) -> CompareResult
+fn process_ignores<F, Rc, Rl>(f: F, cres: Rc, lres: Rl, ch: &mut BTreeMap<OsString, BTreeMap<Algorithm, String>>,
+                              lh: &mut BTreeMap<OsString, BTreeMap<Algorithm, String>>)
+                              -> Vec<CompareResult>
+    where F: Fn(&OsStr, &BTreeMap<Algorithm, String>, &BTreeMap<OsString, BTreeMap<Algorithm, String>>) -> bool,
+          Rc: Fn(OsString) -> CompareResult,
+          Rl: Fn(OsString) -> CompareResult
 {
     let mut results = Vec::new();
-    let mut keys_to_remove = Vec::new();
+    let mut keys_to_remove = BTreeSet::new();
 
     process_ignores_iter(&f, &cres, ch, lh, &mut keys_to_remove, &mut results);
     process_ignores_iter(&f, &lres, lh, ch, &mut keys_to_remove, &mut results);
@@ -98,15 +447,15 @@ fn process_ignores<F, Rc, Rl>(f: F, cres: Rc, lres: Rl, ch: &mut BTreeMap<String
     results
 }
 
-fn process_ignores_iter<F, R>(f: &F, res: &R, curr: &BTreeMap<String, String>, other: &BTreeMap<String, String>, keys_to_remove: &mut Vec<String>,
-                              results: &mut Vec<CompareResult>)
-    where F: Fn(&str, &str, &BTreeMap<String, String>) -> bool,
-          R: Fn(String) -> CompareResult
+fn process_ignores_iter<F, R>(f: &F, res: &R, curr: &BTreeMap<OsString, BTreeMap<Algorithm, String>>, other: &BTreeMap<OsString, BTreeMap<Algorithm, String>>,
+                              keys_to_remove: &mut BTreeSet<OsString>, results: &mut Vec<CompareResult>)
+    where F: Fn(&OsStr, &BTreeMap<Algorithm, String>, &BTreeMap<OsString, BTreeMap<Algorithm, String>>) -> bool,
+          R: Fn(OsString) -> CompareResult
 {
     for (key, value) in curr {
-        if f(key, value, other) {
+        // Report files matching on both sides once
+        if f(key, value, other) && keys_to_remove.insert(key.clone()) {
             results.push(res(key.clone()));
-            keys_to_remove.push(key.clone());
         }
     }
 }
@@ -0,0 +1,185 @@
+use self::super::super::util::{backslash_unescape, checksums_escape};
+use self::super::super::{Hasher, hasher};
+use std::io::{BufRead, BufReader, Write};
+use self::super::super::{Algorithm, Error};
+use std::ffi::{OsStr, OsString};
+use std::collections::BTreeMap;
+use self::super::compare;
+use std::path::{Path, PathBuf};
+use once_cell::sync::Lazy;
+use std::str::FromStr;
+use std::fs::File;
+use regex::Regex;
+
+
+/// Subpath->digest mappings of directories, the root one under `"."`, as made by `TreeDigester` and read by `read_digests()`.
+pub type Digests = BTreeMap<OsString, String>;
+
+/// Maker of deterministic per-directory aggregate digests, Merkle tree-style, from file hashes fed one at a time.
+///
+/// The digest of a directory is the hash, with the chosen algorithm, of, for each of its children, in name order,
+/// the child's name, followed by a `/` for subdirectories, a NUL, the file's hash or the subdirectory's digest, and a LF.
+/// Only directories with hashed files in them have digests, and the root one is under `"."`.
+///
+/// Files must be fed in the order they're walked in, i.e. with their names in `Path` order, and so in `stream_hashes()`' order.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::TreeDigester;
+/// # use checksums::{Algorithm, hash_reader};
+/// # use std::collections::BTreeMap;
+/// # use std::ffi::OsStr;
+/// let mut hashes = BTreeMap::new();
+/// hashes.insert(Algorithm::SHA1, "8313958F86F7B15D4775D12886D479C1CFAAA111".to_string());
+///
+/// let mut digester = TreeDigester::new(Algorithm::SHA1);
+/// digester.add(OsStr::new("dir/a.txt"), &hashes);
+/// let digests = digester.finish();
+///
+/// let dir = hash_reader(&mut &b"a.txt\x008313958F86F7B15D4775D12886D479C1CFAAA111\n"[..], Algorithm::SHA1).unwrap();
+/// assert_eq!(digests[OsStr::new("dir")], dir);
+/// assert_eq!(digests[OsStr::new(".")], hash_reader(&mut format!("dir/\0{}\n", dir).as_bytes(), Algorithm::SHA1).unwrap());
+/// ```
+pub struct TreeDigester {
+    algo: Algorithm,
+    /// Names of the directories being fed, outermost first, with their children so far.
+    open: Vec<(OsString, Box<dyn Hasher>)>,
+    digests: Digests,
+}
+
+impl TreeDigester {
+    /// Create a digester for the specified algorithm.
+    pub fn new(algo: Algorithm) -> TreeDigester {
+        TreeDigester {
+            algo,
+            open: vec![(OsString::new(), hasher(algo))],
+            digests: BTreeMap::new(),
+        }
+    }
+
+    /// Feed the hashes of the next file.
+    ///
+    /// Ignored files, and ones without a hash for the digester's algorithm, are skipped.
+    pub fn add(&mut self, fname: &OsStr, hashes: &BTreeMap<Algorithm, String>) {
+        let hash = match hashes.get(&self.algo) {
+            Some(hash) if !compare::is_ignored(hashes) => hash,
+            _ => return,
+        };
+
+        let mut components: Vec<_> = Path::new(fname).components().map(|c| c.as_os_str()).collect();
+        let name = match components.pop() {
+            Some(name) => name,
+            None => return,
+        };
+
+        let common = self.open[1..].iter().zip(&components).take_while(|(open, dir)| open.0 == **dir).count();
+        while self.open.len() > common + 1 {
+            self.close();
+        }
+        for dir in &components[common..] {
+            self.open.push((dir.to_os_string(), hasher(self.algo)));
+        }
+        feed(&mut *self.open.last_mut().unwrap().1, name, false, hash);
+    }
+
+    /// Get the digests of all directories fed, including the root one.
+    pub fn finish(mut self) -> Digests {
+        while self.open.len() > 1 {
+            self.close();
+        }
+        let (_, root) = self.open.pop().unwrap();
+        self.digests.insert(".".into(), root.finish());
+        self.digests
+    }
+
+    fn close(&mut self) {
+        let (name, dir) = self.open.pop().unwrap();
+        let digest = dir.finish();
+        feed(&mut *self.open.last_mut().unwrap().1, &name, true, &digest);
+
+        let mut path = OsString::new();
+        for (parent, _) in &self.open[1..] {
+            path.push(parent);
+            path.push("/");
+        }
+        path.push(&name);
+        self.digests.insert(path, digest);
+    }
+}
+
+
+/// Compute the `TreeDigester` digests of the specified hashes with the specified algorithm, in any order.
+pub fn tree_digests(hashes: &BTreeMap<OsString, BTreeMap<Algorithm, String>>, algo: Algorithm) -> Digests {
+    let mut files: Vec<_> = hashes.iter().collect();
+    files.sort_by(|l, r| Path::new(l.0).cmp(Path::new(r.0)));
+
+    let mut digester = TreeDigester::new(algo);
+    for (fname, file_hashes) in files {
+        digester.add(fname, file_hashes);
+    }
+    digester.finish()
+}
+
+/// Format a hashes file line recording the specified directory's digest with the specified algorithm.
+///
+/// The line is a `#` comment, so that `sha256sum -c`, `shasum -c` et al. skip it.
+/// The name is escaped like in checksums-format lines.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::{format_digest_line, parse_digest_line};
+/// # use checksums::Algorithm;
+/// # use std::ffi::OsStr;
+/// let line = format_digest_line(OsStr::new("dir"), Algorithm::CRC32, "0B0ADA8E");
+/// assert_eq!(line, "# digest CRC32 0B0ADA8E : dir");
+/// assert_eq!(parse_digest_line(&line), Some(("dir".into(), Algorithm::CRC32, "0B0ADA8E".to_string())));
+///
+/// let line = format_digest_line(OsStr::new("tab\tdir "), Algorithm::CRC32, "0B0ADA8E");
+/// assert_eq!(line, "# digest CRC32 0B0ADA8E : tab\\tdir\\x20");
+/// assert_eq!(parse_digest_line(&line), Some(("tab\tdir ".into(), Algorithm::CRC32, "0B0ADA8E".to_string())));
+/// ```
+pub fn format_digest_line(dname: &OsStr, algo: Algorithm, digest: &str) -> String {
+    format!("# digest {} {} : {}", algo, digest, checksums_escape(dname))
+}
+
+/// Parse a hashes file line made by `format_digest_line()`, if it is one.
+pub fn parse_digest_line(line: &str) -> Option<(OsString, Algorithm, String)> {
+    static LINE_RGX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^# digest ([[:alnum:]_-]+) ([[:xdigit:]]+) : (.+)$").unwrap());
+
+    let c = LINE_RGX.captures(line)?;
+    Some((backslash_unescape(&c[3])?, Algorithm::from_str(&c[1]).ok()?, c[2].to_uppercase()))
+}
+
+/// Read the digest lines saved with `write_hashes()` or `HashesWriter::write_digest()` from the specified hashes file,
+/// by algorithm, or fail with the reason it couldn't be read.
+pub fn read_digests(err: &mut dyn Write, file: &(OsString, PathBuf)) -> Result<BTreeMap<Algorithm, Digests>, Error> {
+    let read = File::open(&file.1).and_then(|f| {
+        let mut digests: BTreeMap<Algorithm, Digests> = BTreeMap::new();
+        for line in BufReader::new(f).lines() {
+            if let Some((dname, algo, digest)) = parse_digest_line(&line?) {
+                digests.entry(algo).or_default().insert(dname, digest);
+            }
+        }
+        Ok(digests)
+    });
+    match read {
+        Ok(digests) => Ok(digests),
+        Err(error) => {
+            writeln!(err, "{}: {}", file.0.to_string_lossy(), error).unwrap();
+            Err(Error::HashesFileIoFailure)
+        }
+    }
+}
+
+
+fn feed(hasher: &mut dyn Hasher, name: &OsStr, is_dir: bool, hash: &str) {
+    hasher.update(name.as_encoded_bytes());
+    if is_dir {
+        hasher.update(b"/");
+    }
+    hasher.update(b"\0");
+    hasher.update(hash.as_bytes());
+    hasher.update(b"\n");
+}
@@ -0,0 +1,65 @@
+use self::super::super::util::{backslash_unescape, checksums_escape, relative_name};
+use std::io::{BufRead, BufReader, Write};
+use std::collections::BTreeSet;
+use std::ffi::{OsStr, OsString};
+use self::super::super::Error;
+use self::super::{FileFilter, TreeWalk};
+use std::path::{Path, PathBuf};
+use once_cell::sync::Lazy;
+use std::fs::File;
+use regex::Regex;
+
+
+/// Find the directories under a given path up to a given depth, including empty ones, but not the path itself,
+/// walking it the same way `create_hashes()` does.
+///
+/// Directories ignored by `filter` are skipped, as are ones that couldn't be walked;
+/// its include patterns only apply to files, so they don't skip any.
+pub fn create_directories(path: &Path, filter: &FileFilter, depth: Option<usize>, follow_symlinks: bool) -> BTreeSet<OsString> {
+    TreeWalk::new(path, filter, depth, follow_symlinks)
+        .filter_map(Result::ok)
+        .filter(|(entry, ignored)| entry.file_type().is_dir() && !ignored && entry.depth() != 0)
+        .map(|(entry, _)| relative_name(path, entry.path()))
+        .collect()
+}
+
+/// Format a hashes file line recording the specified directory.
+///
+/// The line is a `#` comment, so that `sha256sum -c`, `shasum -c` et al. skip it.
+/// The name is escaped like in checksums-format lines.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::{format_directory_line, parse_directory_line};
+/// # use std::ffi::OsStr;
+/// let line = format_directory_line(OsStr::new("dir/sub\\dir"));
+/// assert_eq!(line, "# directory dir/sub\\\\dir");
+/// assert_eq!(parse_directory_line(&line), Some("dir/sub\\dir".into()));
+///
+/// let line = format_directory_line(OsStr::new("dir/tab\tdir "));
+/// assert_eq!(line, "# directory dir/tab\\tdir\\x20");
+/// assert_eq!(parse_directory_line(&line), Some("dir/tab\tdir ".into()));
+/// ```
+pub fn format_directory_line(dname: &OsStr) -> String {
+    format!("# directory {}", checksums_escape(dname))
+}
+
+/// Parse a hashes file line made by `format_directory_line()`, if it is one.
+pub fn parse_directory_line(line: &str) -> Option<OsString> {
+    static LINE_RGX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^# directory (.+)$").unwrap());
+
+    LINE_RGX.captures(line).and_then(|c| backslash_unescape(&c[1]))
+}
+
+/// Read the directory lines saved with `write_hashes()` or `HashesWriter::write_directory()` from the specified hashes file,
+/// or fail with the reason it couldn't be read.
+pub fn read_directories(err: &mut dyn Write, file: &(OsString, PathBuf)) -> Result<BTreeSet<OsString>, Error> {
+    match File::open(&file.1).and_then(|f| BufReader::new(f).lines().filter_map(|l| l.map(|l| parse_directory_line(&l)).transpose()).collect()) {
+        Ok(directories) => Ok(directories),
+        Err(error) => {
+            writeln!(err, "{}: {}", file.0.to_string_lossy(), error).unwrap();
+            Err(Error::HashesFileIoFailure)
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::io::{self, BufReader, ErrorKind as IoErrorKind, Read, Write};
+use self::super::super::{Algorithm, hash_reader};
+use self::super::super::util::relative_name;
+use self::super::{PipelineFile, TreeWalk, hash_pipeline};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use self::super::FileFilter;
+use std::ffi::OsString;
+use std::str::FromStr;
+use reflink_copy;
+use std::fs::{self, File};
+
+
+/// Amount of bytes at the start of each file hashed to tell apart same-sized files in `find_duplicates()`,
+/// before reading them whole.
+pub const PARTIAL_HASH_SIZE: u64 = 4096;
+
+
+/// A set of files with the same contents, as found by `find_duplicates()`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DuplicateGroup {
+    /// Size of each file, in bytes.
+    pub size: u64,
+    /// Hash of the contents.
+    pub hash: String,
+    /// Subpaths of the files, in walk order, each with the other subpaths of the same file, if it's hardlinked.
+    ///
+    /// There are always at least two.
+    pub files: Vec<Vec<OsString>>,
+}
+
+impl DuplicateGroup {
+    /// Get the amount of bytes that'd be freed if all the files were one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use checksums::ops::DuplicateGroup;
+    /// let group = DuplicateGroup {
+    ///     size: 1024,
+    ///     hash: "8313958F86F7B15D4775D12886D479C1CFAAA111".to_string(),
+    ///     files: vec![vec!["a".into()], vec!["b".into(), "hardlink-to-b".into()], vec!["c".into()]],
+    /// };
+    /// assert_eq!(group.wasted(), 2048);
+    /// ```
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.files.len() as u64 - 1)
+    }
+}
+
+
+/// A way to make duplicate files share their contents.
+///
+/// # Examples
+///
+/// ```
+/// # use std::str::FromStr;
+/// # use checksums::ops::LinkKind;
+/// assert_eq!(LinkKind::from_str("hard"), Ok(LinkKind::Hard));
+/// assert_eq!(LinkKind::from_str("RefLink"), Ok(LinkKind::Reflink));
+/// ```
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LinkKind {
+    /// Hardlinks, making them one file, with one set of metadata.
+    Hard,
+    /// Copy-on-write clones, sharing storage, but not metadata; only supported by some filesystems, like Btrfs, XFS and APFS.
+    Reflink,
+}
+
+impl FromStr for LinkKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "hard" | "hardlink" => Ok(LinkKind::Hard),
+            "reflink" | "clone" => Ok(LinkKind::Reflink),
+            _ => Err(format!("\"{}\" is not a recognised link kind", s)),
+        }
+    }
+}
+
+
+/// Find groups of files with the same contents under a given path up to a given depth, using the given algorithm,
+/// walking it the same way `create_hashes()` does.
+///
+/// Only files of the same size have the first `PARTIAL_HASH_SIZE` bytes hashed, and only ones whose first bytes match
+/// are hashed whole, in up to `jobs` threads.
+/// Hardlinks to the same file are read once, and don't make a group on their own.
+///
+/// Empty files, and files that aren't included by `filter`, or are ignored by it, are skipped.
+/// Files that couldn't be walked or read are returned alongside the groups with the reason why.
+///
+/// The groups are sorted by size, biggest first.
+pub fn find_duplicates<Wo>(path: &Path, filter: &FileFilter, algo: Algorithm, depth: Option<usize>, follow_symlinks: bool, jobs: usize, pb_out: Wo)
+                           -> (Vec<DuplicateGroup>, BTreeMap<OsString, io::Error>)
+    where Wo: Write
+{
+    let mut errors = BTreeMap::new();
+    let mut by_size: BTreeMap<u64, Vec<Copy>> = BTreeMap::new();
+    let mut seen: BTreeMap<_, usize> = BTreeMap::new();
+    for entry in TreeWalk::new(path, filter, depth, follow_symlinks) {
+        match entry {
+            Ok((entry, ignored)) => {
+                if !entry.file_type().is_file() || ignored {
+                    continue;
+                }
+
+                let filename = relative_name(path, entry.path());
+                match entry.metadata() {
+                    Ok(meta) if meta.len() == 0 => {}
+                    Ok(meta) => {
+                        let copies = by_size.entry(meta.len()).or_default();
+                        match file_id(&meta).and_then(|id| seen.get(&id)) {
+                            Some(&idx) => copies[idx].0.push(filename),
+                            None => {
+                                if let Some(id) = file_id(&meta) {
+                                    seen.insert(id, copies.len());
+                                }
+                                copies.push((vec![filename], entry.into_path()));
+                            }
+                        }
+                    }
+                    Err(error) => {
+                        errors.insert(filename, error.into());
+                    }
+                }
+            }
+            Err(error) => {
+                errors.insert(relative_name(path, error.path().unwrap_or(path)), error.into());
+            }
+        }
+    }
+
+    let mut candidates: BTreeMap<(u64, String), Vec<Copy>> = BTreeMap::new();
+    for (size, copies) in by_size.into_iter().filter(|(_, copies)| copies.len() > 1) {
+        for (names, file) in copies {
+            match File::open(&file).and_then(|f| hash_reader(&mut f.take(PARTIAL_HASH_SIZE), algo)) {
+                Ok(hash) => candidates.entry((size, hash)).or_default().push((names, file)),
+                Err(error) => {
+                    errors.insert(names[0].clone(), error);
+                }
+            }
+        }
+    }
+    candidates.retain(|_, copies| copies.len() > 1);
+
+    // The first bytes of small files are all of them
+    let (small, big): (BTreeMap<_, _>, BTreeMap<_, _>) = candidates.into_iter().partition(|((size, _), _)| *size <= PARTIAL_HASH_SIZE);
+    let mut groups: BTreeMap<(u64, String), Vec<Vec<OsString>>> =
+        small.into_iter().map(|(key, copies)| (key, copies.into_iter().map(|(names, _)| names).collect())).collect();
+
+    let mut names: BTreeMap<OsString, (u64, Vec<OsString>)> = BTreeMap::new();
+    let walk = |emit: &mut dyn FnMut(PipelineFile)| for ((size, _), copies) in big {
+        for (copy_names, file) in copies {
+            let filename = copy_names[0].clone();
+            names.insert(filename.clone(), (size, copy_names));
+            emit(PipelineFile::Hash(filename, file, None));
+        }
+    };
+    let mut hashed = vec![];
+    hash_pipeline(&[algo], None, jobs, pb_out, walk, |filename, _, result| hashed.push((filename, result)));
+    for (filename, result) in hashed {
+        let (size, copy_names) = names.remove(&filename).unwrap();
+        match result {
+            Ok(mut hashes) => groups.entry((size, hashes.remove(&algo).unwrap())).or_default().push(copy_names),
+            Err(error) => {
+                errors.insert(filename, error);
+            }
+        }
+    }
+
+    let mut groups: Vec<_> = groups.into_iter()
+        .filter(|(_, copies)| copies.len() > 1)
+        .map(|((size, hash), files)| {
+            DuplicateGroup {
+                size,
+                hash,
+                files,
+            }
+        })
+        .collect();
+    groups.sort_by(|l, r| r.size.cmp(&l.size).then_with(|| l.files.cmp(&r.files)));
+    (groups, errors)
+}
+
+/// Replace the files in a group of duplicates under a given path with links of the specified kind to the first one,
+/// after checking that their contents still match it byte-for-byte.
+///
+/// Each file is linked to a temporary name beside it first, which is then renamed over it,
+/// so a file that couldn't be linked is left as it was.
+/// Reflinks keep the permissions of the files they replace.
+///
+/// Returns the subpaths of the replaced files, with the reason why, if they couldn't be replaced.
+pub fn link_duplicates(path: &Path, group: &DuplicateGroup, kind: LinkKind) -> BTreeMap<OsString, io::Result<()>> {
+    let original = path.join(&group.files[0][0]);
+
+    let mut results = BTreeMap::new();
+    for fname in group.files[1..].iter().flatten() {
+        let file = path.join(fname);
+        results.insert(fname.clone(), link_duplicate(&original, &file, kind));
+    }
+    results
+}
+
+
+/// Subpaths of a file, and the path to read it at.
+type Copy = (Vec<OsString>, PathBuf);
+
+fn link_duplicate(original: &Path, file: &Path, kind: LinkKind) -> io::Result<()> {
+    if !same_contents(original, file)? {
+        return Err(io::Error::new(IoErrorKind::InvalidData, "contents changed since hashing"));
+    }
+
+    let mut temp_name = OsString::from(".");
+    temp_name.push(file.file_name().unwrap());
+    temp_name.push(".checksums-link");
+    let temp = file.with_file_name(temp_name);
+
+    match kind {
+        LinkKind::Hard => fs::hard_link(original, &temp)?,
+        LinkKind::Reflink => {
+            reflink_copy::reflink(original, &temp)?;
+            if let Err(error) = fs::metadata(file).and_then(|meta| fs::set_permissions(&temp, meta.permissions())) {
+                let _ = fs::remove_file(&temp);
+                return Err(error);
+            }
+        }
+    }
+    fs::rename(&temp, file).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
+
+fn same_contents(lhs: &Path, rhs: &Path) -> io::Result<bool> {
+    let (lhs, rhs) = (File::open(lhs)?, File::open(rhs)?);
+    if lhs.metadata()?.len() != rhs.metadata()?.len() {
+        return Ok(false);
+    }
+
+    let (mut lhs, mut rhs) = (BufReader::new(lhs), BufReader::new(rhs));
+    let (mut lbuf, mut rbuf) = ([0u8; 8192], [0u8; 8192]);
+    loop {
+        let read = lhs.read(&mut lbuf)?;
+        if read == 0 {
+            return Ok(true);
+        }
+        match rhs.read_exact(&mut rbuf[..read]) {
+            Ok(()) => {}
+            Err(ref error) if error.kind() == IoErrorKind::UnexpectedEof => return Ok(false),
+            Err(error) => return Err(error),
+        }
+        if lbuf[..read] != rbuf[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn file_id(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
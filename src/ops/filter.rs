@@ -0,0 +1,163 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+use std::collections::BTreeSet;
+use ignore;
+
+
+/// Names of the per-directory ignore files read by a `FileFilter`, in ascending order of precedence.
+pub const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".checksumsignore"];
+
+
+/// Which files under a directory to hash, according to gitignore-style patterns.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::FileFilter;
+/// # use std::path::Path;
+/// let filter = FileFilter::new(Path::new("/root"),
+///                              &["*.tmp".to_string(), "**/node_modules".to_string(), "!keep.tmp".to_string()],
+///                              &["src".to_string(), "*.md".to_string()],
+///                              false)
+///     .unwrap();
+///
+/// assert!(filter.is_ignored(Path::new("/root/src/a.tmp"), false));
+/// assert!(!filter.is_ignored(Path::new("/root/src/keep.tmp"), false));
+/// assert!(filter.is_ignored(Path::new("/root/src/web/node_modules"), true));
+///
+/// assert!(filter.is_included(Path::new("/root/src/deep/file.rs"), false));
+/// assert!(filter.is_included(Path::new("/root/doc/README.md"), false));
+/// assert!(!filter.is_included(Path::new("/root/doc/index.html"), false));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileFilter {
+    ignore: Gitignore,
+    include: Option<Gitignore>,
+    ignore_files: bool,
+    ignored_paths: BTreeSet<PathBuf>,
+}
+
+/// The state of a `FileFilter` during a single depth-first walk, holding the ignore files of the directories being walked.
+#[derive(Debug)]
+pub struct WalkFilter<'f> {
+    filter: &'f FileFilter,
+    dirs: Vec<(usize, Gitignore)>,
+}
+
+impl FileFilter {
+    /// Create a filter for the specified root directory, ignoring files matching `ignored` and, if `included` isn't empty,
+    /// everything not matching it.
+    ///
+    /// The patterns follow `.gitignore` rules: they're relative to `root` if they contain a non-trailing slash,
+    /// and match at any level otherwise; a trailing slash matches only directories, and a leading `!` negates the pattern.
+    ///
+    /// If `ignore_files` is `true`, the `IGNORE_FILES` in each walked directory are also honoured,
+    /// with files in deeper directories taking precedence, and `ignored` taking precedence over all of them.
+    pub fn new(root: &Path, ignored: &[String], included: &[String], ignore_files: bool) -> Result<FileFilter, ignore::Error> {
+        Ok(FileFilter {
+            ignore: FileFilter::build(root, ignored)?,
+            include: if included.is_empty() {
+                None
+            } else {
+                Some(FileFilter::build(root, included)?)
+            },
+            ignore_files,
+            ignored_paths: BTreeSet::new(),
+        })
+    }
+
+    /// Additionally ignore exactly the specified path, regardless of the patterns.
+    pub fn ignore_path(&mut self, path: PathBuf) {
+        self.ignored_paths.insert(path);
+    }
+
+    /// Start filtering a depth-first walk, which must yield directories before their contents.
+    pub fn start_walk<'f>(&'f self) -> WalkFilter<'f> {
+        WalkFilter {
+            filter: self,
+            dirs: vec![],
+        }
+    }
+
+    /// Check whether the specified path, under the root directory, is matched by an ignore pattern.
+    ///
+    /// Ignored directories shouldn't be descended into.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.ignored_paths.contains(path) || self.ignore.matched(path, is_dir).is_ignore()
+    }
+
+    /// Check whether the specified file, under the root directory, or one of its parents is matched by an ignore pattern,
+    /// for when it's not reached by a walk.
+    ///
+    /// Ignore files aren't honoured.
+    pub fn is_file_ignored(&self, path: &Path) -> bool {
+        self.ignored_paths.contains(path) || self.ignore.matched_path_or_any_parents(path, false).is_ignore()
+    }
+
+    /// Check whether the specified path, under the root directory, or one of its parents is matched by an include pattern,
+    /// or if there are none.
+    pub fn is_included(&self, path: &Path, is_dir: bool) -> bool {
+        match self.include {
+            Some(ref include) => include.matched_path_or_any_parents(path, is_dir).is_ignore(),
+            None => true,
+        }
+    }
+
+    fn build(root: &Path, patterns: &[String]) -> Result<Gitignore, ignore::Error> {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in patterns {
+            builder.add_line(None, pattern)?;
+        }
+        builder.build()
+    }
+}
+
+impl Default for FileFilter {
+    /// A filter that hashes everything.
+    fn default() -> FileFilter {
+        FileFilter {
+            ignore: Gitignore::empty(),
+            include: None,
+            ignore_files: false,
+            ignored_paths: BTreeSet::new(),
+        }
+    }
+}
+
+impl<'f> WalkFilter<'f> {
+    /// Check whether the specified path, at the specified depth of the walk, is ignored,
+    /// and, if it's a directory that isn't, read its ignore files.
+    ///
+    /// Malformed ignore file lines are skipped.
+    pub fn is_ignored(&mut self, path: &Path, depth: usize, is_dir: bool) -> bool {
+        while self.dirs.last().map(|&(dir_depth, _)| dir_depth >= depth).unwrap_or(false) {
+            self.dirs.pop();
+        }
+
+        let ignored = if depth == 0 {
+            false
+        } else if self.filter.ignored_paths.contains(path) {
+            true
+        } else {
+            let matched = self.filter.ignore.matched(path, is_dir);
+            if matched.is_none() {
+                self.dirs.iter().rev().map(|(_, dir)| dir.matched(path, is_dir)).find(|m| !m.is_none()).map(|m| m.is_ignore()).unwrap_or(false)
+            } else {
+                matched.is_ignore()
+            }
+        };
+
+        if is_dir && !ignored && self.filter.ignore_files {
+            let mut builder = GitignoreBuilder::new(path);
+            for fname in &IGNORE_FILES {
+                let file = path.join(fname);
+                if file.is_file() {
+                    builder.add(file);
+                }
+            }
+            self.dirs.push((depth, builder.build().unwrap_or_else(|_| Gitignore::empty())));
+        }
+
+        ignored
+    }
+}
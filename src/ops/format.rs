@@ -0,0 +1,634 @@
+use self::super::super::util::{backslash_escape, backslash_unescape, checksums_escape, mul_str};
+use self::super::{Hashes, MetadataKind, format_digest_line, format_directory_line, format_metadata_line};
+use std::io::{self, BufWriter, Write};
+use self::super::super::Algorithm;
+use std::collections::{BTreeSet, BTreeMap};
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+use tabwriter::TabWriter;
+use std::str::FromStr;
+use std::fs::{self, File};
+use std::fmt;
+use std::mem;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+
+/// A layout of the hashes file.
+///
+/// # Examples
+///
+/// ```
+/// # use std::str::FromStr;
+/// # use checksums::ops::HashesFormat;
+/// assert_eq!(HashesFormat::from_str("checksums"), Ok(HashesFormat::Checksums));
+/// assert_eq!(HashesFormat::from_str("coreutils"), Ok(HashesFormat::Coreutils));
+/// assert_eq!(HashesFormat::from_str("GNU"), Ok(HashesFormat::Coreutils));
+/// assert_eq!(HashesFormat::from_str("bsd"), Ok(HashesFormat::Bsd));
+/// assert_eq!(HashesFormat::from_str("tag"), Ok(HashesFormat::Bsd));
+/// ```
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum HashesFormat {
+    /// `checksums`' own tab-aligned `name  HASH` layout, with a column per algorithm.
+    ///
+    /// Names are escaped the same way as in the `Coreutils` format,
+    /// and so are names containing tabs, as `\t`, or ending with whitespace, as `\xHH`,
+    /// which would otherwise be taken for column separators, and names starting with `#`, which would be taken for comments.
+    Checksums,
+    /// GNU coreutils' `*sum` layout: `hash  name` or `hash *name`.
+    ///
+    /// Names containing backslashes, CRs, LFs or invalid UTF-8 have them escaped with `util::backslash_escape()`
+    /// and the line prefixed with a backslash.
+    ///
+    /// Ignored files have no hash to record, so they're omitted, as `sha256sum -c` et al. would reject their lines.
+    Coreutils,
+    /// BSD-style tagged layout: `ALGO (name) = hash`, as made by `shasum --tag`, `openssl dgst` and BSD `sha256`.
+    ///
+    /// Each line records its own algorithm, so a single file can mix them.
+    /// Names are escaped the same way as in the `Coreutils` format, and ignored files omitted like in it.
+    Bsd,
+}
+
+/// The first line of a hashes file, recording how it was made.
+///
+/// Formatted as a `#` comment, so that `sha256sum -c`, `shasum -c` et al. skip it.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::HashesHeader;
+/// # use checksums::Algorithm;
+/// let header = HashesHeader::new(vec![Algorithm::SHA1, Algorithm::CRC32], None, true);
+/// assert_eq!(header.to_string(),
+///            format!("# checksums v{} algorithms=SHA1,CRC32 depth=-1 follow-symlinks=yes", env!("CARGO_PKG_VERSION")));
+/// assert_eq!(HashesHeader::parse(&header.to_string()), Some(header));
+/// ```
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct HashesHeader {
+    /// Version of `checksums` that made the file.
+    pub version: String,
+    /// Algorithms of untagged hashes, in order.
+    pub algorithms: Vec<Algorithm>,
+    /// Max recursion depth the file was made with, infinite if `None`.
+    pub depth: Option<usize>,
+    /// Whether symlinks were followed when making the file.
+    pub follow_symlinks: bool,
+    /// `key_id()` of the key the hashes were keyed with, if any.
+    pub key_id: Option<String>,
+    /// Whether directories were recorded in the file.
+    pub directories: bool,
+}
+
+impl HashesHeader {
+    /// Create a header for a hashes file being made by this version of `checksums` with the specified parameters.
+    pub fn new(algorithms: Vec<Algorithm>, depth: Option<usize>, follow_symlinks: bool) -> HashesHeader {
+        HashesHeader {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            algorithms,
+            depth,
+            follow_symlinks,
+            key_id: None,
+            directories: false,
+        }
+    }
+
+    /// Parse a hashes file line into a header, if it is one.
+    ///
+    /// Unknown parameters are ignored, but `algorithms` is required.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use checksums::ops::HashesHeader;
+    /// # use checksums::Algorithm;
+    /// assert_eq!(HashesHeader::parse("# checksums v1.2.3 algorithms=BLAKE3 depth=2 follow-symlinks=no key=0123456789ABCDEF directories=yes \
+    ///                                  future=param"),
+    ///            Some(HashesHeader {
+    ///                version: "1.2.3".to_string(),
+    ///                algorithms: vec![Algorithm::BLAKE3],
+    ///                depth: Some(2),
+    ///                follow_symlinks: false,
+    ///                key_id: Some("0123456789ABCDEF".to_string()),
+    ///                directories: true,
+    ///            }));
+    /// assert_eq!(HashesHeader::parse("# checksums v1.2.3 depth=2"), None);
+    /// assert_eq!(HashesHeader::parse("# checksums  8313958F86F7B15D4775D12886D479C1CFAAA111"), None);
+    /// ```
+    pub fn parse(line: &str) -> Option<HashesHeader> {
+        static HEADER_RGX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^# checksums v(\S+)((?: [[:alnum:]-]+=\S*)*)$").unwrap());
+
+        let c = HEADER_RGX.captures(line)?;
+        let mut algorithms = None;
+        let mut depth = None;
+        let mut follow_symlinks = true;
+        let mut key_id = None;
+        let mut directories = false;
+        for param in c[2].split_whitespace() {
+            let mut param = param.splitn(2, '=');
+            match (param.next().unwrap(), param.next().unwrap()) {
+                ("algorithms", algos) => algorithms = Some(algos.split(',').map(Algorithm::from_str).collect::<Result<Vec<_>, _>>().ok()?),
+                ("depth", d) => depth = d.parse().ok(),
+                ("follow-symlinks", f) => follow_symlinks = f != "no",
+                ("key", k) => key_id = Some(k.to_string()),
+                ("directories", d) => directories = d == "yes",
+                _ => {}
+            }
+        }
+
+        Some(HashesHeader {
+            version: c[1].to_string(),
+            algorithms: algorithms?,
+            depth,
+            follow_symlinks,
+            key_id,
+            directories,
+        })
+    }
+}
+
+impl fmt::Display for HashesHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "# checksums v{} algorithms=", self.version)?;
+        for (i, algo) in self.algorithms.iter().enumerate() {
+            if i != 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", algo)?;
+        }
+        match self.depth {
+            Some(depth) => write!(f, " depth={}", depth)?,
+            None => f.write_str(" depth=-1")?,
+        }
+        write!(f, " follow-symlinks={}", if self.follow_symlinks { "yes" } else { "no" })?;
+        if let Some(ref key_id) = self.key_id {
+            write!(f, " key={}", key_id)?;
+        }
+        if self.directories {
+            f.write_str(" directories=yes")?;
+        }
+        Ok(())
+    }
+}
+
+
+impl HashesFormat {
+    /// All formats, in the order they're tried when detecting the format of a hashes file.
+    pub const ALL: [HashesFormat; 3] = [HashesFormat::Checksums, HashesFormat::Coreutils, HashesFormat::Bsd];
+
+
+    /// Detect the format of the specified hashes file lines, `algos` being the algorithms of the hashes in untagged lines.
+    ///
+    /// The first format in `HashesFormat::ALL` that can parse every non-empty line into hashes as long as their algorithms' is chosen,
+    /// then the first that can parse them at all, falling back to `HashesFormat::Checksums` if there's none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use checksums::ops::HashesFormat;
+    /// # use checksums::Algorithm;
+    /// assert_eq!(HashesFormat::detect(&["a.txt  8313958F86F7B15D4775D12886D479C1CFAAA111"], &[Algorithm::SHA1]), HashesFormat::Checksums);
+    /// assert_eq!(HashesFormat::detect(&["8313958f86f7b15d4775d12886d479c1cfaaa111  a.txt", "",
+    ///                                   "\\8d742c1f2d39434771039e98ad854c72f91fcca5 *a\\\\b.txt"], &[Algorithm::SHA1]),
+    ///            HashesFormat::Coreutils);
+    /// assert_eq!(HashesFormat::detect(&["8313958f86f7b15d4775d12886d479c1cfaaa111  001"], &[Algorithm::SHA1]), HashesFormat::Coreutils);
+    /// assert_eq!(HashesFormat::detect(&["SHA1 (a.txt) = 8313958f86f7b15d4775d12886d479c1cfaaa111",
+    ///                                   "MD5(b.txt)= 8c7dd922ad47494fc02c388e12c00eac"], &[Algorithm::SHA1]),
+    ///            HashesFormat::Bsd);
+    /// ```
+    pub fn detect<S: AsRef<str>>(lines: &[S], algos: &[Algorithm]) -> HashesFormat {
+        let mut detector = FormatDetector::new(algos);
+        for (n, line) in lines.iter().enumerate() {
+            detector.feed(n, line.as_ref());
+        }
+        detector.finish().0
+    }
+
+    /// Parse a single hashes file line into a filename, the algorithm, if the line specifies one, and upper-cased hashes,
+    /// if it's in this format.
+    ///
+    /// `Checksums` lines hold `columns` hashes each, `Coreutils` lines only ever one, and `Bsd` lines one tagged hash.
+    ///
+    /// `Checksums` lines prefixed with a backslash, but not holding a validly escaped name, are read verbatim,
+    /// as they were before escaping was introduced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use checksums::ops::HashesFormat;
+    /// # use checksums::Algorithm;
+    /// assert_eq!(HashesFormat::Checksums.parse_line("a b.txt  8313958F86F7B15D4775D12886D479C1CFAAA111", 1),
+    ///            Some(("a b.txt".into(), None, vec!["8313958F86F7B15D4775D12886D479C1CFAAA111".to_string()])));
+    /// assert_eq!(HashesFormat::Checksums.parse_line("a  b.txt  9B9A  CFAAA111", 2),
+    ///            Some(("a  b.txt".into(), None, vec!["9B9A".to_string(), "CFAAA111".to_string()])));
+    /// assert_eq!(HashesFormat::Checksums.parse_line("\\caf\\xC3\\xA9  CFAAA111", 1),
+    ///            Some(("caf\u{e9}".into(), None, vec!["CFAAA111".to_string()])));
+    /// assert_eq!(HashesFormat::Coreutils.parse_line("\\8313958f86f7b15d4775d12886d479c1cfaaa111 *a\\nb.txt", 1),
+    ///            Some(("a\nb.txt".into(), None, vec!["8313958F86F7B15D4775D12886D479C1CFAAA111".to_string()])));
+    /// assert_eq!(HashesFormat::Coreutils.parse_line("a.txt  8313958F86F7B15D4775D12886D479C1CFAAA111", 1), None);
+    /// assert_eq!(HashesFormat::Bsd.parse_line("SHA1 (a (b).txt) = 8313958f86f7b15d4775d12886d479c1cfaaa111", 1),
+    ///            Some(("a (b).txt".into(), Some(Algorithm::SHA1), vec!["8313958F86F7B15D4775D12886D479C1CFAAA111".to_string()])));
+    /// assert_eq!(HashesFormat::Bsd.parse_line("SHA0 (a.txt) = 8313958f86f7b15d4775d12886d479c1cfaaa111", 1), None);
+    /// ```
+    pub fn parse_line(&self, line: &str, columns: usize) -> Option<(OsString, Option<Algorithm>, Vec<String>)> {
+        static CHECKSUMS_COLUMN_RGX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s{2,}([[:xdigit:]-]+)$").unwrap());
+        static COREUTILS_LINE_RGX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\\)?([[:xdigit:]]+) [ *](.+)$").unwrap());
+        static BSD_LINE_RGX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\\)?([[:alnum:]_-]+) ?\((.*)\) ?= ?([[:xdigit:]]+)$").unwrap());
+
+        match *self {
+            HashesFormat::Checksums => {
+                let mut fname = line;
+                let mut hashes = Vec::with_capacity(columns);
+                for _ in 0..columns {
+                    let c = CHECKSUMS_COLUMN_RGX.captures(fname)?;
+                    hashes.push(c[1].to_uppercase());
+                    fname = &fname[..c.get(0).unwrap().start()];
+                }
+                hashes.reverse();
+
+                if fname.is_empty() || columns == 0 {
+                    None
+                } else {
+                    let fname = fname.strip_prefix('\\').and_then(backslash_unescape).unwrap_or_else(|| fname.into());
+                    Some((fname, None, hashes))
+                }
+            }
+            HashesFormat::Coreutils => {
+                if columns != 1 {
+                    return None;
+                }
+
+                COREUTILS_LINE_RGX.captures(line).and_then(|c| {
+                    let fname = if c.get(1).is_some() {
+                        backslash_unescape(&c[3])?
+                    } else {
+                        c[3].into()
+                    };
+                    Some((fname, None, vec![c[2].to_uppercase()]))
+                })
+            }
+            HashesFormat::Bsd => {
+                BSD_LINE_RGX.captures(line).and_then(|c| {
+                    let algo = Algorithm::from_str(&c[2]).ok()?;
+                    let fname = if c.get(1).is_some() {
+                        backslash_unescape(&c[3])?
+                    } else {
+                        c[3].into()
+                    };
+                    Some((fname, Some(algo), vec![c[4].to_uppercase()]))
+                })
+            }
+        }
+    }
+
+    /// Format the hashes file line(s) for a single file, without the trailing newline.
+    ///
+    /// `Checksums` puts all hashes on one line, separated with tabs, to be aligned with a `TabWriter`;
+    /// the other formats hold one hash per line, so they get a line each.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use checksums::ops::HashesFormat;
+    /// # use checksums::Algorithm;
+    /// # use std::ffi::OsStr;
+    /// assert_eq!(HashesFormat::Checksums.format_line(OsStr::new("a.txt"), &[(Algorithm::SHA1, "8313958F86F7B15D4775D12886D479C1CFAAA111")]),
+    ///            "a.txt\t8313958F86F7B15D4775D12886D479C1CFAAA111");
+    /// assert_eq!(HashesFormat::Checksums.format_line(OsStr::new("a.txt"), &[(Algorithm::CRC16, "9B9A"), (Algorithm::CRC32, "CFAAA111")]),
+    ///            "a.txt\t9B9A\tCFAAA111");
+    /// assert_eq!(HashesFormat::Checksums.format_line(OsStr::new("a\tb  "), &[(Algorithm::CRC32, "CFAAA111")]),
+    ///            "\\a\\tb\\x20\\x20\tCFAAA111");
+    /// assert_eq!(HashesFormat::Coreutils.format_line(OsStr::new("a\\b.txt"), &[(Algorithm::SHA1, "8313958F86F7B15D4775D12886D479C1CFAAA111")]),
+    ///            "\\8313958f86f7b15d4775d12886d479c1cfaaa111  a\\\\b.txt");
+    /// assert_eq!(HashesFormat::Bsd.format_line(OsStr::new("a.txt"), &[(Algorithm::CRC16, "9B9A"), (Algorithm::CRC32, "CFAAA111")]),
+    ///            "CRC16 (a.txt) = 9b9a\nCRC32 (a.txt) = cfaaa111");
+    /// ```
+    pub fn format_line(&self, fname: &OsStr, hashes: &[(Algorithm, &str)]) -> String {
+        let escape = fname.to_str().map(|f| f.contains(&['\\', '\n', '\r'][..])).unwrap_or(true);
+        let escaped_fname = if escape {
+            backslash_escape(fname)
+        } else {
+            fname.to_string_lossy().into_owned()
+        };
+        let prefix = if escape { "\\" } else { "" };
+
+        match *self {
+            HashesFormat::Checksums => {
+                // Tabs would be taken for column separators, trailing whitespace for part of one, and leading hashes for comments
+                let mut line = match fname.to_str() {
+                    Some(f) if !escape && !f.contains('\t') && f.trim_end().len() == f.len() && !f.starts_with('#') => f.to_string(),
+                    _ => format!("\\{}", checksums_escape(fname)),
+                };
+                for &(_, hash) in hashes {
+                    line.push('\t');
+                    line.push_str(hash);
+                }
+                line
+            }
+            HashesFormat::Coreutils => {
+                hashes.iter()
+                    .map(|&(_, hash)| format!("{}{}  {}", prefix, hash.to_lowercase(), escaped_fname))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            HashesFormat::Bsd => {
+                hashes.iter()
+                    .map(|&(algo, hash)| format!("{}{} ({}) = {}", prefix, algo, escaped_fname, hash.to_lowercase()))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        }
+    }
+}
+
+impl FromStr for HashesFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "checksums" | "native" => Ok(HashesFormat::Checksums),
+            "coreutils" | "gnu" | "sha1sum" | "sha256sum" | "md5sum" => Ok(HashesFormat::Coreutils),
+            "bsd" | "tag" | "tagged" => Ok(HashesFormat::Bsd),
+            _ => Err(format!("\"{}\" is not a recognised hashes file format", s)),
+        }
+    }
+}
+
+
+/// Detector of the format of hashes file lines fed one at a time, the same way as `HashesFormat::detect()`,
+/// parsing them in each format that can still be chosen, so the lines themselves needn't be kept.
+pub(crate) struct FormatDetector {
+    algos: Vec<Algorithm>,
+    /// In `HashesFormat::ALL` order; ones that failed to parse a line are dropped, except for the `Checksums` fallback.
+    candidates: Vec<Candidate>,
+}
+
+struct Candidate {
+    format: HashesFormat,
+    /// Whether all hashes so far were as long as their algorithms'.
+    lengths_match: bool,
+    /// The hashes parsed so far, or `None` once a line failed to parse.
+    hashes: Option<Hashes>,
+    /// Numbers of the lines that failed to parse.
+    failed: Vec<usize>,
+}
+
+impl FormatDetector {
+    /// Create a detector for lines whose untagged hashes are of `algos`.
+    pub(crate) fn new(algos: &[Algorithm]) -> FormatDetector {
+        FormatDetector {
+            algos: algos.to_vec(),
+            candidates: HashesFormat::ALL.iter()
+                .map(|&format| {
+                    Candidate {
+                        format,
+                        lengths_match: true,
+                        hashes: Some(BTreeMap::new()),
+                        failed: vec![],
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Parse the `n`th line, skipping it if it's empty.
+    pub(crate) fn feed(&mut self, n: usize, line: &str) {
+        if line.is_empty() {
+            return;
+        }
+
+        let algos = &self.algos;
+        for candidate in &mut self.candidates {
+            match candidate.format.parse_line(line, algos.len()) {
+                Some((fname, line_algo, line_hashes)) => {
+                    let line_hashes: Vec<_> = match line_algo {
+                        Some(line_algo) => line_hashes.into_iter().map(|hash| (line_algo, hash)).collect(),
+                        None => algos.iter().cloned().zip(line_hashes).collect(),
+                    };
+                    candidate.lengths_match &= line_hashes.iter().all(|(algo, hash)| hash.len() == algo.hexlen());
+                    if let Some(ref mut hashes) = candidate.hashes {
+                        hashes.entry(fname).or_default().extend(line_hashes);
+                    }
+                }
+                None => {
+                    candidate.hashes = None;
+                    candidate.failed.push(n);
+                }
+            }
+        }
+        self.candidates.retain(|c| c.hashes.is_some() || c.format == HashesFormat::Checksums);
+    }
+
+    /// Get the detected format, and the hashes parsed in it, or the numbers of the lines that failed to parse.
+    pub(crate) fn finish(self) -> (HashesFormat, Result<Hashes, Vec<usize>>) {
+        let chosen = match self.candidates.iter().position(|c| c.hashes.is_some() && c.lengths_match) {
+            Some(chosen) => chosen,
+            None => self.candidates.iter().position(|c| c.hashes.is_some()).unwrap_or(0),
+        };
+        let candidate = self.candidates.into_iter().nth(chosen).unwrap();
+        (candidate.format, candidate.hashes.ok_or(candidate.failed))
+    }
+}
+
+
+/// Max number of lines aligned together by `HashesWriter` in the `Checksums` format.
+pub const ALIGNMENT_BLOCK: usize = 1024;
+
+
+/// Serialiser of hashes to a hashes file, one file at a time.
+///
+/// The hashes are written to the `hashes_temp_file()` beside the hashes file, which replaces it once `finish()`ed,
+/// so a previous hashes file is kept if writing is interrupted or fails.
+///
+/// The hashes file itself is marked as ignored.
+/// Ignored files, and hashes missing for some algorithms, are written as placeholders in the `Checksums` format,
+/// and omitted from the others.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::{HashesFormat, HashesHeader, HashesWriter};
+/// # use checksums::Algorithm;
+/// # use std::collections::BTreeMap;
+/// # use std::env::temp_dir;
+/// # use std::ffi::OsStr;
+/// # use std::fs;
+/// let file = ("hashes.hash".into(), temp_dir().join("checksums-doctest-HashesWriter.hash"));
+/// let mut hashes = BTreeMap::new();
+/// hashes.insert(Algorithm::CRC32, "0B0ADA8E".to_string());
+///
+/// let mut writer = HashesWriter::create(&file, &HashesHeader::new(vec![Algorithm::CRC32], None, true), HashesFormat::Checksums).unwrap();
+/// writer.mark_ignored("a.txt".into());
+/// writer.write(OsStr::new("b.txt"), &hashes).unwrap();
+/// writer.finish().unwrap();
+///
+/// assert!(fs::read_to_string(&file.1).unwrap().ends_with("\na.txt        --------\nb.txt        0B0ADA8E\nhashes.hash  --------\n"));
+/// # fs::remove_file(&file.1).unwrap();
+/// ```
+pub struct HashesWriter {
+    out: Box<dyn Write>,
+    format: HashesFormat,
+    algos: Vec<Algorithm>,
+    ignored: BTreeSet<OsString>,
+    path: PathBuf,
+    /// The file being written, omitted from the hashes, until it's renamed over `path`.
+    temp: Option<(OsString, PathBuf)>,
+    /// Lines written since the last flush.
+    lines: usize,
+}
+
+impl HashesWriter {
+    /// Create the `hashes_temp_file()` of the specified hashes file and write the specified header to it.
+    pub fn create(out_file: &(OsString, PathBuf), header: &HashesHeader, format: HashesFormat) -> io::Result<HashesWriter> {
+        let temp = hashes_temp_file(out_file);
+        let out = File::create(&temp.1)?;
+        let mut out: Box<dyn Write> = match format {
+            HashesFormat::Checksums => Box::new(TabWriter::new(out)),
+            _ => Box::new(BufWriter::new(out)),
+        };
+        writeln!(out, "{}", header)?;
+
+        let mut writer = HashesWriter {
+            out,
+            format,
+            algos: header.algorithms.clone(),
+            ignored: BTreeSet::new(),
+            path: out_file.1.clone(),
+            temp: Some(temp),
+            lines: 0,
+        };
+        writer.mark_ignored(out_file.0.clone());
+        Ok(writer)
+    }
+
+    /// Mark the specified file as ignored for all algorithms, whatever hashes are written for it, if any.
+    ///
+    /// Its entry is written in place of those hashes, or, if none are, in order before the first file whose name sorts after it,
+    /// or by `finish()`.
+    pub fn mark_ignored(&mut self, fname: OsString) {
+        self.ignored.insert(fname);
+    }
+
+    /// Write the hashes of a single file, in the order of the header's algorithms.
+    ///
+    /// Lines in the `Checksums` format are aligned in blocks of `ALIGNMENT_BLOCK`.
+    pub fn write(&mut self, fname: &OsStr, hashes: &BTreeMap<Algorithm, String>) -> io::Result<()> {
+        while self.ignored.first().map(|ignored| ignored.as_os_str() < fname).unwrap_or(false) {
+            let ignored = self.ignored.pop_first().unwrap();
+            self.write_ignored(&ignored)?;
+        }
+
+        if self.temp.as_ref().map(|t| t.0 == fname).unwrap_or(false) {
+            Ok(())
+        } else if self.ignored.remove(fname) {
+            self.write_ignored(fname)
+        } else {
+            self.write_line(fname, hashes)
+        }
+    }
+
+    /// Write a `format_directory_line()` recording a single directory, after all hashes.
+    ///
+    /// The entries of the files marked as ignored that weren't written yet are written first.
+    pub fn write_directory(&mut self, dname: &OsStr) -> io::Result<()> {
+        self.write_pending_ignored()?;
+        writeln!(self.out, "{}", format_directory_line(dname))
+    }
+
+    /// Write a `format_digest_line()` recording a single directory's digest, after all hashes.
+    ///
+    /// The entries of the files marked as ignored that weren't written yet are written first.
+    pub fn write_digest(&mut self, dname: &OsStr, algo: Algorithm, digest: &str) -> io::Result<()> {
+        self.write_pending_ignored()?;
+        writeln!(self.out, "{}", format_digest_line(dname, algo, digest))
+    }
+
+    /// Write a `format_metadata_line()` recording the metadata of a single file, after all hashes.
+    ///
+    /// The entries of the files marked as ignored that weren't written yet are written first.
+    pub fn write_metadata(&mut self, fname: &OsStr, values: &BTreeMap<MetadataKind, String>) -> io::Result<()> {
+        self.write_pending_ignored()?;
+        writeln!(self.out, "{}", format_metadata_line(fname, values))
+    }
+
+    /// Write the entries of the files marked as ignored that weren't written yet, flush the output,
+    /// and replace the hashes file with it.
+    ///
+    /// If this isn't called, or fails, the output is removed, and the hashes file left as it was.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.write_pending_ignored()?;
+        self.out.flush()?;
+
+        // Close the output before renaming it
+        self.out = Box::new(io::sink());
+        fs::rename(&self.temp.as_ref().unwrap().1, &self.path)?;
+        self.temp = None;
+        Ok(())
+    }
+
+    fn write_pending_ignored(&mut self) -> io::Result<()> {
+        for fname in mem::take(&mut self.ignored) {
+            self.write_ignored(&fname)?;
+        }
+        Ok(())
+    }
+
+    fn write_ignored(&mut self, fname: &OsStr) -> io::Result<()> {
+        self.write_line(fname, &BTreeMap::new())
+    }
+
+    fn write_line(&mut self, fname: &OsStr, hashes: &BTreeMap<Algorithm, String>) -> io::Result<()> {
+        let placeholders: Vec<_> = self.algos.iter().map(|algo| mul_str("-", algo.hexlen())).collect();
+        let mut hashes: Vec<_> = self.algos
+            .iter()
+            .zip(&placeholders)
+            .map(|(algo, placeholder)| (*algo, hashes.get(algo).unwrap_or(placeholder).as_str()))
+            .collect();
+        if self.format != HashesFormat::Checksums {
+            // Only the checksums format can hold placeholders
+            hashes.retain(|&(_, hash)| !hash.chars().all(|c| c == '-'));
+            if hashes.is_empty() {
+                return Ok(());
+            }
+        }
+        writeln!(self.out, "{}", self.format.format_line(fname, &hashes))?;
+
+        self.lines += 1;
+        if self.lines == ALIGNMENT_BLOCK {
+            self.lines = 0;
+            self.out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for HashesWriter {
+    fn drop(&mut self) {
+        if let Some((_, ref temp)) = self.temp {
+            self.out = Box::new(io::sink());
+            let _ = fs::remove_file(temp);
+        }
+    }
+}
+
+
+/// Get the name and path of the temporary file `HashesWriter` writes the specified hashes file to: `.FILE.checksums-tmp`, beside it.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::hashes_temp_file;
+/// # use std::path::PathBuf;
+/// assert_eq!(hashes_temp_file(&("dir.hash".into(), PathBuf::from("/dir/dir.hash"))),
+///            (".dir.hash.checksums-tmp".into(), PathBuf::from("/dir/.dir.hash.checksums-tmp")));
+/// ```
+pub fn hashes_temp_file(file: &(OsString, PathBuf)) -> (OsString, PathBuf) {
+    let mut name = OsString::from(".");
+    name.push(&file.0);
+    name.push(".checksums-tmp");
+    let mut path = OsString::from(".");
+    path.push(file.1.file_name().unwrap());
+    path.push(".checksums-tmp");
+
+    (name, file.1.with_file_name(path))
+}
+
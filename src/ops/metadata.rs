@@ -0,0 +1,311 @@
+use self::super::super::util::{backslash_escape, backslash_unescape, checksums_escape, relative_name};
+use self::super::super::hash_string;
+use std::io::{BufRead, BufReader, Write};
+use std::collections::BTreeMap;
+use self::super::{FileFilter, FileStamp, TreeWalk};
+use std::ffi::{OsStr, OsString};
+use self::super::super::Error;
+use std::path::{Component, Path, PathBuf};
+use once_cell::sync::Lazy;
+use std::str::FromStr;
+use std::fs::File;
+use regex::Regex;
+use std::{fmt, fs, io};
+
+
+/// A kind of file metadata that can be recorded besides its contents.
+///
+/// # Examples
+///
+/// ```
+/// # use std::str::FromStr;
+/// # use checksums::ops::MetadataKind;
+/// assert_eq!(MetadataKind::from_str("mode"), Ok(MetadataKind::Mode));
+/// assert_eq!(MetadataKind::from_str("XATTRS"), Ok(MetadataKind::Xattrs));
+/// assert_eq!(MetadataKind::Target.to_string(), "target");
+/// ```
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MetadataKind {
+    /// Permission bits, in octal, or `ro`/`rw` on non-Unix platforms.
+    Mode,
+    /// `uid:gid`; not recorded on non-Unix platforms.
+    Owner,
+    /// Modification time, in seconds and nanoseconds since the epoch.
+    Mtime,
+    /// Backslash-escaped target of a symlink; only recorded for symlinks.
+    Target,
+    /// Number and hash of extended attribute names and values, or `0` if there are none;
+    /// not recorded on non-Unix platforms and filesystems without them.
+    Xattrs,
+}
+
+/// Subpath->kind->value mappings, as made by `create_metadata()` and read by `read_metadata()`.
+pub type Metadata = BTreeMap<OsString, BTreeMap<MetadataKind, String>>;
+
+
+impl MetadataKind {
+    /// All kinds, in the order they're recorded in.
+    pub const ALL: [MetadataKind; 5] = [MetadataKind::Mode, MetadataKind::Owner, MetadataKind::Mtime, MetadataKind::Target, MetadataKind::Xattrs];
+}
+
+impl FromStr for MetadataKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "mode" => Ok(MetadataKind::Mode),
+            "owner" => Ok(MetadataKind::Owner),
+            "mtime" => Ok(MetadataKind::Mtime),
+            "target" => Ok(MetadataKind::Target),
+            "xattrs" => Ok(MetadataKind::Xattrs),
+            _ => Err(format!("\"{}\" is not a recognised metadata kind", s)),
+        }
+    }
+}
+
+impl fmt::Display for MetadataKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            MetadataKind::Mode => "mode",
+            MetadataKind::Owner => "owner",
+            MetadataKind::Mtime => "mtime",
+            MetadataKind::Target => "target",
+            MetadataKind::Xattrs => "xattrs",
+        })
+    }
+}
+
+
+/// Get the specified kinds of metadata of the specified file, following it if it's a symlink and `follow_symlinks`.
+///
+/// Kinds not available for the file or on the platform are omitted.
+pub fn file_metadata(path: &Path, kinds: &[MetadataKind], follow_symlinks: bool) -> io::Result<BTreeMap<MetadataKind, String>> {
+    let link_meta = fs::symlink_metadata(path)?;
+    let meta = if follow_symlinks && link_meta.file_type().is_symlink() {
+        fs::metadata(path)?
+    } else {
+        link_meta.clone()
+    };
+
+    let mut values = BTreeMap::new();
+    for &kind in kinds {
+        let value = match kind {
+            MetadataKind::Mode => Some(mode(&meta)),
+            MetadataKind::Owner => owner(&meta),
+            MetadataKind::Mtime => {
+                let mtime = FileStamp::from_metadata(&meta).mtime;
+                Some(format!("{}.{:09}", mtime.0, mtime.1))
+            }
+            MetadataKind::Target if link_meta.file_type().is_symlink() => Some(backslash_escape(&fs::read_link(path)?)),
+            MetadataKind::Target => None,
+            MetadataKind::Xattrs => xattrs(path, follow_symlinks)?,
+        };
+        if let Some(value) = value {
+            values.insert(kind, value);
+        }
+    }
+    Ok(values)
+}
+
+/// Create subpath->kind->value mappings for a given path using the given metadata kinds up to a given depth,
+/// walking it the same way `create_hashes()` does, without reading any files.
+///
+/// Symlinks are included, even if they aren't followed.
+///
+/// Files whose metadata couldn't be read are omitted, and returned alongside the mappings with the reason why.
+pub fn create_metadata(path: &Path, filter: &FileFilter, kinds: &[MetadataKind], depth: Option<usize>, follow_symlinks: bool)
+                       -> (Metadata, BTreeMap<OsString, io::Error>) {
+    let mut metadata = BTreeMap::new();
+    let mut errors = BTreeMap::new();
+    for (entry, ignored) in TreeWalk::new(path, filter, depth, follow_symlinks).filter_map(Result::ok) {
+        let file_type = entry.file_type();
+        if (file_type.is_file() || file_type.is_symlink()) && !ignored {
+            let filename = relative_name(path, entry.path());
+            match file_metadata(entry.path(), kinds, follow_symlinks) {
+                Ok(values) => {
+                    metadata.insert(filename, values);
+                }
+                Err(error) => {
+                    errors.insert(filename, error);
+                }
+            }
+        }
+    }
+
+    (metadata, errors)
+}
+
+/// Get the metadata of the files listed in `listed` the same way `create_metadata()` does, without walking the path.
+///
+/// Files that don't exist anymore or aren't under the path are skipped,
+/// and ones whose metadata couldn't be read are omitted, and returned alongside the mappings with the reason why.
+pub fn create_listed_metadata<V>(path: &Path, listed: &BTreeMap<OsString, V>, filter: &FileFilter, kinds: &[MetadataKind], follow_symlinks: bool)
+                                 -> (Metadata, BTreeMap<OsString, io::Error>) {
+    let mut metadata = BTreeMap::new();
+    let mut errors = BTreeMap::new();
+    for filename in listed.keys() {
+        let file = path.join(filename);
+        if !Path::new(filename).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) || fs::symlink_metadata(&file).is_err() ||
+           filter.is_file_ignored(&file) || !filter.is_included(&file, false) {
+            continue;
+        }
+
+        match file_metadata(&file, kinds, follow_symlinks) {
+            Ok(values) => {
+                metadata.insert(filename.clone(), values);
+            }
+            Err(error) => {
+                errors.insert(filename.clone(), error);
+            }
+        }
+    }
+
+    (metadata, errors)
+}
+
+/// Format a hashes file line recording the specified file's metadata.
+///
+/// The line is a `#` comment, so that `sha256sum -c`, `shasum -c` et al. skip it.
+/// The name is escaped like in checksums-format lines, and whitespace in the values as `\t` and `\xHH`.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::{MetadataKind, format_metadata_line, parse_metadata_line};
+/// # use std::collections::BTreeMap;
+/// # use std::ffi::OsStr;
+/// let mut values = BTreeMap::new();
+/// values.insert(MetadataKind::Mode, "644".to_string());
+/// values.insert(MetadataKind::Owner, "1000:100".to_string());
+///
+/// let line = format_metadata_line(OsStr::new("dir/a : b"), &values);
+/// assert_eq!(line, "# metadata mode=644 owner=1000:100 : dir/a : b");
+/// assert_eq!(parse_metadata_line(&line), Some(("dir/a : b".into(), values.clone())));
+///
+/// values.insert(MetadataKind::Target, "to\tb c".to_string());
+/// let line = format_metadata_line(OsStr::new("a\tb "), &values);
+/// assert_eq!(line, "# metadata mode=644 owner=1000:100 target=to\\tb\\x20c : a\\tb\\x20");
+/// assert_eq!(parse_metadata_line(&line), Some(("a\tb ".into(), values)));
+/// ```
+pub fn format_metadata_line(fname: &OsStr, values: &BTreeMap<MetadataKind, String>) -> String {
+    let mut line = "# metadata".to_string();
+    for (kind, value) in values {
+        line.push_str(&format!(" {}={}", kind, escape_value(value)));
+    }
+    line.push_str(" : ");
+    line.push_str(&checksums_escape(fname));
+    line
+}
+
+/// Parse a hashes file line made by `format_metadata_line()`, if it is one.
+///
+/// Unknown kinds are ignored.
+pub fn parse_metadata_line(line: &str) -> Option<(OsString, BTreeMap<MetadataKind, String>)> {
+    static LINE_RGX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^# metadata((?: [[:alnum:]-]+=\S*)*) : (.+)$").unwrap());
+
+    let c = LINE_RGX.captures(line)?;
+    let mut values = BTreeMap::new();
+    for kv in c[1].split_whitespace() {
+        let mut kv = kv.splitn(2, '=');
+        if let Ok(kind) = MetadataKind::from_str(kv.next().unwrap()) {
+            // Values are kept backslash-escaped, but with their whitespace back
+            values.insert(kind, backslash_escape(&backslash_unescape(kv.next().unwrap())?));
+        }
+    }
+    Some((backslash_unescape(&c[2])?, values))
+}
+
+/// Read the metadata lines saved with `write_hashes()` or `HashesWriter::write_metadata()` from the specified hashes file,
+/// or fail with the reason it couldn't be read.
+///
+/// Files without metadata lines have no metadata.
+pub fn read_metadata(err: &mut dyn Write, file: &(OsString, PathBuf)) -> Result<Metadata, Error> {
+    match File::open(&file.1).and_then(|f| BufReader::new(f).lines().filter_map(|l| l.map(|l| parse_metadata_line(&l)).transpose()).collect()) {
+        Ok(metadata) => Ok(metadata),
+        Err(error) => {
+            writeln!(err, "{}: {}", file.0.to_string_lossy(), error).unwrap();
+            Err(Error::HashesFileIoFailure)
+        }
+    }
+}
+
+
+fn escape_value(value: &str) -> String {
+    // Values are already backslash-escaped, so only the whitespace that'd split them up is left
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\t' => result.push_str("\\t"),
+            c if c.is_whitespace() => {
+                for byte in c.to_string().bytes() {
+                    result.push_str(&format!("\\x{:02X}", byte));
+                }
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+
+#[cfg(unix)]
+fn mode(meta: &fs::Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    format!("{:o}", meta.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn mode(meta: &fs::Metadata) -> String {
+    if meta.permissions().readonly() { "ro" } else { "rw" }.to_string()
+}
+
+#[cfg(unix)]
+fn owner(meta: &fs::Metadata) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(format!("{}:{}", meta.uid(), meta.gid()))
+}
+
+#[cfg(not(unix))]
+fn owner(_: &fs::Metadata) -> Option<String> {
+    None
+}
+
+#[cfg(unix)]
+fn xattrs(path: &Path, follow_symlinks: bool) -> io::Result<Option<String>> {
+    use xattr;
+
+    let names = if follow_symlinks {
+        xattr::list_deref(path)
+    } else {
+        xattr::list(path)
+    };
+    let mut names: Vec<_> = match names {
+        Ok(names) => names.collect(),
+        // Filesystems without extended attributes
+        Err(ref error) if error.kind() == io::ErrorKind::Unsupported => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    if names.is_empty() {
+        return Ok(Some("0".to_string()));
+    }
+    names.sort();
+
+    let mut hasher = ::blake3::Hasher::new();
+    for name in &names {
+        let value = if follow_symlinks {
+            xattr::get_deref(path, name)?
+        } else {
+            xattr::get(path, name)?
+        };
+        hasher.update(name.as_encoded_bytes()).update(b"\0");
+        hasher.update(&value.unwrap_or_default()).update(b"\0");
+    }
+    Ok(Some(format!("{}:{}", names.len(), hash_string(&hasher.finalize().as_bytes()[..16]))))
+}
+
+#[cfg(not(unix))]
+fn xattrs(_: &Path, _: bool) -> io::Result<Option<String>> {
+    Ok(None)
+}
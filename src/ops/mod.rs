@@ -5,137 +5,568 @@
 //!
 //! Then use `write_hashes()` to save it to disk, or `read_hashes()` to get the saved hashes, them with
 //! `compare_hashes()` and print them with `write_hash_comparison_results()`.
+//! `read_hashes_file()` gets everything else saved alongside them, too.
+//!
+//! For trees too big to keep in memory, use `stream_hashes()` and save each file's hashes with a `HashesWriter` as they come.
+//!
+//! Single files, or standard input, can be hashed on their own with `hash_input()`, and printed with `write_input_hashes()`.
+//!
+//! To only check the files listed in a hashes file, use `create_listed_hashes()` instead of walking the whole tree.
+//!
+//! To tell apart files whose sizes differ without reading them, use `create_sizes()` and `compare_sizes()`.
+//!
+//! Directories, including empty ones, can be recorded alongside the hashes with `create_directories()`,
+//! and checked with `compare_directories()`.
+//!
+//! Per-directory aggregate digests of the hashes, and a single root one for the whole tree,
+//! can be made with a `TreeDigester` or `tree_digests()`.
+//!
+//! Files with the same contents can be found with `find_duplicates()`, and made to share them with `link_duplicates()`.
+//!
+//! Members of tar, tar.gz and zip archives can be hashed without extracting them with `create_archive_hashes()` or `stream_archive_hashes()`,
+//! and their directories and metadata made with `create_archive_directories()` and `create_archive_metadata()`.
+//!
+//! File metadata can be recorded alongside the hashes with `create_metadata()`, and checked with `compare_metadata()`.
+//!
+//! Saved hashes files can be signed with `sign_hashes()`, and checked with `verify_hashes_signature()` before reading them.
 
 
+mod archive;
+mod cache;
 mod compare;
+mod digests;
+mod directories;
+mod dupes;
+mod filter;
+mod format;
+mod metadata;
+mod sign;
 mod write;
 
 use self::super::util::{relative_name, mul_str};
 use std::collections::{BTreeSet, BTreeMap};
-use futures_cpupool::{CpuFuture, CpuPool};
-use std::io::{BufRead, BufReader, Write};
-use self::super::{Algorithm, hash_file};
-use futures::future::Future;
-use walkdir::WalkDir;
-use std::path::{PathBuf, Path};
-use tabwriter::TabWriter;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::io::{self, BufRead, BufReader, Write};
+use self::super::{Algorithm, hash_file_multiple, hash_file_multiple_keyed, hash_reader_multiple, hash_reader_multiple_keyed};
+use std::sync::{Mutex, mpsc};
+use walkdir::{self, DirEntry, WalkDir};
+use std::path::{Component, PathBuf, Path};
+use std::ffi::{OsStr, OsString};
 use self::super::Error;
 use pbr::ProgressBar;
 use std::fs::File;
-use regex::Regex;
-use once_cell::sync::Lazy;
+use std::thread;
 
+pub use self::archive::*;
+pub use self::cache::*;
 pub use self::compare::*;
+pub use self::digests::*;
+pub use self::directories::*;
+pub use self::dupes::*;
+pub use self::filter::*;
+pub use self::format::*;
+pub use self::metadata::*;
+pub use self::sign::*;
 pub use self::write::*;
 
 
-/// Create subpath->hash mappings for a given path using a given algorithm up to a given depth.
-pub fn create_hashes<Wo, We>(path: &Path, ignored_files: BTreeSet<String>, algo: Algorithm, depth: Option<usize>, follow_symlinks: bool, jobs: usize,
-                             pb_out: Wo, pb_err: &mut We)
-                             -> BTreeMap<String, String>
-    where Wo: Write,
-          We: Write
-{
-    let mut walkdir = WalkDir::new(path).follow_links(follow_symlinks);
-    if let Some(depth) = depth {
-        walkdir = walkdir.max_depth(depth + 1);
+/// Subpath->algorithm->hash mappings, as made by `create_hashes()` and read by `read_hashes()`.
+pub type Hashes = BTreeMap<OsString, BTreeMap<Algorithm, String>>;
+
+/// How to walk a tree of files to hash with `create_hashes()` and `stream_hashes()`,
+/// or the members of an archive with `create_archive_hashes()` and `stream_archive_hashes()`.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::{FileFilter, WalkOptions};
+/// let filter = FileFilter::default();
+/// let walk = WalkOptions {
+///     depth: Some(0),
+///     jobs: 4,
+///     ..WalkOptions::new(&filter)
+/// };
+/// assert!(walk.follow_symlinks);
+/// assert!(walk.cache.is_none());
+/// ```
+#[derive(Debug)]
+pub struct WalkOptions<'a> {
+    /// Filter of the files to hash, mark as ignored, or skip.
+    pub filter: &'a FileFilter,
+    /// Max recursion depth. Infinite if None.
+    pub depth: Option<usize>,
+    /// Whether to recurse down symlinks.
+    pub follow_symlinks: bool,
+    /// # of threads used for hashing.
+    pub jobs: usize,
+    /// Hashes of files to reuse if they're unchanged, replaced with entries for all hashed files afterwards, if any.
+    pub cache: Option<&'a mut BTreeMap<OsString, CacheEntry>>,
+    /// Whether to walk archives inside an archive like directories, instead of hashing them.
+    pub nested_archives: bool,
+}
+
+impl<'a> WalkOptions<'a> {
+    /// Walk with the specified filter at infinite depth, following symlinks, hashing in a single thread, without a cache,
+    /// and hashing nested archives.
+    pub fn new(filter: &'a FileFilter) -> WalkOptions<'a> {
+        WalkOptions {
+            filter,
+            depth: None,
+            follow_symlinks: true,
+            jobs: 1,
+            cache: None,
+            nested_archives: false,
+        }
     }
+}
+
+/// Everything saved in a hashes file, as read by `read_hashes_file()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashesFile {
+    /// The header, if any.
+    pub header: Option<HashesHeader>,
+    /// The detected format.
+    pub format: HashesFormat,
+    /// Upper-cased hashes, as read by `read_hashes()`.
+    pub hashes: Hashes,
+    /// Directories, as read by `read_directories()`.
+    pub directories: BTreeSet<OsString>,
+    /// Directory digests by algorithm, as read by `read_digests()`.
+    pub digests: BTreeMap<Algorithm, Digests>,
+    /// Metadata, as read by `read_metadata()`.
+    pub metadata: Metadata,
+}
+
+
+/// Max number of files walked, but not yet passed to the sink, in `stream_hashes()`.
+///
+/// Also caps the number of hashing threads.
+pub const MAX_IN_FLIGHT: usize = 1024;
 
+
+/// Create subpath->algorithm->hash mappings for a given path using the given algorithms, walking it as specified by `walk`.
+///
+/// Files that aren't included by the filter are omitted, and ones ignored by it are marked as such, without being read;
+/// ignored directories aren't walked.
+///
+/// Files that couldn't be walked or read are omitted from the hashes, and returned alongside them with the reason why.
+///
+/// If a `key` is specified, the algorithms are keyed with it, as by `keyed_hasher()`.
+///
+/// If a cache is specified, files whose `FileStamp` matches the cached one aren't read, and their cached hashes are used instead.
+/// Afterwards, the cache is replaced with entries for all hashed files.
+/// The cache must've been made with the same `key`.
+///
+/// This collects the results of `stream_hashes()`.
+pub fn create_hashes<Wo>(path: &Path, algos: &[Algorithm], key: Option<&[u8]>, walk: WalkOptions, pb_out: Wo)
+                         -> (BTreeMap<OsString, BTreeMap<Algorithm, String>>, BTreeMap<OsString, io::Error>)
+    where Wo: Write
+{
     let mut hashes = BTreeMap::new();
-    let mut hashes_f: BTreeMap<String, CpuFuture<String, ()>> = BTreeMap::new();
+    let mut errors = BTreeMap::new();
+
+    stream_hashes(path,
+                  algos,
+                  key,
+                  walk,
+                  pb_out,
+                  |fname, result| match result {
+                      Ok(file_hashes) => {
+                          hashes.insert(fname, file_hashes);
+                      }
+                      Err(error) => {
+                          errors.insert(fname, error);
+                      }
+                  });
+
+    (hashes, errors)
+}
+
+/// Hash the files under a given path like `create_hashes()`, but pass each file's name and hashes, or the reason why
+/// it couldn't be walked or read, to `sink` as soon as they're available, instead of collecting them.
+///
+/// The tree is walked in one thread, the files are hashed in up to `walk.jobs` others, and `sink` is called on the current one,
+/// in walk order, with directory entries sorted by name.
+/// At most `MAX_IN_FLIGHT` files are ever waiting to be passed to `sink`, so memory use doesn't grow with the size of the tree,
+/// apart from the cache, if specified.
+pub fn stream_hashes<Wo, S>(path: &Path, algos: &[Algorithm], key: Option<&[u8]>, walk: WalkOptions, pb_out: Wo, mut sink: S)
+    where Wo: Write,
+          S: FnMut(OsString, io::Result<BTreeMap<Algorithm, String>>)
+{
+    let WalkOptions { filter, depth, follow_symlinks, jobs, mut cache, .. } = walk;
+    let walk_tree = TreeWalk::new(path, filter, depth, follow_symlinks);
 
-    let mut errored = false;
-    let pool = CpuPool::new(jobs);
+    let mut new_cache = BTreeMap::new();
+    let old_cache = cache.as_deref();
 
-    let mut walkdir = walkdir.into_iter();
-    while let Some(entry) = walkdir.next() {
-        match entry {
-            Ok(entry) => {
-                let file_type = entry.file_type();
-                let filename = relative_name(path, entry.path());
-                let ignored = ignored_files.contains(&filename);
+    let walk = move |emit: &mut dyn FnMut(PipelineFile)| {
+        for entry in walk_tree {
+            match entry {
+                Ok((entry, ignored)) => {
+                    if !entry.file_type().is_file() {
+                        continue;
+                    }
 
-                if file_type.is_file() {
+                    let filename = relative_name(path, entry.path());
                     if ignored {
-                        hashes.insert(filename, mul_str("-", algo.hexlen()));
+                        emit(PipelineFile::Done(filename, None, Ok(placeholders(algos))));
                     } else {
-                        hashes_f.insert(filename, pool.spawn_fn(move || Ok(hash_file(entry.path(), algo))));
+                        let stamp = old_cache.and_then(|_| entry.metadata().ok()).map(|meta| FileStamp::from_metadata(&meta));
+                        if let Some(stamp) = stamp {
+                            if let Some(cached) = old_cache.and_then(|c| c.get(&filename)).and_then(|e| e.hashes_for(&stamp, algos)) {
+                                emit(PipelineFile::Done(filename, Some(stamp), Ok(cached)));
+                                continue;
+                            }
+                        }
+
+                        emit(PipelineFile::Hash(filename, entry.into_path(), stamp));
                     }
-                } else if ignored {
-                    walkdir.skip_current_dir();
+                }
+                Err(error) => {
+                    let filename = relative_name(path, error.path().unwrap_or(path));
+                    emit(PipelineFile::Done(filename, None, Err(error.into())));
                 }
             }
-            Err(error) => {
-                errored = true;
-                writeln!(pb_err, "Symlink loop detected at {}", relative_name(path, &error.path().unwrap())).unwrap();
+        }
+    };
+    hash_pipeline(algos, key, jobs, pb_out, walk, |filename, stamp, result| {
+        if let (Some(stamp), Ok(hashes)) = (stamp, &result) {
+            new_cache.insert(filename.clone(),
+                             CacheEntry {
+                                 stamp,
+                                 hashes: hashes.clone(),
+                             });
+        }
+        sink(filename, result);
+    });
+
+    if let Some(cache) = cache.as_mut() {
+        **cache = new_cache;
+    }
+}
+
+/// Create subpath->algorithm->hash mappings for exactly the files listed in `listed`, relative to a given path,
+/// without walking it, using the given algorithms.
+///
+/// Files listed with ignore placeholders, or ignored by `filter`'s patterns, are marked as such without being read;
+/// per-directory ignore files aren't honoured.
+/// Files that don't exist are omitted, and ones that couldn't be read, or aren't under the path, are returned alongside the hashes
+/// with the reason why.
+pub fn create_listed_hashes<Wo>(path: &Path, listed: &Hashes, filter: &FileFilter, algos: &[Algorithm], key: Option<&[u8]>, jobs: usize, pb_out: Wo)
+                                -> (Hashes, BTreeMap<OsString, io::Error>)
+    where Wo: Write
+{
+    let mut hashes = BTreeMap::new();
+    let mut errors = BTreeMap::new();
+
+    let walk = move |emit: &mut dyn FnMut(PipelineFile)| {
+        for (filename, listed_hashes) in listed {
+            let file = path.join(filename);
+            if !Path::new(filename).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+                emit(PipelineFile::Done(filename.clone(), None, Err(io::Error::new(io::ErrorKind::InvalidInput, "not under the directory"))));
+            } else if !file.is_file() || !filter.is_included(&file, false) {
+                continue;
+            } else if compare::is_ignored(listed_hashes) || filter.is_file_ignored(&file) {
+                emit(PipelineFile::Done(filename.clone(), None, Ok(placeholders(algos))));
+            } else {
+                emit(PipelineFile::Hash(filename.clone(), file, None));
             }
         }
+    };
+    hash_pipeline(algos, key, jobs, pb_out, walk, |filename, _, result| match result {
+        Ok(file_hashes) => {
+            hashes.insert(filename, file_hashes);
+        }
+        Err(error) => {
+            errors.insert(filename, error);
+        }
+    });
+
+    (hashes, errors)
+}
+
+/// Find the files under a given path up to a given depth that aren't listed in `listed`, without reading them.
+///
+/// Files that aren't included by `filter`, or are ignored by it, are skipped, as are ones that couldn't be walked.
+pub fn find_unlisted_files(path: &Path, listed: &Hashes, filter: &FileFilter, depth: Option<usize>, follow_symlinks: bool) -> Vec<OsString> {
+    TreeWalk::new(path, filter, depth, follow_symlinks)
+        .filter_map(Result::ok)
+        .filter(|(entry, ignored)| entry.file_type().is_file() && !ignored)
+        .map(|(entry, _)| relative_name(path, entry.path()))
+        .filter(|filename| !listed.contains_key(filename))
+        .collect()
+}
+
+/// Find the sizes of the files under a given path up to a given depth, without reading them,
+/// walking it the same way `create_hashes()` does.
+///
+/// Files that aren't included by `filter`, or are ignored by it, are skipped, as are ones that couldn't be walked.
+pub fn create_sizes(path: &Path, filter: &FileFilter, depth: Option<usize>, follow_symlinks: bool) -> BTreeMap<OsString, u64> {
+    TreeWalk::new(path, filter, depth, follow_symlinks)
+        .filter_map(Result::ok)
+        .filter(|(entry, ignored)| entry.file_type().is_file() && !ignored)
+        .filter_map(|(entry, _)| entry.metadata().ok().map(|meta| (relative_name(path, entry.path()), meta.len())))
+        .collect()
+}
+
+/// Hash the specified file, or standard input if it's `-`, using the given algorithms, keyed with `key`, if specified.
+pub fn hash_input(input: &OsStr, algos: &[Algorithm], key: Option<&[u8]>) -> io::Result<BTreeMap<Algorithm, String>> {
+    if input == "-" {
+        let stdin = io::stdin();
+        let mut stdin = stdin.lock();
+        match key {
+            Some(key) => hash_reader_multiple_keyed(&mut stdin, algos, key),
+            None => hash_reader_multiple(&mut stdin, algos),
+        }
+    } else {
+        match key {
+            Some(key) => hash_file_multiple_keyed(Path::new(input), algos, key),
+            None => hash_file_multiple(Path::new(input), algos),
+        }
     }
+}
+
+/// Serialise the specified hashes to the specified output file in the specified format, preceded by the specified header
+/// and followed by the specified directories, directory digests, and metadata, if any.
+///
+/// The output file itself is marked as ignored, and ignored files are omitted from formats other than `Checksums`, like by `HashesWriter`.
+///
+/// See `HashesWriter` to write them one at a time instead.
+pub fn write_hashes(out_file: &(OsString, PathBuf), header: &HashesHeader, format: HashesFormat, mut hashes: BTreeMap<OsString, BTreeMap<Algorithm, String>>,
+                    directories: &BTreeSet<OsString>, digests: &BTreeMap<Algorithm, Digests>, metadata: &Metadata)
+                    -> io::Result<()> {
+    let mut out = HashesWriter::create(out_file, header, format)?;
 
-    if errored {
-        writeln!(pb_err, "").unwrap();
+    // Keep the output file's entry in order
+    hashes.entry(out_file.0.clone()).or_default();
+    for (fname, file_hashes) in hashes {
+        out.write(&fname, &file_hashes)?;
+    }
+    for dname in directories {
+        out.write_directory(dname)?;
     }
+    for (algo, algo_digests) in digests {
+        for (dname, digest) in algo_digests {
+            out.write_digest(dname, *algo, digest)?;
+        }
+    }
+    for (fname, values) in metadata {
+        out.write_metadata(fname, values)?;
+    }
+    out.finish()
+}
 
+/// Read the header, if any, and upper-cased hashes saved with `write_hashes()` from the specified path
+/// or fail with line numbers not matching pattern, or with the reason the file couldn't be read.
+///
+/// The format of the file is detected with `HashesFormat::detect()`, and returned alongside them.
+///
+/// Lines that don't specify their algorithm are assumed to hold a hash for each of the header's algorithms,
+/// or of `algos` if there's no header, in that order.
+///
+/// Directory, digest and metadata lines are skipped; use `read_hashes_file()` to get them, too.
+pub fn read_hashes(err: &mut dyn Write, file: &(OsString, PathBuf), algos: &[Algorithm])
+                   -> Result<(Option<HashesHeader>, HashesFormat, Hashes), Error> {
+    read_hashes_file(err, file, algos).map(|read| (read.header, read.format, read.hashes))
+}
 
-    let mut pb = ProgressBar::on(pb_out, hashes_f.len() as u64);
-    pb.set_width(Some(80));
-    pb.show_speed = false;
-    pb.show_tick = true;
+/// Read everything saved with `write_hashes()` or a `HashesWriter` in the specified path, like `read_hashes()`,
+/// `read_directories()`, `read_digests()` and `read_metadata()` would, in a single pass.
+///
+/// The file is read one line at a time, so only what's parsed out of it is kept.
+pub fn read_hashes_file(err: &mut dyn Write, file: &(OsString, PathBuf), algos: &[Algorithm]) -> Result<HashesFile, Error> {
+    let read = File::open(&file.1).and_then(|f| {
+        let mut lines = BufReader::new(f).lines();
+        let first = lines.next().transpose()?;
+        let header = first.as_deref().and_then(HashesHeader::parse);
+
+        let mut detector = FormatDetector::new(header.as_ref().map(|h| &h.algorithms[..]).unwrap_or(algos));
+        let mut directories = BTreeSet::new();
+        let mut digests: BTreeMap<Algorithm, Digests> = BTreeMap::new();
+        let mut metadata = BTreeMap::new();
+        // Keep line numbers pointing at the original lines
+        for (n, line) in first.map(Ok).into_iter().chain(lines).enumerate().skip(header.is_some() as usize) {
+            let line = line?;
+            if let Some(dname) = parse_directory_line(&line) {
+                directories.insert(dname);
+            } else if let Some((dname, algo, digest)) = parse_digest_line(&line) {
+                digests.entry(algo).or_default().insert(dname, digest);
+            } else if let Some((fname, values)) = parse_metadata_line(&line) {
+                metadata.insert(fname, values);
+            } else {
+                detector.feed(n, &line);
+            }
+        }
 
-    hashes.extend(hashes_f.into_iter()
-        .map(|(k, f)| {
-            pb.message(&format!("{} ", k));
-            pb.inc();
+        let (format, hashes) = detector.finish();
+        Ok((HashesFile {
+                header,
+                format,
+                hashes: BTreeMap::new(),
+                directories,
+                digests,
+                metadata,
+            },
+            hashes))
+    });
 
-            match f.wait() {
-                Ok(result) => return (k, result),
-                Err(error) => panic!("Failed to hash file \"{}\": {:?}", k, error),
+    match read {
+        Ok((read, Ok(hashes))) => Ok(HashesFile { hashes, ..read }),
+        Ok((_, Err(failed))) => {
+            for n in failed {
+                writeln!(err, "{}:{}: Line doesn't match accepted pattern", file.0.to_string_lossy(), n).unwrap();
             }
-        }));
+            Err(Error::HashesFileParsingFailure)
+        }
+        Err(error) => {
+            writeln!(err, "{}: {}", file.0.to_string_lossy(), error).unwrap();
+            Err(Error::HashesFileIoFailure)
+        }
+    }
+}
 
-    pb.show_tick = false;
-    pb.tick();
-    pb.finish_print("");
-    hashes
+/// Walker of the entries under a path up to a depth, the path itself included, in name order within each directory,
+/// as done by `create_hashes()` and everything else that walks a tree the same way.
+///
+/// Yields each entry with whether `filter` ignores it, or the reason it couldn't be walked;
+/// ignored directories aren't descended into, and files not included by `filter` are skipped.
+struct TreeWalk<'f> {
+    walkdir: walkdir::IntoIter,
+    filter: &'f FileFilter,
+    walk_filter: WalkFilter<'f>,
 }
 
-/// Serialise the specified hashes to the specified output file.
-pub fn write_hashes(out_file: &(String, PathBuf), algo: Algorithm, mut hashes: BTreeMap<String, String>) {
-    let mut out = TabWriter::new(File::create(&out_file.1).unwrap());
+impl<'f> TreeWalk<'f> {
+    fn new(path: &Path, filter: &'f FileFilter, depth: Option<usize>, follow_symlinks: bool) -> TreeWalk<'f> {
+        let mut walkdir = WalkDir::new(path).follow_links(follow_symlinks).sort_by(|l, r| l.file_name().cmp(r.file_name()));
+        if let Some(depth) = depth {
+            walkdir = walkdir.max_depth(depth + 1);
+        }
 
-    hashes.insert(out_file.0.clone(), mul_str("-", algo.hexlen()));
-    for (fname, hash) in hashes {
-        writeln!(&mut out, "{}\t{}", fname, hash).unwrap();
+        TreeWalk {
+            walkdir: walkdir.into_iter(),
+            filter,
+            walk_filter: filter.start_walk(),
+        }
     }
-
-    out.flush().unwrap();
 }
 
-/// Read upper-cased hashes saved with `write_hashes()` from the specified path or fail with line numbers not matching pattern.
-pub fn read_hashes(err: &mut dyn Write, file: &(String, PathBuf)) -> Result<BTreeMap<String, String>, Error> {
-    static LINE_RGX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(.+?)\s{2,}([[:xdigit:]-]+)$").unwrap());
+impl<'f> Iterator for TreeWalk<'f> {
+    type Item = walkdir::Result<(DirEntry, bool)>;
 
-    let mut hashes = BTreeMap::new();
-    let mut failed = false;
-
-    let in_file = BufReader::new(File::open(&file.1).unwrap());
-    for (n, line) in in_file.lines().map(Result::unwrap).enumerate() {
-        if !line.is_empty() {
-            match LINE_RGX.captures(&line) {
-                Some(captures) => {
-                    hashes.insert(captures[1].to_string(), captures[2].to_uppercase());
-                }
-                None => {
-                    failed = true;
-                    writeln!(err, "{}:{}: Line doesn't match accepted pattern", file.0, n).unwrap();
-                }
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match self.walkdir.next()? {
+                Ok(entry) => entry,
+                Err(error) => return Some(Err(error)),
             };
+            let is_dir = entry.file_type().is_dir();
+            if !is_dir && !self.filter.is_included(entry.path(), false) {
+                continue;
+            }
+
+            let ignored = self.walk_filter.is_ignored(entry.path(), entry.depth(), is_dir);
+            if ignored && is_dir {
+                self.walkdir.skip_current_dir();
+            }
+            return Some(Ok((entry, ignored)));
         }
     }
+}
 
-    if !failed {
-        Ok(hashes)
-    } else {
-        Err(Error::HashesFileParsingFailure)
+
+enum PipelineFile {
+    /// A file to read and hash, with its stamp to cache, if any.
+    Hash(OsString, PathBuf, Option<FileStamp>),
+    /// A file whose result is already known.
+    Done(OsString, Option<FileStamp>, io::Result<BTreeMap<Algorithm, String>>),
+}
+
+/// Hash the files emitted by `walk` in one thread in up to `jobs` others,
+/// and pass them to `sink` on the current one, in the order they were emitted.
+///
+/// At most `MAX_IN_FLIGHT` files are ever waiting to be passed to `sink`.
+fn hash_pipeline<Wo, W, S>(algos: &[Algorithm], key: Option<&[u8]>, jobs: usize, pb_out: Wo, walk: W, mut sink: S)
+    where Wo: Write,
+          W: FnOnce(&mut dyn FnMut(PipelineFile)) + Send,
+          S: FnMut(OsString, Option<FileStamp>, io::Result<BTreeMap<Algorithm, String>>)
+{
+    let walked = AtomicUsize::new(0);
+
+    let mut pb = ProgressBar::on(pb_out, 0);
+    pb.set_width(Some(80));
+    pb.show_speed = false;
+    pb.show_tick = true;
+
+    {
+        // Every walked file takes a permit, which is returned once it's passed to the sink
+        let (permit_tx, permit_rx) = mpsc::sync_channel(MAX_IN_FLIGHT);
+        let (job_tx, job_rx) = mpsc::sync_channel::<(usize, OsString, PathBuf, Option<FileStamp>)>(MAX_IN_FLIGHT);
+        let (result_tx, result_rx) = mpsc::channel();
+        let job_rx = Mutex::new(job_rx);
+
+        thread::scope(|scope| {
+            for _ in 0..jobs.clamp(1, MAX_IN_FLIGHT) {
+                let job_rx = &job_rx;
+                let result_tx = result_tx.clone();
+                scope.spawn(move || loop {
+                    let job = job_rx.lock().unwrap().recv();
+                    match job {
+                        Ok((idx, filename, path, stamp)) => {
+                            let hashes = match key {
+                                Some(key) => hash_file_multiple_keyed(&path, algos, key),
+                                None => hash_file_multiple(&path, algos),
+                            };
+                            let _ = result_tx.send((idx, filename, stamp, hashes));
+                        }
+                        Err(_) => break,
+                    }
+                });
+            }
+
+            let walked = &walked;
+            scope.spawn(move || {
+                let mut idx = 0;
+                walk(&mut |file| {
+                    let _ = permit_tx.send(());
+                    walked.fetch_add(1, AtomicOrdering::Relaxed);
+
+                    match file {
+                        PipelineFile::Hash(filename, path, stamp) => {
+                            let _ = job_tx.send((idx, filename, path, stamp));
+                        }
+                        PipelineFile::Done(filename, stamp, result) => {
+                            let _ = result_tx.send((idx, filename, stamp, result));
+                        }
+                    }
+                    idx += 1;
+                });
+            });
+
+            // Pass the results on in walk order, holding back ones that finished early
+            let mut pending = BTreeMap::new();
+            let mut next = 0;
+            for (idx, filename, stamp, result) in result_rx {
+                pb.total = walked.load(AtomicOrdering::Relaxed) as u64;
+                pb.message(&format!("{} ", filename.to_string_lossy()));
+                pb.inc();
+
+                pending.insert(idx, (filename, stamp, result));
+                while let Some((filename, stamp, result)) = pending.remove(&next) {
+                    sink(filename, stamp, result);
+
+                    let _ = permit_rx.recv();
+                    next += 1;
+                }
+            }
+        });
     }
+
+    pb.show_tick = false;
+    pb.tick();
+    pb.finish_print("");
+}
+
+fn placeholders(algos: &[Algorithm]) -> BTreeMap<Algorithm, String> {
+    algos.iter().map(|&algo| (algo, mul_str("-", algo.hexlen()))).collect()
 }
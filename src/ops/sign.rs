@@ -0,0 +1,221 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use blake2::digest::{Update, VariableOutput};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use std::time::{SystemTime, UNIX_EPOCH};
+use blake2::{Blake2b, Digest, VarBlake2b};
+use std::ffi::OsString;
+use self::super::super::Error;
+use std::path::{Path, PathBuf};
+use base64::Engine;
+use std::str::FromStr;
+use std::fs::{self, File};
+
+
+/// An Ed25519 secret key, read from an unencrypted minisign secret key file, as made by `minisign -G -W`.
+#[derive(Debug, Clone)]
+pub struct SecretKey {
+    id: [u8; 8],
+    key: SigningKey,
+}
+
+/// An Ed25519 public key, in the minisign format, as made by `minisign -G`.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::PublicKey;
+/// # use std::str::FromStr;
+/// let key = PublicKey::from_str("RWTSx0Pn2Pz1e4pxWdc7GBraaNME8PmVpfAd40GXKUifWKb0h7rygRtU").unwrap();
+/// assert_eq!(key.id(), "7BF5FCD8E743C7D2");
+///
+/// assert!(PublicKey::from_str("checksums").is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicKey {
+    id: [u8; 8],
+    key: VerifyingKey,
+}
+
+
+impl SecretKey {
+    /// Read a secret key from the specified minisign secret key file.
+    ///
+    /// Password-protected keys aren't supported.
+    pub fn read(path: &Path) -> io::Result<SecretKey> {
+        let data = read_base64(path)?;
+        if data.len() != 158 || &data[0..2] != b"Ed" || &data[4..6] != b"B2" {
+            return Err(invalid_data("not a minisign secret key"));
+        }
+        if data[2..4] != [0, 0] {
+            return Err(invalid_data("password-protected secret keys aren't supported, make one with minisign -G -W"));
+        }
+
+        let mut id = [0; 8];
+        id.copy_from_slice(&data[54..62]);
+        let mut seed = [0; 32];
+        seed.copy_from_slice(&data[62..94]);
+
+        let mut checksum = VarBlake2b::new(32).unwrap();
+        checksum.update(&data[0..2]);
+        checksum.update(&data[54..126]);
+        let mut checksum_ok = false;
+        checksum.finalize_variable(|c| checksum_ok = c == &data[126..158]);
+
+        let key = SigningKey::from_bytes(&seed);
+        if !checksum_ok || key.verifying_key().as_bytes()[..] != data[94..126] {
+            return Err(invalid_data("corrupted secret key"));
+        }
+
+        Ok(SecretKey { id, key })
+    }
+
+    /// Get the public key corresponding to this one.
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            id: self.id,
+            key: self.key.verifying_key(),
+        }
+    }
+}
+
+impl PublicKey {
+    /// Read a public key from the specified minisign public key file.
+    pub fn read(path: &Path) -> io::Result<PublicKey> {
+        PublicKey::from_bytes(&read_base64(path)?).ok_or_else(|| invalid_data("not a minisign public key"))
+    }
+
+    /// Get the key ID, as shown by minisign.
+    pub fn id(&self) -> String {
+        self.id.iter().rev().map(|b| format!("{:02X}", b)).collect()
+    }
+
+    fn from_bytes(data: &[u8]) -> Option<PublicKey> {
+        if data.len() != 42 || &data[0..2] != b"Ed" {
+            return None;
+        }
+
+        let mut id = [0; 8];
+        id.copy_from_slice(&data[2..10]);
+        let mut key = [0; 32];
+        key.copy_from_slice(&data[10..42]);
+
+        Some(PublicKey {
+            id,
+            key: VerifyingKey::from_bytes(&key).ok()?,
+        })
+    }
+}
+
+impl FromStr for PublicKey {
+    type Err = String;
+
+    /// Parse a base64-encoded public key, as passed to `minisign -P`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BASE64.decode(s.trim())
+            .ok()
+            .and_then(|data| PublicKey::from_bytes(&data))
+            .ok_or_else(|| format!("\"{}\" is not a minisign public key", s))
+    }
+}
+
+
+/// Get the name and path of the detached signature of the specified hashes file: `FILE.minisig`.
+pub fn signature_file(file: &(OsString, PathBuf)) -> (OsString, PathBuf) {
+    let mut name = file.0.clone();
+    name.push(".minisig");
+    let mut path = file.1.clone().into_os_string();
+    path.push(".minisig");
+
+    (name, path.into())
+}
+
+/// Sign the specified hashes file with the specified key, saving the signature to its `signature_file()`.
+///
+/// The signature is in the minisign format, of the file's BLAKE2b-512 hash,
+/// and can be checked with `minisign -V` as well as `verify_hashes_signature()`.
+pub fn sign_hashes(file: &(OsString, PathBuf), key: &SecretKey) -> io::Result<()> {
+    let signature = key.key.sign(&prehash(&file.1)?);
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let trusted_comment = format!("timestamp:{}\tfile:{}\thashed", timestamp, file.0.to_string_lossy().replace(['\r', '\n'], " "));
+    let global_signature = key.key.sign(&[&signature.to_bytes()[..], trusted_comment.as_bytes()].concat());
+
+    let mut out = File::create(&signature_file(file).1)?;
+    writeln!(out, "untrusted comment: signature from checksums secret key {}", key.public_key().id())?;
+    writeln!(out, "{}", BASE64.encode([&b"ED"[..], &key.id, &signature.to_bytes()].concat()))?;
+    writeln!(out, "trusted comment: {}", trusted_comment)?;
+    writeln!(out, "{}", BASE64.encode(global_signature.to_bytes()))?;
+    out.flush()
+}
+
+/// Check the specified hashes file's `signature_file()` against the specified key,
+/// or fail with the reason it's missing or doesn't match.
+///
+/// Both signatures of the file's BLAKE2b-512 hash, as made by `sign_hashes()` and `minisign -S`,
+/// and legacy signatures of the file itself, as made by `minisign -S -l`, are accepted.
+pub fn verify_hashes_signature(err: &mut dyn Write, file: &(OsString, PathBuf), key: &PublicKey) -> Result<(), Error> {
+    let sig_file = signature_file(file);
+
+    match check_signature(&sig_file.1, &file.1, key) {
+        Ok(()) => Ok(()),
+        Err(error) => {
+            writeln!(err, "{}: {}", sig_file.0.to_string_lossy(), error).unwrap();
+            Err(Error::SignatureInvalid)
+        }
+    }
+}
+
+
+fn check_signature(sig_path: &Path, path: &Path, key: &PublicKey) -> io::Result<()> {
+    let lines = BufReader::new(File::open(sig_path)?).lines().collect::<io::Result<Vec<_>>>()?;
+    if lines.len() < 4 || !lines[0].starts_with("untrusted comment: ") || !lines[2].starts_with("trusted comment: ") {
+        return Err(invalid_data("not a minisign signature"));
+    }
+    let (signature, global_signature) = match (BASE64.decode(&lines[1]), BASE64.decode(&lines[3])) {
+        (Ok(ref s), Ok(ref g)) if s.len() == 74 && g.len() == 64 => (s.clone(), Signature::from_slice(g).unwrap()),
+        _ => return Err(invalid_data("not a minisign signature")),
+    };
+    if signature[2..10] != key.id {
+        return Err(invalid_data(&format!("signed with a different key, not {}", key.id())));
+    }
+
+    let sig = Signature::from_slice(&signature[10..74]).unwrap();
+    let signed_ok = match &signature[0..2] {
+        b"ED" => key.key.verify(&prehash(path)?, &sig).is_ok(),
+        b"Ed" => key.key.verify(&fs::read(path)?, &sig).is_ok(),
+        _ => return Err(invalid_data("unsupported signature algorithm")),
+    };
+    if !signed_ok {
+        return Err(invalid_data("signature doesn't match"));
+    }
+
+    let trusted_comment = &lines[2]["trusted comment: ".len()..];
+    if key.key.verify(&[&signature[10..74], trusted_comment.as_bytes()].concat(), &global_signature).is_err() {
+        return Err(invalid_data("trusted comment doesn't match"));
+    }
+
+    Ok(())
+}
+
+fn prehash(path: &Path) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut hasher = Blake2b::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buf)? {
+            0 => break,
+            read => Digest::update(&mut hasher, &buf[..read]),
+        }
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+fn read_base64(path: &Path) -> io::Result<Vec<u8>> {
+    let text = fs::read_to_string(path)?;
+    let line = text.lines().find(|l| !l.is_empty() && !l.starts_with("untrusted comment:")).unwrap_or("");
+    BASE64.decode(line.trim()).map_err(|e| invalid_data(&e.to_string()))
+}
+
+fn invalid_data(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what.to_string())
+}
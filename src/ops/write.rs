@@ -1,7 +1,47 @@
-use self::super::{CompareResult, CompareFileResult, CompareError};
+use self::super::{CompareResult, CompareFileResult, CompareError, DuplicateGroup, HashesFormat};
 use self::super::super::util::mul_str;
-use self::super::super::Error;
-use std::io::Write;
+use self::super::super::{Algorithm, Error};
+use std::collections::{BTreeSet, BTreeMap};
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Write};
+use tabwriter::TabWriter;
+use serde_json::Value;
+use std::str::FromStr;
+
+
+/// A presentation of hash comparison results.
+///
+/// # Examples
+///
+/// ```
+/// # use std::str::FromStr;
+/// # use checksums::ops::OutputFormat;
+/// assert_eq!(OutputFormat::from_str("text"), Ok(OutputFormat::Text));
+/// assert_eq!(OutputFormat::from_str("JSON"), Ok(OutputFormat::Json));
+/// assert_eq!(OutputFormat::from_str("ndjson"), Ok(OutputFormat::Ndjson));
+/// ```
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-consumable text, as written by `write_hash_comparison_results()`.
+    Text,
+    /// A single JSON object, as written by `write_hash_comparison_results_json()`.
+    Json,
+    /// Newline-delimited JSON objects, one per result, as written by `write_hash_comparison_results_json()`.
+    Ndjson,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" | "jsonl" | "json-lines" => Ok(OutputFormat::Ndjson),
+            _ => Err(format!("\"{}\" is not a recognised output format", s)),
+        }
+    }
+}
 
 
 /// Write hash comparison results to the output streams in a human-consumable format
@@ -18,13 +58,26 @@ pub fn write_hash_comparison_results<Wo: Write, We: Write>(output: &mut Wo, erro
                     CompareResult::FileAdded(ref file) => write_compare_result(output, "File added: ", file),
                     CompareResult::FileRemoved(ref file) => write_compare_result(output, "File removed: ", file),
                     CompareResult::FileIgnored(ref file) => write_compare_result(output, "File ignored, skipping: ", file),
+                    CompareResult::FileMoved { ref from, ref to } => {
+                        write_compare_result(output, "File moved: ", from);
+                        write_result(output, "  to: ", &to.to_string_lossy(), 4, true);
+                    }
+                    CompareResult::FileCopied { ref from, ref to } => {
+                        write_compare_result(output, "File copied: ", to);
+                        write_result(output, "  from: ", &from.to_string_lossy(), 4, true);
+                    }
+                    CompareResult::DirectoryAdded(ref dir) => write_compare_result(output, "Directory added: ", dir),
+                    CompareResult::DirectoryRemoved(ref dir) => write_compare_result(output, "Directory removed: ", dir),
+                    CompareResult::DigestDiffers { ref dir, .. } => write_compare_result(output, "Directory digest differs: ", dir),
                 }
             }
 
+            // Moved and copied files' contents were verified, too
+            let relocated = compare_results.iter().any(|res| matches!(*res, CompareResult::FileMoved { .. } | CompareResult::FileCopied { .. }));
             if file_compare_results.is_empty() && compare_results.is_empty() {
                 writeln!(output, "No files left to verify").unwrap();
                 Error::NoError
-            } else if file_compare_results.is_empty() {
+            } else if file_compare_results.is_empty() && !relocated {
                 writeln!(output, "No files to verify").unwrap();
                 Error::NoError
             } else {
@@ -32,20 +85,28 @@ pub fn write_hash_comparison_results<Wo: Write, We: Write>(output: &mut Wo, erro
                     writeln!(output, "").unwrap();
                 }
 
-                let mut differed_n = 0;
+                let mut differed = BTreeSet::new();
                 for fres in &file_compare_results {
                     match *fres {
                         CompareFileResult::FileMatches(ref file) => write_file_result_match(output, file),
                         CompareFileResult::FileDiffers { ref file, ref was_hash, ref new_hash } => {
                             write_file_result_diff(output, file, was_hash, new_hash);
-                            differed_n += 1;
+                            differed.insert(file);
+                        }
+                        CompareFileResult::SizeDiffers { ref file, was_size, new_size } => {
+                            write_file_result_diff(output, file, &format!("{} bytes", was_size), &format!("{} bytes", new_size));
+                            differed.insert(file);
+                        }
+                        CompareFileResult::MetadataDiffers { ref file, kind, ref was, ref is } => {
+                            write_file_result_metadata_diff(output, file, &kind.to_string(), was, is);
+                            differed.insert(file);
                         }
                     }
                 }
 
-                match differed_n {
+                match differed.len() {
                     0 => Error::NoError,
-                    n => Error::NFilesDiffer(n),
+                    n => Error::NFilesDiffer(n as i32),
                 }
             }
         }
@@ -75,9 +136,410 @@ pub fn write_hash_comparison_results<Wo: Write, We: Write>(output: &mut Wo, erro
     result
 }
 
+/// Write the changes made by `update_hashes()` to the output streams in a human-consumable format, followed by their summary
+///
+/// Returns `Error::NFilesDiffer` with the amount of changed files if they weren't rehashed,
+/// or `Error::HashLengthDiffers` if the hashes couldn't be compared.
+pub fn write_hash_update_results<Wo: Write, We: Write>(output: &mut Wo, error: &mut We,
+                                                       results: Result<(Vec<CompareResult>, Vec<CompareFileResult>), CompareError>,
+                                                       rehash_changed: bool)
+                                                       -> Error {
+    let (mut compare_results, mut file_compare_results) = match results {
+        Ok(results) => results,
+        Err(err) => return write_hash_comparison_results(output, error, Err(err)),
+    };
+    compare_results.sort();
+    file_compare_results.sort();
+
+    let (mut added_n, mut removed_n, mut moved_n, mut copied_n, mut directories_n) = (0, 0, 0, 0, 0);
+    for res in &compare_results {
+        match *res {
+            CompareResult::FileAdded(ref file) => {
+                write_compare_result(output, "File added: ", file);
+                added_n += 1;
+            }
+            CompareResult::FileRemoved(ref file) => {
+                write_compare_result(output, "File removed: ", file);
+                removed_n += 1;
+            }
+            CompareResult::FileIgnored(_) => {}
+            CompareResult::FileMoved { ref from, ref to } => {
+                write_compare_result(output, "File moved: ", from);
+                write_result(output, "  to: ", &to.to_string_lossy(), 4, true);
+                moved_n += 1;
+            }
+            CompareResult::FileCopied { ref from, ref to } => {
+                write_compare_result(output, "File copied: ", to);
+                write_result(output, "  from: ", &from.to_string_lossy(), 4, true);
+                copied_n += 1;
+            }
+            CompareResult::DirectoryAdded(ref dir) => {
+                write_compare_result(output, "Directory added: ", dir);
+                directories_n += 1;
+            }
+            CompareResult::DirectoryRemoved(ref dir) => {
+                write_compare_result(output, "Directory removed: ", dir);
+                directories_n += 1;
+            }
+            CompareResult::DigestDiffers { .. } => {}
+        }
+    }
+
+    let mut changed_n = 0;
+    for fres in &file_compare_results {
+        if let CompareFileResult::FileDiffers { ref file, ref was_hash, ref new_hash } = *fres {
+            if rehash_changed {
+                write_compare_result(output, "File rehashed: ", file);
+                write_result(output, "  Was: ", was_hash, 4, false);
+                write_result(output, "  Is : ", new_hash, 4, false);
+            } else {
+                write_file_result_diff(output, file, was_hash, new_hash);
+            }
+            changed_n += 1;
+        }
+    }
+
+    if added_n + removed_n + moved_n + copied_n + directories_n + changed_n != 0 {
+        writeln!(output).unwrap();
+    }
+    writeln!(output,
+             "{} added, {} removed, {} moved, {} copied, {} {}",
+             added_n,
+             removed_n,
+             moved_n,
+             copied_n,
+             changed_n,
+             if rehash_changed { "rehashed" } else { "changed and kept" })
+        .unwrap();
+    output.flush().unwrap();
+    error.flush().unwrap();
+
+    match changed_n {
+        n if n != 0 && !rehash_changed => Error::NFilesDiffer(n),
+        _ => Error::NoError,
+    }
+}
+
+/// Write the reasons files couldn't be hashed and hash comparison results to the output stream as JSON
+///
+/// If `ndjson` is `false`, a single object is written, with a `"results"` array and either a `"summary"` object
+/// or, if the hashes couldn't be compared, an `"error"` object.
+/// Otherwise, each result, then the summary or error, are written as separate objects on separate lines.
+///
+/// A result is an object with the `"result"`
+/// (one of `"unreadable"`, `"added"`, `"removed"`, `"ignored"`, `"moved"`, `"copied"`, `"directory_added"`, `"directory_removed"`,
+/// `"digest_differs"`, `"matches"`, `"differs"` and `"metadata_differs"`) and `"file"` keys, the latter also naming directories,
+/// and, depending on the result, `"error"`, `"from"`, `"was_hash"` and `"new_hash"`, `"was_size"` and `"new_size"`,
+/// `"was_digest"` and `"new_digest"`, or `"kind"`, `"was"` and `"is"`.
+/// The summary has the count of each result.
+///
+/// Returns the same value as `write_hash_comparison_results()` would,
+/// or `Error::NFilesUnreadable`, if there were unreadable files and all others matched.
+pub fn write_hash_comparison_results_json<Wo: Write>(output: &mut Wo, errors: &BTreeMap<OsString, io::Error>,
+                                                     results: Result<(Vec<CompareResult>, Vec<CompareFileResult>), CompareError>, ndjson: bool)
+                                                     -> Error {
+    let mut results_json = Vec::new();
+    for (file, error) in errors {
+        write_json_result(output,
+                          &mut results_json,
+                          json!({"result": "unreadable", "file": file.to_string_lossy(), "error": error.to_string()}),
+                          ndjson);
+    }
+
+    let (result, tail) = match results {
+        Ok((mut compare_results, mut file_compare_results)) => {
+            compare_results.sort();
+            file_compare_results.sort();
+
+            let (mut added_n, mut removed_n, mut ignored_n, mut moved_n, mut copied_n) = (0, 0, 0, 0, 0);
+            let (mut directories_added_n, mut directories_removed_n, mut digests_differed_n) = (0, 0, 0);
+            let (mut matched_n, mut differed_n, mut metadata_differed_n) = (0, 0, 0);
+            let mut differed = BTreeSet::new();
+            for res in compare_results {
+                let res = match res {
+                    CompareResult::FileAdded(file) => {
+                        added_n += 1;
+                        json!({"result": "added", "file": file.to_string_lossy()})
+                    }
+                    CompareResult::FileRemoved(file) => {
+                        removed_n += 1;
+                        json!({"result": "removed", "file": file.to_string_lossy()})
+                    }
+                    CompareResult::FileIgnored(file) => {
+                        ignored_n += 1;
+                        json!({"result": "ignored", "file": file.to_string_lossy()})
+                    }
+                    CompareResult::FileMoved { from, to } => {
+                        moved_n += 1;
+                        json!({"result": "moved", "file": to.to_string_lossy(), "from": from.to_string_lossy()})
+                    }
+                    CompareResult::FileCopied { from, to } => {
+                        copied_n += 1;
+                        json!({"result": "copied", "file": to.to_string_lossy(), "from": from.to_string_lossy()})
+                    }
+                    CompareResult::DirectoryAdded(dir) => {
+                        directories_added_n += 1;
+                        json!({"result": "directory_added", "file": dir.to_string_lossy()})
+                    }
+                    CompareResult::DirectoryRemoved(dir) => {
+                        directories_removed_n += 1;
+                        json!({"result": "directory_removed", "file": dir.to_string_lossy()})
+                    }
+                    CompareResult::DigestDiffers { dir, was_digest, new_digest } => {
+                        digests_differed_n += 1;
+                        json!({"result": "digest_differs", "file": dir.to_string_lossy(), "was_digest": was_digest, "new_digest": new_digest})
+                    }
+                };
+                write_json_result(output, &mut results_json, res, ndjson);
+            }
+            for fres in file_compare_results {
+                let fres = match fres {
+                    CompareFileResult::FileMatches(file) => {
+                        matched_n += 1;
+                        json!({"result": "matches", "file": file.to_string_lossy()})
+                    }
+                    CompareFileResult::FileDiffers { file, was_hash, new_hash } => {
+                        differed_n += 1;
+                        differed.insert(file.clone());
+                        json!({"result": "differs", "file": file.to_string_lossy(), "was_hash": was_hash, "new_hash": new_hash})
+                    }
+                    CompareFileResult::SizeDiffers { file, was_size, new_size } => {
+                        differed_n += 1;
+                        differed.insert(file.clone());
+                        json!({"result": "differs", "file": file.to_string_lossy(), "was_size": was_size, "new_size": new_size})
+                    }
+                    CompareFileResult::MetadataDiffers { file, kind, was, is } => {
+                        metadata_differed_n += 1;
+                        differed.insert(file.clone());
+                        json!({"result": "metadata_differs", "file": file.to_string_lossy(), "kind": kind.to_string(), "was": was, "is": is})
+                    }
+                };
+                write_json_result(output, &mut results_json, fres, ndjson);
+            }
+
+            (match (differed.len(), errors.len()) {
+                 (0, 0) => Error::NoError,
+                 (0, n) => Error::NFilesUnreadable(n as i32),
+                 (n, _) => Error::NFilesDiffer(n as i32),
+             },
+             ("summary",
+              json!({
+                  "unreadable": errors.len(),
+                  "added": added_n,
+                  "removed": removed_n,
+                  "ignored": ignored_n,
+                  "moved": moved_n,
+                  "copied": copied_n,
+                  "directories_added": directories_added_n,
+                  "directories_removed": directories_removed_n,
+                  "digests_differ": digests_differed_n,
+                  "matches": matched_n,
+                  "differs": differed_n,
+                  "metadata_differs": metadata_differed_n,
+              })))
+        }
+        Err(CompareError::HashLengthDiffers { previous_len, current_len }) => {
+            (Error::HashLengthDiffers,
+             ("error",
+              json!({
+                  "kind": "hash_length_differs",
+                  "previous_len": previous_len,
+                  "current_len": current_len,
+              })))
+        }
+    };
+
+    if ndjson {
+        writeln!(output, "{}", json!({ tail.0: tail.1 })).unwrap();
+    } else {
+        writeln!(output, "{}", json!({"results": results_json, tail.0: tail.1})).unwrap();
+    }
+    output.flush().unwrap();
+
+    result
+}
+
+/// Write the reasons files couldn't be hashed to the output stream in a human-consumable format
+///
+/// Returns `Error::NFilesUnreadable` with the amount of such files, if any.
+pub fn write_hashing_errors<W: Write>(output: &mut W, errors: &BTreeMap<OsString, io::Error>) -> Error {
+    for (file, error) in errors {
+        write_compare_result(output, "Couldn't read file: ", file);
+        write_result(output, "  ", &error.to_string(), 4, false);
+    }
+
+    if !errors.is_empty() {
+        writeln!(output).unwrap();
+    }
+    output.flush().unwrap();
+
+    match errors.len() {
+        0 => Error::NoError,
+        n => Error::NFilesUnreadable(n as i32),
+    }
+}
+
+/// Write the hashes of individually hashed files to the output stream as hashes file lines in the specified format,
+/// with the given algorithms' hashes in order, and the reasons the others couldn't be hashed to the error stream
+///
+/// Returns `Error::NFilesUnreadable` with the amount of files that couldn't be hashed, if any.
+///
+/// # Examples
+///
+/// ```
+/// # use checksums::ops::{HashesFormat, write_input_hashes};
+/// # use checksums::{Algorithm, Error};
+/// # use std::collections::BTreeMap;
+/// let mut hashes = BTreeMap::new();
+/// hashes.insert(Algorithm::SHA1, "8313958F86F7B15D4775D12886D479C1CFAAA111".to_string());
+///
+/// let mut out = vec![];
+/// assert_eq!(write_input_hashes(&mut out, &mut vec![], HashesFormat::Coreutils, &[Algorithm::SHA1], vec![("-".into(), Ok(hashes))]),
+///            Error::NoError);
+/// assert_eq!(String::from_utf8(out).unwrap(), "8313958f86f7b15d4775d12886d479c1cfaaa111  -\n");
+/// ```
+pub fn write_input_hashes<Wo: Write, We: Write>(output: &mut Wo, error: &mut We, format: HashesFormat, algos: &[Algorithm],
+                                                hashes: Vec<(OsString, io::Result<BTreeMap<Algorithm, String>>)>)
+                                                -> Error {
+    let mut errors = BTreeMap::new();
+    {
+        let mut output: Box<dyn Write> = match format {
+            HashesFormat::Checksums => Box::new(TabWriter::new(&mut *output)),
+            _ => Box::new(&mut *output),
+        };
+        for (fname, result) in hashes {
+            match result {
+                Ok(hashes) => {
+                    let hashes: Vec<_> = algos.iter().filter_map(|algo| hashes.get(algo).map(|hash| (*algo, &hash[..]))).collect();
+                    writeln!(output, "{}", format.format_line(&fname, &hashes)).unwrap();
+                }
+                Err(error) => {
+                    errors.insert(fname, error);
+                }
+            }
+        }
+        output.flush().unwrap();
+    }
 
-fn write_compare_result<W: Write>(out: &mut W, pre: &str, fname: &str) {
-    write_result(out, pre, fname, 2, true)
+    write_hashing_errors(error, &errors)
+}
+
+
+/// Write groups of identical files found by `find_duplicates()`, and the results of `link_duplicates()`, if any,
+/// to the output stream in a human-consumable format, followed by their summary
+///
+/// Returns `Error::NFilesUnreadable` with the amount of files that couldn't be linked, if any.
+pub fn write_duplicates<W: Write>(output: &mut W, groups: &[DuplicateGroup], links: &BTreeMap<OsString, io::Result<()>>) -> Error {
+    let mut link_failed_n = 0;
+    for group in groups {
+        writeln!(output, "Identical files, {} bytes each:", group.size).unwrap();
+        write_result(output, "  Hash: ", &group.hash, 4, false);
+        for copy in &group.files {
+            for (i, fname) in copy.iter().enumerate() {
+                match links.get(fname) {
+                    Some(Ok(())) => write_result(output, "  Linked: ", &fname.to_string_lossy(), 4, true),
+                    Some(Err(error)) => {
+                        write_result(output, "  Couldn't link: ", &fname.to_string_lossy(), 4, true);
+                        write_result(output, "    ", &error.to_string(), 6, false);
+                        link_failed_n += 1;
+                    }
+                    None if i != 0 => write_result(output, "  Same file: ", &fname.to_string_lossy(), 4, true),
+                    None => write_result(output, "  ", &fname.to_string_lossy(), 4, true),
+                }
+            }
+        }
+        writeln!(output).unwrap();
+    }
+
+    if groups.is_empty() {
+        writeln!(output, "No identical files").unwrap();
+    } else {
+        writeln!(output,
+                 "{} groups, {} bytes duplicated, {} files linked",
+                 groups.len(),
+                 groups.iter().map(DuplicateGroup::wasted).sum::<u64>(),
+                 links.len() - link_failed_n)
+            .unwrap();
+    }
+    output.flush().unwrap();
+
+    match link_failed_n {
+        0 => Error::NoError,
+        n => Error::NFilesUnreadable(n as i32),
+    }
+}
+
+/// Write the reasons files couldn't be hashed, groups of identical files found by `find_duplicates()`,
+/// and the results of `link_duplicates()`, if any, to the output stream as JSON
+///
+/// If `ndjson` is `false`, a single object is written, with a `"results"` array and a `"summary"` object.
+/// Otherwise, each result, then the summary, are written as separate objects on separate lines.
+///
+/// A result is an object with the `"result"` key, one of `"unreadable"`, with `"file"` and `"error"`,
+/// `"duplicates"`, with `"size"`, `"hash"` and `"files"`, an array of arrays of names of the same file,
+/// `"linked"`, with `"file"`, and `"link_failed"`, with `"file"` and `"error"`.
+/// The summary has the count of unreadable files, groups, duplicated bytes, and linked and unlinked files.
+///
+/// Returns `Error::NFilesUnreadable` with the amount of files that couldn't be read or linked, if any.
+pub fn write_duplicates_json<W: Write>(output: &mut W, errors: &BTreeMap<OsString, io::Error>, groups: &[DuplicateGroup],
+                                       links: &BTreeMap<OsString, io::Result<()>>, ndjson: bool)
+                                       -> Error {
+    let mut results_json = Vec::new();
+    for (file, error) in errors {
+        write_json_result(output,
+                          &mut results_json,
+                          json!({"result": "unreadable", "file": file.to_string_lossy(), "error": error.to_string()}),
+                          ndjson);
+    }
+    for group in groups {
+        let files: Vec<Vec<_>> = group.files.iter().map(|copy| copy.iter().map(|fname| fname.to_string_lossy()).collect()).collect();
+        write_json_result(output,
+                          &mut results_json,
+                          json!({"result": "duplicates", "size": group.size, "hash": group.hash, "files": files}),
+                          ndjson);
+    }
+    let mut link_failed_n = 0;
+    for (file, result) in links {
+        let res = match *result {
+            Ok(()) => json!({"result": "linked", "file": file.to_string_lossy()}),
+            Err(ref error) => {
+                link_failed_n += 1;
+                json!({"result": "link_failed", "file": file.to_string_lossy(), "error": error.to_string()})
+            }
+        };
+        write_json_result(output, &mut results_json, res, ndjson);
+    }
+
+    let summary = json!({
+        "unreadable": errors.len(),
+        "groups": groups.len(),
+        "duplicated_bytes": groups.iter().map(DuplicateGroup::wasted).sum::<u64>(),
+        "linked": links.len() - link_failed_n,
+        "link_failed": link_failed_n,
+    });
+    if ndjson {
+        writeln!(output, "{}", json!({ "summary": summary })).unwrap();
+    } else {
+        writeln!(output, "{}", json!({"results": results_json, "summary": summary})).unwrap();
+    }
+    output.flush().unwrap();
+
+    match errors.len() + link_failed_n {
+        0 => Error::NoError,
+        n => Error::NFilesUnreadable(n as i32),
+    }
+}
+
+fn write_json_result<W: Write>(out: &mut W, results: &mut Vec<Value>, result: Value, ndjson: bool) {
+    if ndjson {
+        writeln!(out, "{}", result).unwrap();
+    } else {
+        results.push(result);
+    }
+}
+
+fn write_compare_result<W: Write>(out: &mut W, pre: &str, fname: &OsStr) {
+    write_result(out, pre, &fname.to_string_lossy(), 2, true)
 }
 
 fn write_result<W: Write>(out: &mut W, pre: &str, fname: &str, fname_indent: usize, quote: bool) {
@@ -97,19 +559,33 @@ fn write_result<W: Write>(out: &mut W, pre: &str, fname: &str, fname_indent: usi
     }
 }
 
-fn write_file_result_match<W: Write>(out: &mut W, fname: &str) {
+fn write_file_result_match<W: Write>(out: &mut W, fname: &OsStr) {
+    let fname = fname.to_string_lossy();
     if 15 + fname.len() <= 80 {
         writeln!(out, "File \"{}\" matches", fname).unwrap();
     } else {
-        write_compare_result(out, "File matches: ", fname);
+        write_result(out, "File matches: ", &fname, 2, true);
     }
 }
 
-fn write_file_result_diff<W: Write>(out: &mut W, fname: &str, lhash: &str, chash: &str) {
+fn write_file_result_metadata_diff<W: Write>(out: &mut W, fname: &OsStr, kind: &str, was: &str, is: &str) {
+    let fname = fname.to_string_lossy();
+    if 16 + fname.len() + kind.len() <= 80 {
+        writeln!(out, "File \"{}\" {} changed", fname, kind).unwrap();
+    } else {
+        write_result(out, &format!("File {} changed: ", kind), &fname, 4, true);
+    }
+
+    write_result(out, "  Was: ", was, 4, false);
+    write_result(out, "  Is : ", is, 4, false);
+}
+
+fn write_file_result_diff<W: Write>(out: &mut W, fname: &OsStr, lhash: &str, chash: &str) {
+    let fname = fname.to_string_lossy();
     if 21 + fname.len() <= 80 {
         writeln!(out, "File \"{}\" doesn't match", fname).unwrap();
     } else {
-        write_result(out, "File doesn't match: ", fname, 4, true);
+        write_result(out, "File doesn't match: ", &fname, 4, true);
     }
 
     write_result(out, "  Was: ", lhash, 4, false);
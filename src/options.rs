@@ -11,9 +11,10 @@
 //! ```
 
 
-use clap::{self, App, Arg, AppSettings};
-use std::collections::BTreeSet;
+use clap::{self, App, Arg, AppSettings, SubCommand};
+use std::ffi::{OsStr, OsString};
 use std::path::{Path, PathBuf};
+use self::super::ops::{ArchiveFormat, HashesFormat, LinkKind, MetadataKind, OutputFormat};
 use self::super::Algorithm;
 use std::str::FromStr;
 use num_cpus;
@@ -25,22 +26,110 @@ use std::fs;
 pub struct Options {
     /// Directory to hash/verify. Default: `"."`
     pub dir: PathBuf,
-    /// Hashing algorithm to use. Default: `"SHA1"`
-    pub algorithm: Algorithm,
+    /// Format of the archive `dir` is, if asked to hash its members instead of a directory's files. Default: none
+    pub archive: Option<ArchiveFormat>,
+    /// Whether to walk archives inside `archive` like directories, instead of hashing them. Default: `false`
+    pub nested_archives: bool,
+    /// Files to hash on their own and print the hashes of, instead of hashing `dir`, with `-` standing for standard input, if any.
+    /// Default: none
+    pub inputs: Option<Vec<OsString>>,
+    /// Digest to check the only one of `inputs` against with the first algorithm instead, uppercased, if any. Default: none
+    pub check: Option<String>,
+    /// Hashing algorithms to use, in order. Default: `["SHA1"]`
+    ///
+    /// When verifying, untagged hashes file lines are read as holding a hash for each of these,
+    /// and only these are checked if they were specified.
+    pub algorithms: Vec<Algorithm>,
+    /// Whether `algorithms` were specified explicitly. Default: `false`
+    pub algorithms_specified: bool,
+    /// Layout of the created hashes file. Default: `"checksums"`
+    ///
+    /// The layout of a verified file is detected automatically.
+    pub format: HashesFormat,
+    /// Presentation of verification results. Default: `"text"`
+    pub output_format: OutputFormat,
     /// Whether to verify or create checksums. Default: yes
     pub verify: bool,
+    /// Whether to update the hashes file in place instead. Default: `false`
+    pub update: bool,
+    /// Whether to save the current hashes of files that changed when updating. Default: `false`
+    pub rehash_changed: bool,
+    /// Whether to verify only the files listed in the hashes file, instead of walking the directory. Default: `false`
+    pub listed_only: bool,
+    /// Whether to also report files not listed in the hashes file, without hashing them, if `listed_only`. Default: `false`
+    pub report_unlisted: bool,
     /// Max recursion depth. Infinite if None. Default: `0`
     pub depth: Option<usize>,
-    /// In-/Output filename. Default: `"./INFERRED_FROM_DIRECTORY.hash"`
-    pub file: (String, PathBuf),
+    /// Whether `depth` was specified explicitly. Default: `false`
+    pub depth_specified: bool,
+    /// In-/Output filename. Default: `"./INFERRED_FROM_DIRECTORY.hash"`, or `"ARCHIVE.hash"` beside an archive
+    pub file: (OsString, PathBuf),
     /// Whether to recurse down symlinks. Default: `true`
     pub follow_symlinks: bool,
-    /// Files/directories to ignore. Default: none
-    pub ignored_files: BTreeSet<String>,
+    /// Whether `follow_symlinks` was specified explicitly. Default: `false`
+    pub follow_symlinks_specified: bool,
+    /// Gitignore-style patterns of files/directories to ignore,
+    /// those from `--exclude-from` files followed by those from `--ignore`. Default: none
+    pub ignored_files: Vec<String>,
+    /// Gitignore-style patterns of files to exclusively hash, if any. Default: none
+    pub included_files: Vec<String>,
+    /// Whether to honour `.gitignore`, `.ignore` and `.checksumsignore` files in walked directories. Default: `false`
+    pub ignore_files: bool,
     /// # of threads used for hashing.
     ///
     /// Default: 1
     pub jobs: usize,
+    /// Hash cache filename, if any. Default: none, `"./INFERRED_FROM_FILE.cache"` if specified without a value
+    pub cache: Option<(OsString, PathBuf)>,
+    /// Whether to reread all files, even if they're unchanged according to the cache. Default: `false`
+    pub paranoid: bool,
+    /// Whether to record directories, including empty ones, besides the files. Default: `false`
+    ///
+    /// Verifying and updating also check them if they were recorded in the hashes file.
+    pub directories: bool,
+    /// Whether to record per-directory aggregate digests of the first algorithm's hashes, and print the root one. Default: `false`
+    ///
+    /// Updating also records them if they were recorded in the hashes file.
+    pub tree_digests: bool,
+    /// Kinds of file metadata to record or check besides the hashes, if specified.
+    ///
+    /// Default: none when creating, those recorded in the hashes file when verifying or updating,
+    /// all if specified without a value
+    pub metadata: Option<Vec<MetadataKind>>,
+    /// Key to key the hashing algorithms with, if any. Default: none
+    pub key: Option<Vec<u8>>,
+    /// minisign secret key file to sign the created or updated hashes file with, if any. Default: none
+    pub sign: Option<PathBuf>,
+    /// minisign public key file to check the hashes file's signature against before verifying or updating, if any. Default: none
+    pub verify_signature: Option<PathBuf>,
+    /// Subcommand to run instead, if any, with `dir`, `algorithms`, `output_format` and the walking and threading options
+    /// applying to it, if it takes them. Default: none
+    pub subcommand: Option<Subcommand>,
+}
+
+/// A mode of operation other than creating, verifying or updating a hashes file.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum Subcommand {
+    /// Find identical files with the first algorithm, and replace them with links to the first copy, if specified.
+    Dupes {
+        /// Kind of links to replace identical files with, if any. Default: none
+        link: Option<LinkKind>,
+    },
+    /// Compare two hashes files with each other, with the `algorithms` for lines not specifying theirs.
+    Diff {
+        /// The earlier hashes file, compared against like when verifying.
+        old: (OsString, PathBuf),
+        /// The later hashes file, compared like a fresh walk when verifying.
+        new: (OsString, PathBuf),
+    },
+    /// Compare the files in `dir` with the ones in another directory, as if `dir` was hashed to a hashes file,
+    /// and the other directory verified against it.
+    Compare {
+        /// The other directory.
+        other: PathBuf,
+        /// Whether to only hash files whose sizes are the same in both directories. Default: `false`
+        size_first: bool,
+    },
 }
 
 impl Options {
@@ -48,11 +137,14 @@ impl Options {
     pub fn parse() -> Options {
         let matches = App::new("checksums")
             .setting(AppSettings::ColoredHelp)
+            .setting(AppSettings::ArgsNegateSubcommands)
             .version(crate_version!())
             .author(crate_authors!("\n"))
             .about("Tool for making/verifying checksums of directory trees")
-            .args(&[Arg::from_usage("[DIRECTORY] 'Directory to hash/verify'").default_value(".").validator(Options::directory_validator),
-                    Arg::from_usage("--algorithm=[algorithm] -a 'Hashing algorithm to use. {n}\
+            .args(&[Arg::from_usage("[DIRECTORY]... 'Directory, or tar, tar.gz or zip archive with --archive, to hash/verify, or files to hash on their own, - for stdin'")
+                        .default_value(".")
+                        .validator_os(Options::input_validator),
+                    Arg::from_usage("--algorithm=[algorithm]... -a 'Hashing algorithm(s) to use, comma-separated or repeated. {n}\
                                      Supported algorithms: SHA{1,2-{224,256,384,512},3-{256,512}}, \
                                      BLAKE{,2=2B,2S,3}, \
                                      CRC{64,32{,C},16,8}, \
@@ -61,28 +153,282 @@ impl Options {
                                      XOR8'")
                         .next_line_help(true)
                         .default_value("SHA1")
+                        .number_of_values(1)
+                        .use_delimiter(true)
                         .validator(Options::algorithm_validator),
-                    Arg::from_usage("--create -c 'Make checksums'").overrides_with("verify"),
-                    Arg::from_usage("--verify -v 'Verify checksums (default)'").overrides_with("create"),
-                    Arg::from_usage("--depth=[depth] -d 'Max recursion depth. `-1` for infinite.'. Default: don't recurse")
-                        .validator(Options::depth_validator)
-                        .overrides_with("recursive"),
-                    Arg::from_usage("--recursive -r 'Infinite recursion depth.'").overrides_with("depth"),
-                    Arg::from_usage("--file=[file] -f 'File with hashes to be read/created'").validator(Options::file_validator),
+                    Arg::from_usage("--format=[format] 'Hashes file layout to create. {n}\
+                                     Supported formats: checksums, coreutils, bsd'")
+                        .default_value("checksums")
+                        .validator(Options::format_validator),
+                    Arg::from_usage("--output-format=[output-format] 'Verification results presentation. {n}\
+                                     Supported formats: text, json, ndjson'")
+                        .default_value("text")
+                        .validator(Options::output_format_validator),
+                    Arg::from_usage("--create -c 'Make checksums'").overrides_with_all(&["verify", "update"]),
+                    Arg::from_usage("--verify -v 'Verify checksums (default)'").overrides_with_all(&["create", "update"]),
+                    Arg::from_usage("--update -u 'Add new, drop removed, and follow moved files in the hashes file'").overrides_with_all(&["create", "verify"]),
+                    Arg::from_usage("--rehash-changed 'Also save the new hashes of changed files when updating'").requires("update"),
+                    Arg::from_usage("--listed-only 'Verify only the files listed in the hashes file, without walking DIRECTORY'")
+                        .conflicts_with_all(&["create", "update"]),
+                    Arg::from_usage("--report-unlisted 'With --listed-only, also report unlisted files, without reading them'").requires("listed-only"),
+                    Arg::from_usage("--file=[file] -f 'File with hashes to be read/created'").validator_os(Options::file_validator),
                     Arg::from_usage("--force 'Override output file'"),
-                    Arg::from_usage("--follow-symlinks 'Recurse down symlinks. Default: yes'").overrides_with("no-follow-symlinks"),
-                    Arg::from_usage("--no-follow-symlinks 'Don\'t recurse down symlinks'").overrides_with("follow-symlinks"),
-                    Arg::from_usage("-i --ignore [file]... 'Ignore specified file(s)'"),
-                    Arg::from_usage("-j --jobs=[jobs] '# of threads used for hashing. No/empty value: # of CPU threads. -1: Infinite'")
+                    Arg::from_usage("--cache=[cache] 'Reuse hashes of files unchanged since last --create. No/empty value: FILE.cache'")
+                        .min_values(0)
+                        .empty_values(true)
+                        .validator_os(Options::cache_validator),
+                    Arg::from_usage("--paranoid 'Reread all files, even if unchanged according to the cache'"),
+                    Arg::from_usage("--directories 'Also record directories, including empty ones, and report added and removed ones'"),
+                    Arg::from_usage("--tree-digests 'Also record per-directory digests of the hashes, and print the root one'"),
+                    Arg::from_usage("--metadata=[kinds] 'Also record/check file metadata, comma-separated. {n}\
+                                     No/empty value: all. Supported kinds: mode, owner, mtime, target, xattrs'")
+                        .next_line_help(true)
+                        .min_values(0)
                         .empty_values(true)
-                        .validator(Options::jobs_validator)])
+                        .use_delimiter(true)
+                        .validator(Options::metadata_validator),
+                    Arg::from_usage("--check=[digest] 'Check the only file against an expected digest with the first algorithm, instead of hashing it'")
+                        .validator(Options::digest_validator),
+                    Arg::from_usage("--archive 'Hash the members of the tar, tar.gz or zip archive DIRECTORY, instead of the archive itself'"),
+                    Arg::from_usage("--nested-archives 'Hash the members of archives inside an archive DIRECTORY, instead of the archives'").requires("archive"),
+                    Arg::from_usage("--key-file=[key-file] 'Make keyed hashes: HMAC-SHA*, keyed BLAKE2*/BLAKE3, with the contents of key-file as the key'")
+                        .conflicts_with("cache")
+                        .validator_os(Options::key_file_validator),
+                    Arg::from_usage("--sign=[secret-key] 'Sign the created/updated hashes file with an unencrypted minisign secret key, to FILE.minisig'")
+                        .validator_os(Options::key_file_validator),
+                    Arg::from_usage("--verify-signature=[public-key] 'Check FILE.minisig against a minisign public key first'")
+                        .conflicts_with("create")
+                        .validator_os(Options::key_file_validator)])
+            .args(&Options::walk_args())
+            .subcommand(SubCommand::with_name("dupes")
+                .setting(AppSettings::ColoredHelp)
+                .about("Find identical files, and optionally link them together")
+                .args(&[Arg::from_usage("[DIRECTORY] 'Directory to search'").default_value(".").validator_os(Options::directory_validator),
+                        Arg::from_usage("--algorithm=[algorithm] -a 'Hashing algorithm to tell files apart with'")
+                            .default_value("BLAKE3")
+                            .validator(Options::algorithm_validator),
+                        Arg::from_usage("--output-format=[output-format] 'Results presentation. {n}\
+                                         Supported formats: text, json, ndjson'")
+                            .default_value("text")
+                            .validator(Options::output_format_validator),
+                        Arg::from_usage("--link=[kind] 'Replace identical files with links to the first one, after comparing them byte-for-byte. {n}\
+                                         Supported kinds: hard, reflink'")
+                            .next_line_help(true)
+                            .validator(Options::link_validator)])
+                .args(&Options::walk_args()))
+            .subcommand(SubCommand::with_name("diff")
+                .setting(AppSettings::ColoredHelp)
+                .about("Compare two hashes files, without reading the files they list")
+                .args(&[Arg::from_usage("<OLD> 'Earlier hashes file'").validator_os(Options::hashes_file_validator),
+                        Arg::from_usage("<NEW> 'Later hashes file'").validator_os(Options::hashes_file_validator),
+                        Arg::from_usage("--algorithm=[algorithm]... -a 'Hashing algorithm(s) of lines not specifying theirs \
+                                         in hashes files without a header, comma-separated or repeated'")
+                            .next_line_help(true)
+                            .default_value("SHA1")
+                            .number_of_values(1)
+                            .use_delimiter(true)
+                            .validator(Options::algorithm_validator),
+                        Arg::from_usage("--output-format=[output-format] 'Comparison results presentation. {n}\
+                                         Supported formats: text, json, ndjson'")
+                            .default_value("text")
+                            .validator(Options::output_format_validator)]))
+            .subcommand(SubCommand::with_name("compare")
+                .setting(AppSettings::ColoredHelp)
+                .about("Compare two directory trees, without a hashes file")
+                .args(&[Arg::from_usage("<DIRECTORY> 'Original directory'").value_name("DIR_A").validator_os(Options::directory_validator),
+                        Arg::from_usage("<OTHER> 'Directory to verify against DIR_A, e.g. its copy'")
+                            .value_name("DIR_B")
+                            .validator_os(Options::directory_validator),
+                        Arg::from_usage("--algorithm=[algorithm]... -a 'Hashing algorithm(s) to use, comma-separated or repeated'")
+                            .default_value("SHA1")
+                            .number_of_values(1)
+                            .use_delimiter(true)
+                            .validator(Options::algorithm_validator),
+                        Arg::from_usage("--key-file=[key-file] 'Make keyed hashes of both directories, with the contents of key-file as the key'")
+                            .validator_os(Options::key_file_validator),
+                        Arg::from_usage("--output-format=[output-format] 'Comparison results presentation. {n}\
+                                         Supported formats: text, json, ndjson'")
+                            .default_value("text")
+                            .validator(Options::output_format_validator),
+                        Arg::from_usage("--size-first 'Don\'t hash files whose sizes differ between the directories'")])
+                .args(&Options::walk_args()))
             .get_matches();
+        // Options shared with subcommands are given after them
+        let walk_matches = matches.subcommand().1.unwrap_or(&matches);
+        let inputs: Vec<_> = matches.values_of_os("DIRECTORY").unwrap().map(OsStr::to_os_string).collect();
+        // A single directory, or archive if asked to, is walked, anything else hashed on its own
+        let inputs = match &inputs[..] {
+            [input] if !matches.is_present("check") && (Path::new(input).is_dir() || (matches.is_present("archive") && Path::new(input).is_file())) => None,
+            _ => Some(inputs),
+        };
+        if let Some(dir) = inputs.iter().flatten().find(|input| Path::new(input).is_dir()) {
+            clap::Error {
+                    message: format!("\"{}\" is a directory; only a single DIRECTORY can be hashed at a time.", dir.to_string_lossy()),
+                    kind: clap::ErrorKind::InvalidValue,
+                    info: None,
+                }
+                .exit();
+        }
+        let dir = match inputs {
+            Some(_) => fs::canonicalize(".").unwrap(),
+            None => fs::canonicalize(walk_matches.value_of_os("DIRECTORY").or_else(|| matches.value_of_os("DIRECTORY")).unwrap()).unwrap(),
+        };
+        let archive = if matches.is_present("archive") {
+            match ArchiveFormat::from_path(&dir).filter(|_| dir.is_file() && inputs.is_none()) {
+                Some(archive) => Some(archive),
+                None => {
+                    clap::Error {
+                            message: "Only a single tar, tar.gz or zip archive DIRECTORY can be walked with --archive.".to_string(),
+                            kind: clap::ErrorKind::InvalidValue,
+                            info: None,
+                        }
+                        .exit()
+                }
+            }
+        } else {
+            None
+        };
+        let subcommand = match matches.subcommand() {
+            ("dupes", Some(dupes_matches)) => Some(Subcommand::Dupes { link: dupes_matches.value_of("link").map(|l| LinkKind::from_str(l).unwrap()) }),
+            ("diff", Some(diff_matches)) => {
+                Some(Subcommand::Diff {
+                    old: Options::file_process(diff_matches.value_of_os("OLD"), &dir),
+                    new: Options::file_process(diff_matches.value_of_os("NEW"), &dir),
+                })
+            }
+            ("compare", Some(compare_matches)) => {
+                Some(Subcommand::Compare {
+                    other: fs::canonicalize(compare_matches.value_of_os("OTHER").unwrap()).unwrap(),
+                    size_first: compare_matches.is_present("size-first"),
+                })
+            }
+            _ => None,
+        };
+
+        let update = matches.is_present("update");
+        let verify = !matches.is_present("create") && !update;
+        let format = HashesFormat::from_str(matches.value_of("format").unwrap()).unwrap();
+        let mut algorithms: Vec<Algorithm> = Vec::new();
+        for algo in walk_matches.values_of("algorithm").unwrap().map(|a| Algorithm::from_str(a).unwrap()) {
+            if !algorithms.contains(&algo) {
+                algorithms.push(algo);
+            }
+        }
+        let file = Options::file_process(matches.value_of_os("file"), &dir);
+        let cache = if matches.is_present("cache") {
+            Some(match matches.value_of_os("cache") {
+                Some(cache) if !cache.is_empty() => Options::file_process(Some(cache), &dir),
+                _ => (Options::with_suffix(&file.0, ".cache"), PathBuf::from(Options::with_suffix(file.1.as_os_str(), ".cache"))),
+            })
+        } else {
+            None
+        };
+
+        let mut ignored_files = Vec::new();
+        for exclude_from in walk_matches.values_of("exclude-from").into_iter().flatten() {
+            match fs::read_to_string(exclude_from) {
+                Ok(patterns) => ignored_files.extend(patterns.lines().map(String::from)),
+                Err(error) => {
+                    clap::Error {
+                            message: format!("Couldn't read exclude file \"{}\": {}", exclude_from, error),
+                            kind: clap::ErrorKind::Io,
+                            info: None,
+                        }
+                        .exit()
+                }
+            }
+        }
+        ignored_files.extend(walk_matches.values_of("ignore").into_iter().flatten().map(String::from));
 
-        let dir = fs::canonicalize(matches.value_of("DIRECTORY").unwrap()).unwrap();
-        let verify = !matches.is_present("create");
-        let file = Options::file_process(matches.value_of("file"), &dir);
+        let metadata = if matches.is_present("metadata") {
+            let mut kinds: Vec<_> =
+                matches.values_of("metadata").into_iter().flatten().filter(|k| !k.is_empty()).map(|k| MetadataKind::from_str(k).unwrap()).collect();
+            if kinds.is_empty() {
+                kinds = MetadataKind::ALL.to_vec();
+            }
+            kinds.sort();
+            kinds.dedup();
+            Some(kinds)
+        } else {
+            None
+        };
+
+        let key = walk_matches.value_of_os("key-file").map(|key_file| match fs::read(key_file) {
+            Ok(key) => key,
+            Err(error) => {
+                clap::Error {
+                        message: format!("Couldn't read key file \"{}\": {}", key_file.to_string_lossy(), error),
+                        kind: clap::ErrorKind::Io,
+                        info: None,
+                    }
+                    .exit()
+            }
+        });
 
-        if file.1.exists() && !verify && !matches.is_present("force") {
+        if subcommand.is_some() {
+            // Subcommands don't use the hashes file
+        } else if let Some(ref inputs) = inputs {
+            if matches.is_present("check") && inputs.len() != 1 {
+                clap::Error {
+                        message: "Only a single file can be checked against a digest.".to_string(),
+                        kind: clap::ErrorKind::WrongNumberOfValues,
+                        info: None,
+                    }
+                    .exit();
+            } else if let Some(digest) = matches.value_of("check").filter(|digest| digest.len() != algorithms[0].hexlen()) {
+                clap::Error {
+                        message: format!("The digest is {} digits long, but {} digests are {}.", digest.len(), algorithms[0], algorithms[0].hexlen()),
+                        kind: clap::ErrorKind::InvalidValue,
+                        info: None,
+                    }
+                    .exit();
+            } else if format == HashesFormat::Coreutils && algorithms.len() > 1 {
+                clap::Error {
+                        message: "The coreutils format can only hold a single algorithm.\n\
+                                  Use the checksums or bsd format for multiple ones."
+                            .to_string(),
+                        kind: clap::ErrorKind::ArgumentConflict,
+                        info: None,
+                    }
+                    .exit();
+            }
+        } else if archive.is_some() && (matches.is_present("cache") || matches.is_present("listed-only")) {
+            clap::Error {
+                    message: "Archive members can't be cached or verified without walking the archive.\n\
+                              Drop the --cache and --listed-only options to hash an archive."
+                        .to_string(),
+                    kind: clap::ErrorKind::ArgumentConflict,
+                    info: None,
+                }
+                .exit();
+        } else if !verify && !update && format == HashesFormat::Coreutils && algorithms.len() > 1 {
+            clap::Error {
+                    message: "The coreutils format can only hold a single algorithm.\n\
+                              Use the checksums or bsd format for multiple ones."
+                        .to_string(),
+                    kind: clap::ErrorKind::ArgumentConflict,
+                    info: None,
+                }
+                .exit();
+        } else if verify && matches.is_present("sign") {
+            clap::Error {
+                    message: "Only created and updated hashes files can be signed.\n\
+                              Pass the --create or --update option to sign one."
+                        .to_string(),
+                    kind: clap::ErrorKind::ArgumentConflict,
+                    info: None,
+                }
+                .exit();
+        } else if verify && matches.is_present("tree-digests") {
+            clap::Error {
+                    message: "Tree digests are only recorded in created and updated hashes files.\n\
+                              Pass the --create or --update option to record them."
+                        .to_string(),
+                    kind: clap::ErrorKind::ArgumentConflict,
+                    info: None,
+                }
+                .exit();
+        } else if file.1.exists() && !verify && !update && !matches.is_present("force") {
             clap::Error {
                     message: "The output file exists and was not overridden to prevent data loss.\n\
                               Pass the --force option to suppress this error."
@@ -91,10 +437,10 @@ impl Options {
                     info: None,
                 }
                 .exit();
-        } else if !file.1.exists() && verify {
+        } else if !file.1.exists() && (verify || update) {
             clap::Error {
                     message: format!("Can't find checksums file \"{}\".\n\
-                                      Did you mean to create it with -c?", file.0),
+                                      Did you mean to create it with -c?", file.0.to_string_lossy()),
                     kind: clap::ErrorKind::InvalidValue,
                     info: None,
                 }
@@ -103,18 +449,33 @@ impl Options {
 
         Options {
             dir: dir,
-            algorithm: Algorithm::from_str(matches.value_of("algorithm").unwrap()).unwrap(),
+            archive,
+            nested_archives: matches.is_present("nested-archives"),
+            inputs,
+            check: matches.value_of("check").map(str::to_uppercase),
+            algorithms,
+            algorithms_specified: walk_matches.occurrences_of("algorithm") > 0,
+            format,
+            output_format: OutputFormat::from_str(walk_matches.value_of("output-format").unwrap()).unwrap(),
             verify: verify,
-            depth: if matches.is_present("recursive") {
+            update,
+            rehash_changed: matches.is_present("rehash-changed"),
+            listed_only: matches.is_present("listed-only"),
+            report_unlisted: matches.is_present("report-unlisted"),
+            depth: if walk_matches.is_present("recursive") {
                 None
             } else {
-                let i = matches.value_of("depth").map(|s| s.parse::<isize>().unwrap()).unwrap_or(0);
+                let i = walk_matches.value_of("depth").map(|s| s.parse::<isize>().unwrap()).unwrap_or(0);
                 if i < 0 { None } else { Some(i as usize) }
             },
+            depth_specified: walk_matches.is_present("depth") || walk_matches.is_present("recursive"),
             file: file,
-            follow_symlinks: !matches.is_present("no-follow-symlinks"),
-            ignored_files: matches.values_of("ignore").map(|v| v.map(String::from).collect()).unwrap_or_default(),
-            jobs: match matches.value_of("jobs") {
+            follow_symlinks: !walk_matches.is_present("no-follow-symlinks"),
+            follow_symlinks_specified: walk_matches.is_present("follow-symlinks") || walk_matches.is_present("no-follow-symlinks"),
+            ignored_files,
+            included_files: walk_matches.values_of("include").map(|v| v.map(String::from).collect()).unwrap_or_default(),
+            ignore_files: walk_matches.is_present("ignore-files"),
+            jobs: match walk_matches.value_of("jobs") {
                 None | Some("") => num_cpus::get() as usize,
                 Some(s) => {
                     match i32::from_str(s).unwrap() {
@@ -123,31 +484,94 @@ impl Options {
                     }
                 }
             },
+            cache,
+            paranoid: matches.is_present("paranoid"),
+            directories: matches.is_present("directories"),
+            tree_digests: matches.is_present("tree-digests"),
+            metadata,
+            key,
+            sign: matches.value_of_os("sign").map(PathBuf::from),
+            verify_signature: matches.value_of_os("verify-signature").map(PathBuf::from),
+            subcommand,
         }
     }
 
+    fn walk_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![Arg::from_usage("--depth=[depth] -d 'Max recursion depth. `-1` for infinite.'. Default: don't recurse")
+                 .validator(Options::depth_validator)
+                 .overrides_with("recursive"),
+             Arg::from_usage("--recursive -r 'Infinite recursion depth.'").overrides_with("depth"),
+             Arg::from_usage("--follow-symlinks 'Recurse down symlinks. Default: yes'").overrides_with("no-follow-symlinks"),
+             Arg::from_usage("--no-follow-symlinks 'Don\'t recurse down symlinks'").overrides_with("follow-symlinks"),
+             Arg::from_usage("-i --ignore [pattern]... 'Ignore files/directories matching gitignore-style pattern(s)'"),
+             Arg::from_usage("--exclude-from=[file]... 'Ignore files/directories matching patterns in file(s), one per line'")
+                 .number_of_values(1)
+                 .validator(Options::exclude_from_validator),
+             Arg::from_usage("--include=[pattern]... 'Only hash files matching gitignore-style pattern(s)'").number_of_values(1),
+             Arg::from_usage("--ignore-files 'Honour .gitignore, .ignore and .checksumsignore files'"),
+             Arg::from_usage("-j --jobs=[jobs] '# of threads used for hashing. No/empty value: # of CPU threads. -1: Infinite'")
+                 .empty_values(true)
+                 .validator(Options::jobs_validator)]
+    }
+
     fn algorithm_validator(s: String) -> Result<(), String> {
         Algorithm::from_str(&s).map(|_| ())
     }
 
-    fn directory_validator(s: String) -> Result<(), String> {
-        fs::canonicalize(s).map_err(|e| format!("directory: {}", e.to_string())).and_then(|p| {
+    fn format_validator(s: String) -> Result<(), String> {
+        HashesFormat::from_str(&s).map(|_| ())
+    }
+
+    fn output_format_validator(s: String) -> Result<(), String> {
+        OutputFormat::from_str(&s).map(|_| ())
+    }
+
+    fn link_validator(s: String) -> Result<(), String> {
+        LinkKind::from_str(&s).map(|_| ())
+    }
+
+    fn metadata_validator(s: String) -> Result<(), String> {
+        if s.is_empty() {
+            Ok(())
+        } else {
+            MetadataKind::from_str(&s).map(|_| ())
+        }
+    }
+
+    fn directory_validator(s: &OsStr) -> Result<(), OsString> {
+        fs::canonicalize(s).map_err(|e| format!("directory: {}", e.to_string()).into()).and_then(|p| {
             if p.is_file() {
-                Err("DIRECTORY cannot be a file.".to_string())
+                Err("DIRECTORY cannot be a file.".into())
             } else {
                 Ok(())
             }
         })
     }
 
+    fn input_validator(s: &OsStr) -> Result<(), OsString> {
+        if s == "-" {
+            Ok(())
+        } else {
+            fs::canonicalize(s).map(|_| ()).map_err(|e| format!("{}: {}", s.to_string_lossy(), e).into())
+        }
+    }
+
+    fn digest_validator(s: String) -> Result<(), String> {
+        if !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(())
+        } else {
+            Err(format!("\"{}\" is not a hexadecimal digest", s))
+        }
+    }
+
     fn depth_validator(s: String) -> Result<(), String> {
         s.parse::<isize>().map(|_| ()).map_err(|e| e.to_string())
     }
 
-    fn file_validator(s: String) -> Result<(), String> {
+    fn file_validator(s: &OsStr) -> Result<(), OsString> {
         let mut buf = PathBuf::from(s);
         if buf.exists() && buf.is_dir() {
-            Err("file exists and is a directory".to_string())
+            Err("file exists and is a directory".into())
         } else {
             buf.pop();
 
@@ -155,11 +579,43 @@ impl Options {
             if buf.as_os_str().is_empty() {
                 Ok(())
             } else {
-                buf.canonicalize().map(|_| ()).map_err(|e| format!("file: {}", e.to_string()))
+                buf.canonicalize().map(|_| ()).map_err(|e| format!("file: {}", e.to_string()).into())
             }
         }
     }
 
+    fn exclude_from_validator(s: String) -> Result<(), String> {
+        if PathBuf::from(&s).is_file() {
+            Ok(())
+        } else {
+            Err(format!("exclude file \"{}\" doesn't exist or isn't a file", s))
+        }
+    }
+
+    fn key_file_validator(s: &OsStr) -> Result<(), OsString> {
+        if Path::new(s).is_file() {
+            Ok(())
+        } else {
+            Err(format!("key file \"{}\" doesn't exist or isn't a file", s.to_string_lossy()).into())
+        }
+    }
+
+    fn hashes_file_validator(s: &OsStr) -> Result<(), OsString> {
+        if Path::new(s).is_file() {
+            Ok(())
+        } else {
+            Err(format!("hashes file \"{}\" doesn't exist or isn't a file", s.to_string_lossy()).into())
+        }
+    }
+
+    fn cache_validator(s: &OsStr) -> Result<(), OsString> {
+        if s.is_empty() {
+            Ok(())
+        } else {
+            Options::file_validator(s)
+        }
+    }
+
     fn jobs_validator(s: String) -> Result<(), String> {
         if s.is_empty() {
             Ok(())
@@ -177,7 +633,7 @@ impl Options {
     }
 
 
-    fn file_process(file: Option<&str>, dir: &PathBuf) -> (String, PathBuf) {
+    fn file_process(file: Option<&OsStr>, dir: &PathBuf) -> (OsString, PathBuf) {
         match file {
             Some(file) => {
                 let mut file = PathBuf::from(file);
@@ -189,7 +645,7 @@ impl Options {
                     file.push(".");
                 }
 
-                (file_name.to_str().unwrap().to_string(),
+                (file_name.clone(),
                  file.canonicalize()
                     .map(|mut p| {
                         p.push(file_name);
@@ -197,6 +653,10 @@ impl Options {
                     })
                     .unwrap())
             }
+            None if dir.is_file() => {
+                let file = PathBuf::from(Options::with_suffix(dir.as_os_str(), ".hash"));
+                (file.file_name().unwrap().to_os_string(), file)
+            }
             None => {
                 let mut file = dir.clone();
                 match dir.file_name() {
@@ -205,11 +665,17 @@ impl Options {
                 }
                 file.set_extension("hash");
 
-                (file.file_name().unwrap().to_str().unwrap().to_string(), file)
+                (file.file_name().unwrap().to_os_string(), file)
             }
         }
     }
 
+    fn with_suffix(name: &OsStr, suffix: &str) -> OsString {
+        let mut name = name.to_os_string();
+        name.push(suffix);
+        name
+    }
+
     #[cfg(windows)]
     fn root_fname(dir: &Path) -> String {
         let dir = dir.as_os_str().to_str().unwrap().to_string();
@@ -1,8 +1,10 @@
 //! Module containing various utility functions
 
 
+use std::ffi::{OsStr, OsString};
 use std::path::Path;
 use std::iter;
+use std::str;
 
 
 /// Merges two `Vec`s.
@@ -33,13 +35,115 @@ pub fn mul_str(what: &str, n: usize) -> String {
 
 /// Create a user-usable path to `what` from `prefix`.
 ///
+/// Platform path separators are replaced with forward slashes.
+///
 /// # Examples
 ///
 /// ```
 /// # use std::path::Path;
 /// assert_eq!(checksums::util::relative_name(Path::new("/usr"), Path::new("/usr/bin/checksums")),
-///            "bin/checksums".to_string());
+///            "bin/checksums");
+/// ```
+pub fn relative_name(prefix: &Path, what: &Path) -> OsString {
+    let mut name = OsString::new();
+    for component in what.strip_prefix(prefix).unwrap().components() {
+        if !name.is_empty() {
+            name.push("/");
+        }
+        name.push(component);
+    }
+    name
+}
+
+/// Escape backslashes, CRs and LFs in `what` with backslashes, the same way GNU coreutils do in checksum files,
+/// and bytes that aren't valid UTF-8 as `\xHH`.
+///
+/// # Examples
+///
+/// ```
+/// # use std::ffi::OsStr;
+/// # #[cfg(unix)]
+/// # use std::os::unix::ffi::OsStrExt;
+/// assert_eq!(checksums::util::backslash_escape("a\\b\nc"), "a\\\\b\\nc".to_string());
+/// # #[cfg(unix)]
+/// assert_eq!(checksums::util::backslash_escape(OsStr::from_bytes(b"caf\xE9")), "caf\\xE9".to_string());
+/// ```
+pub fn backslash_escape<S: AsRef<OsStr> + ?Sized>(what: &S) -> String {
+    let mut result = String::new();
+    for chunk in what.as_ref().as_encoded_bytes().utf8_chunks() {
+        result.push_str(&chunk.valid().replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r"));
+        for byte in chunk.invalid() {
+            result.push_str(&format!("\\x{:02X}", byte));
+        }
+    }
+    result
+}
+
+/// Escape `what` with `backslash_escape()`, then tabs as `\t` and trailing whitespace as `\xHH`,
+/// the way escaped names are written in checksums-format hashes files, so they can't be taken for column separators or trimmed off.
+///
+/// `backslash_unescape()` reverses it.
+///
+/// # Examples
+///
 /// ```
-pub fn relative_name(prefix: &Path, what: &Path) -> String {
-    what.strip_prefix(prefix).unwrap().to_str().unwrap().replace("\\", "/")
+/// assert_eq!(checksums::util::checksums_escape("a\tb \\ "), "a\\tb \\\\\\x20".to_string());
+/// assert_eq!(checksums::util::backslash_unescape("a\\tb \\\\\\x20"), Some("a\tb \\ ".into()));
+/// ```
+pub fn checksums_escape<S: AsRef<OsStr> + ?Sized>(what: &S) -> String {
+    let escaped = backslash_escape(what);
+    let body = escaped.trim_end();
+    let mut result = body.replace('\t', "\\t");
+    for byte in escaped[body.len()..].bytes() {
+        result.push_str(&format!("\\x{:02X}", byte));
+    }
+    result
+}
+
+/// Reverse `backslash_escape()`, or `None` if `what` contains an invalid escape.
+///
+/// `\t` is accepted for tabs, too.
+/// `\xHH` escapes that don't make up valid UTF-8 are only accepted on Unix.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(checksums::util::backslash_unescape("a\\\\b\\nc"), Some("a\\b\nc".into()));
+/// assert_eq!(checksums::util::backslash_unescape("caf\\xC3\\xA9"), Some("caf\u{e9}".into()));
+/// assert_eq!(checksums::util::backslash_unescape("a\\b"), None);
+/// ```
+pub fn backslash_unescape(what: &str) -> Option<OsString> {
+    let mut result = Vec::with_capacity(what.len());
+    let mut bytes = what.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'\\' {
+            result.push(match bytes.next()? {
+                b'\\' => b'\\',
+                b'n' => b'\n',
+                b'r' => b'\r',
+                b't' => b'\t',
+                b'x' => {
+                    let hex = [bytes.next()?, bytes.next()?];
+                    u8::from_str_radix(str::from_utf8(&hex).ok()?, 16).ok()?
+                }
+                _ => return None,
+            });
+        } else {
+            result.push(b);
+        }
+    }
+    os_string_from_bytes(result)
+}
+
+
+#[cfg(unix)]
+fn os_string_from_bytes(bytes: Vec<u8>) -> Option<OsString> {
+    use std::os::unix::ffi::OsStringExt;
+
+    Some(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn os_string_from_bytes(bytes: Vec<u8>) -> Option<OsString> {
+    String::from_utf8(bytes).ok().map(OsString::from)
 }
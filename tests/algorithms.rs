@@ -13,6 +13,8 @@ fn from_str() {
                ("sha2-384", Algorithm::SHA2384),
                ("sha2-512", Algorithm::SHA2512),
                ("sha2256", Algorithm::SHA2256),
+               ("sha256", Algorithm::SHA2256),
+               ("sha512", Algorithm::SHA2512),
                ("sha2512", Algorithm::SHA2512),
                ("sha3", Algorithm::SHA3512),
                ("sha3-256", Algorithm::SHA3256),
@@ -40,6 +42,34 @@ fn from_str() {
     }
 }
 
+#[test]
+fn display_round_trip() {
+    for a in &[Algorithm::SHA1,
+               Algorithm::SHA2224,
+               Algorithm::SHA2256,
+               Algorithm::SHA2384,
+               Algorithm::SHA2512,
+               Algorithm::SHA3256,
+               Algorithm::SHA3512,
+               Algorithm::BLAKE,
+               Algorithm::BLAKE2B,
+               Algorithm::BLAKE2S,
+               Algorithm::BLAKE3,
+               Algorithm::CRC64,
+               Algorithm::CRC32,
+               Algorithm::CRC32C,
+               Algorithm::CRC16,
+               Algorithm::CRC8,
+               Algorithm::MD5,
+               Algorithm::MD6128,
+               Algorithm::MD6256,
+               Algorithm::MD6512,
+               Algorithm::WHIRLPOOL,
+               Algorithm::XOR8] {
+        assert_eq!(Algorithm::from_str(&a.to_string()).unwrap(), *a);
+    }
+}
+
 #[test]
 fn from_str_bad() {
     for s in &["asdf2", "sha123", "bla", "crc", "31234"] {
@@ -0,0 +1,291 @@
+extern crate checksums;
+extern crate flate2;
+extern crate tar;
+extern crate zip;
+
+use self::checksums::ops::{ArchiveFormat, FileFilter, MetadataKind, WalkOptions, MAX_LINK_TARGETS, create_archive_directories, create_archive_hashes,
+                           create_archive_metadata, read_digests, read_hashes, tree_digests};
+use self::checksums::{Algorithm, hash_reader};
+use std::collections::{BTreeSet, BTreeMap};
+use flate2::write::GzEncoder;
+use std::fs::{self, File};
+use std::env::temp_dir;
+use std::path::Path;
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::process::{self, Command};
+
+
+#[test]
+fn tar() {
+    let file = temp_dir().join("checksums-test-archive-tar.tar");
+    write_tar(File::create(&file).unwrap());
+
+    let filter = FileFilter::default();
+    let (hashes, errors) = create_archive_hashes(&file, ArchiveFormat::Tar, &[Algorithm::SHA1], None, WalkOptions::new(&filter), io::sink());
+    assert!(errors.is_empty());
+    assert_eq!(hashes.keys().collect::<Vec<_>>(), vec!["a.txt", "dir/b.txt", "dir/hardlink", "inner.tar"]);
+    assert_eq!(hashes[OsStr::new("a.txt")][&Algorithm::SHA1], sha1(b"checksums"));
+    assert_eq!(hashes[OsStr::new("dir/hardlink")], hashes[OsStr::new("dir/b.txt")]);
+
+    let walk = WalkOptions { depth: Some(0), ..WalkOptions::new(&filter) };
+    let (hashes, _) = create_archive_hashes(&file, ArchiveFormat::Tar, &[Algorithm::SHA1], None, walk, io::sink());
+    assert_eq!(hashes.keys().collect::<Vec<_>>(), vec!["a.txt", "inner.tar"]);
+
+    let filter = FileFilter::new(&file, &["dir/".to_string()], &["*.txt".to_string()], false).unwrap();
+    let (hashes, _) = create_archive_hashes(&file, ArchiveFormat::Tar, &[Algorithm::SHA1], None, WalkOptions::new(&filter), io::sink());
+    assert_eq!(hashes.keys().collect::<Vec<_>>(), vec!["a.txt", "dir/b.txt"]);
+    assert_eq!(hashes[OsStr::new("dir/b.txt")][&Algorithm::SHA1], "----------------------------------------");
+
+    // Hardlinks go the way of what they link to
+    let filter = FileFilter::new(&file, &["b.txt".to_string()], &[], false).unwrap();
+    let (hashes, errors) = create_archive_hashes(&file, ArchiveFormat::Tar, &[Algorithm::SHA1], None, WalkOptions::new(&filter), io::sink());
+    assert!(errors.is_empty());
+    assert_eq!(hashes[OsStr::new("dir/hardlink")][&Algorithm::SHA1], "----------------------------------------");
+
+    let filter = FileFilter::new(&file, &[], &["a.txt".to_string(), "hardlink".to_string()], false).unwrap();
+    let (hashes, errors) = create_archive_hashes(&file, ArchiveFormat::Tar, &[Algorithm::SHA1], None, WalkOptions::new(&filter), io::sink());
+    assert!(errors.is_empty());
+    assert_eq!(hashes.keys().collect::<Vec<_>>(), vec!["a.txt"]);
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn nested() {
+    let file = temp_dir().join("checksums-test-archive-nested.zip");
+    {
+        let mut inner = vec![];
+        write_tar(&mut inner);
+
+        let mut zip = zip::ZipWriter::new(File::create(&file).unwrap());
+        zip.start_file("top.txt", Default::default()).unwrap();
+        zip.write_all(b"top").unwrap();
+        zip.start_file("sub/outer.tar", Default::default()).unwrap();
+        zip.write_all(&inner).unwrap();
+        zip.finish().unwrap();
+    }
+
+    let filter = FileFilter::default();
+    let walk = WalkOptions { nested_archives: true, ..WalkOptions::new(&filter) };
+    let (hashes, errors) = create_archive_hashes(&file, ArchiveFormat::Zip, &[Algorithm::SHA1], None, walk, io::sink());
+    assert!(errors.is_empty());
+    assert_eq!(hashes.keys().collect::<Vec<_>>(),
+               vec!["sub/outer.tar/a.txt",
+                    "sub/outer.tar/dir/b.txt",
+                    "sub/outer.tar/dir/hardlink",
+                    "sub/outer.tar/inner.tar/c.txt",
+                    "top.txt"]);
+    assert_eq!(hashes[OsStr::new("sub/outer.tar/inner.tar/c.txt")][&Algorithm::SHA1], sha1(b"nested"));
+
+    let walk = WalkOptions {
+        depth: Some(2),
+        nested_archives: true,
+        ..WalkOptions::new(&filter)
+    };
+    let (hashes, _) = create_archive_hashes(&file, ArchiveFormat::Zip, &[Algorithm::SHA1], None, walk, io::sink());
+    assert_eq!(hashes.keys().collect::<Vec<_>>(), vec!["sub/outer.tar/a.txt", "top.txt"]);
+
+    assert_eq!(create_archive_directories(&file, ArchiveFormat::Zip, &Default::default(), None, true),
+               vec!["sub", "sub/outer.tar", "sub/outer.tar/dir", "sub/outer.tar/inner.tar"].into_iter().map(Into::into).collect::<BTreeSet<_>>());
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn metadata() {
+    let file = temp_dir().join("checksums-test-archive-metadata.tar.gz");
+    write_tar(GzEncoder::new(File::create(&file).unwrap(), Default::default()));
+
+    let (metadata, errors) = create_archive_metadata(&file, ArchiveFormat::TarGz, &Default::default(), &MetadataKind::ALL, None, false);
+    assert!(errors.is_empty());
+    assert_eq!(metadata.keys().collect::<Vec<_>>(), vec!["a.txt", "dir/b.txt", "dir/hardlink", "dir/symlink", "inner.tar"]);
+    assert_eq!(metadata[OsStr::new("a.txt")],
+               values(&[(MetadataKind::Mode, "640"), (MetadataKind::Owner, "1000:100"), (MetadataKind::Mtime, "1234567890.000000000")]));
+    assert_eq!(metadata[OsStr::new("dir/symlink")][&MetadataKind::Target], "../a b");
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn zip_mtime() {
+    let file = temp_dir().join("checksums-test-archive-zip_mtime.zip");
+    {
+        // The writer only takes unreserved extra field IDs, so the extended timestamp's is swapped in afterwards
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(vec![]));
+        zip.start_file_with_extra_data("extended.txt", Default::default()).unwrap();
+        zip.write_all(&[0xFE, 0xCA, 5, 0, 1]).unwrap();
+        zip.write_all(&1234567890i32.to_le_bytes()).unwrap();
+        zip.end_extra_data().unwrap();
+        zip.write_all(b"extended").unwrap();
+        zip.start_file("dos.txt", Default::default()).unwrap();
+        zip.write_all(b"dos").unwrap();
+
+        let mut data = zip.finish().unwrap().into_inner();
+        for i in 0..data.len() - 4 {
+            if data[i..i + 5] == [0xFE, 0xCA, 5, 0, 1] {
+                data[i..i + 2].copy_from_slice(&[0x55, 0x54]);
+            }
+        }
+        File::create(&file).unwrap().write_all(&data).unwrap();
+    }
+
+    let (metadata, errors) = create_archive_metadata(&file, ArchiveFormat::Zip, &Default::default(), &[MetadataKind::Mtime], None, false);
+    assert!(errors.is_empty());
+    assert_eq!(metadata[OsStr::new("extended.txt")], values(&[(MetadataKind::Mtime, "1234567890.000000000")]));
+    assert_eq!(metadata[OsStr::new("dos.txt")], values(&[]));
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn only_when_asked() {
+    let file = temp_dir().join("checksums-test-archive-only_when_asked.tar");
+    let hashes_file = ("only_when_asked.hash".into(), temp_dir().join("checksums-test-archive-only_when_asked.hash"));
+    write_tar(File::create(&file).unwrap());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_checksums")).arg(&file).output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().ends_with(&format!("  {}\n", sha1(&fs::read(&file).unwrap()))));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_checksums")).args(["-c", "--force", "--archive"]).arg(&file).arg("-f").arg(&hashes_file.1).status().unwrap();
+    assert!(status.success());
+    let (_, _, hashes) = read_hashes(&mut vec![], &hashes_file, &[Algorithm::SHA1]).unwrap();
+    assert_eq!(hashes[OsStr::new("a.txt")][&Algorithm::SHA1], sha1(b"checksums"));
+
+    fs::remove_file(&file).unwrap();
+    fs::remove_file(&hashes_file.1).unwrap();
+}
+
+#[test]
+fn tree_digests_out_of_order() {
+    let file = temp_dir().join("checksums-test-archive-tree_digests_out_of_order.tar");
+    let hashes_file = ("tree_digests_out_of_order.hash".into(), temp_dir().join("checksums-test-archive-tree_digests_out_of_order.hash"));
+    {
+        let mut builder = tar::Builder::new(File::create(&file).unwrap());
+        append(&mut builder, tar::EntryType::Regular, "dir/b.txt", None, b"more checksums");
+        append(&mut builder, tar::EntryType::Regular, "a.txt", None, b"checksums");
+        append(&mut builder, tar::EntryType::Regular, "dir/a.txt", None, b"checksums");
+        builder.into_inner().unwrap();
+    }
+
+    let status = Command::new(env!("CARGO_BIN_EXE_checksums"))
+        .args(["-c", "-r", "--force", "--tree-digests", "--archive"])
+        .arg(&file)
+        .arg("-f")
+        .arg(&hashes_file.1)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success());
+
+    let (_, _, hashes) = read_hashes(&mut vec![], &hashes_file, &[Algorithm::SHA1]).unwrap();
+    let mut digests = BTreeMap::new();
+    digests.insert(Algorithm::SHA1, tree_digests(&hashes, Algorithm::SHA1));
+    assert_eq!(read_digests(&mut vec![], &hashes_file), Ok(digests));
+
+    // Written in walk order, like the hashes of the extracted tree would be
+    let written = fs::read_to_string(&hashes_file.1).unwrap();
+    let names: Vec<_> = written.lines().filter(|line| !line.starts_with('#')).map(|line| line.split_whitespace().next().unwrap()).collect();
+    assert_eq!(names.into_iter().filter(|name| !name.ends_with(".hash")).collect::<Vec<_>>(), vec!["a.txt", "dir/a.txt", "dir/b.txt"]);
+
+    fs::remove_file(&file).unwrap();
+    fs::remove_file(&hashes_file.1).unwrap();
+}
+
+#[test]
+fn hardlink_far_back() {
+    let file = temp_dir().join("checksums-test-archive-hardlink_far_back.tar");
+    {
+        let mut builder = tar::Builder::new(File::create(&file).unwrap());
+        append(&mut builder, tar::EntryType::Regular, "a.txt", None, b"checksums");
+        for i in 0..MAX_LINK_TARGETS {
+            append(&mut builder, tar::EntryType::Regular, &format!("dir/{}", i), None, b"");
+        }
+        append(&mut builder, tar::EntryType::Link, "hardlink", Some("a.txt"), b"");
+        builder.into_inner().unwrap();
+    }
+
+    let (hashes, errors) = create_archive_hashes(&file, ArchiveFormat::Tar, &[Algorithm::SHA1], None, WalkOptions::new(&Default::default()), io::sink());
+    assert!(errors.is_empty());
+    assert_eq!(hashes[OsStr::new("hardlink")][&Algorithm::SHA1], sha1(b"checksums"));
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn nested_zip() {
+    let file = temp_dir().join("checksums-test-archive-nested_zip.tar");
+    {
+        let mut inner = zip::ZipWriter::new(io::Cursor::new(vec![]));
+        inner.start_file("c.txt", Default::default()).unwrap();
+        inner.write_all(b"nested").unwrap();
+
+        let mut builder = tar::Builder::new(File::create(&file).unwrap());
+        append(&mut builder, tar::EntryType::Regular, "inner.zip", None, &inner.finish().unwrap().into_inner());
+        builder.into_inner().unwrap();
+    }
+
+    let filter = FileFilter::default();
+    let walk = WalkOptions { nested_archives: true, ..WalkOptions::new(&filter) };
+    let (hashes, errors) = create_archive_hashes(&file, ArchiveFormat::Tar, &[Algorithm::SHA1], None, walk, io::sink());
+    assert!(errors.is_empty());
+    assert_eq!(hashes.keys().collect::<Vec<_>>(), vec!["inner.zip/c.txt"]);
+    assert_eq!(hashes[OsStr::new("inner.zip/c.txt")][&Algorithm::SHA1], sha1(b"nested"));
+
+    // The copy it was read from is gone
+    let spilled = format!("checksums-nested-{}-", process::id());
+    assert!(!fs::read_dir(temp_dir()).unwrap().any(|entry| entry.unwrap().file_name().to_string_lossy().starts_with(&spilled)));
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn unreadable() {
+    let file = temp_dir().join("checksums-test-archive-unreadable.zip");
+    File::create(&file).unwrap().write_all(b"checksums").unwrap();
+
+    let (hashes, errors) = create_archive_hashes(&file, ArchiveFormat::Zip, &[Algorithm::SHA1], None, WalkOptions::new(&Default::default()), io::sink());
+    assert!(hashes.is_empty());
+    assert_eq!(errors.keys().collect::<Vec<_>>(), vec![file.file_name().unwrap()]);
+
+    fs::remove_file(&file).unwrap();
+}
+
+
+/// a.txt, dir/, dir/b.txt, dir/hardlink -> dir/b.txt, dir/symlink -> "../a b", and inner.tar with c.txt
+fn write_tar<W: Write>(out: W) {
+    let mut inner = tar::Builder::new(vec![]);
+    append(&mut inner, tar::EntryType::Regular, "c.txt", None, b"nested");
+
+    let mut builder = tar::Builder::new(out);
+    append(&mut builder, tar::EntryType::Regular, "./a.txt", None, b"checksums");
+    append(&mut builder, tar::EntryType::Directory, "dir/", None, b"");
+    append(&mut builder, tar::EntryType::Regular, "dir/b.txt", None, b"more checksums");
+    append(&mut builder, tar::EntryType::Link, "dir/hardlink", Some("dir/b.txt"), b"");
+    append(&mut builder, tar::EntryType::Symlink, "dir/symlink", Some("../a b"), b"");
+    append(&mut builder, tar::EntryType::Regular, "inner.tar", None, &inner.into_inner().unwrap());
+    builder.into_inner().unwrap();
+}
+
+fn append<W: Write>(builder: &mut tar::Builder<W>, kind: tar::EntryType, path: &str, link: Option<&str>, data: &[u8]) {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(kind);
+    header.set_size(data.len() as u64);
+    header.set_mode(0o640);
+    header.set_uid(1000);
+    header.set_gid(100);
+    header.set_mtime(1234567890);
+    if let Some(link) = link {
+        header.set_link_name(link).unwrap();
+    }
+    builder.append_data(&mut header, Path::new(path), data).unwrap();
+}
+
+fn sha1(data: &[u8]) -> String {
+    hash_reader(&mut &data[..], Algorithm::SHA1).unwrap()
+}
+
+fn values(values: &[(MetadataKind, &str)]) -> BTreeMap<MetadataKind, String> {
+    values.iter().map(|&(kind, value)| (kind, value.to_string())).collect()
+}
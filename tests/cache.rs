@@ -0,0 +1,80 @@
+extern crate checksums;
+
+use self::checksums::ops::{CacheEntry, FileStamp, WalkOptions, create_hashes, read_cache, write_cache};
+use self::checksums::Algorithm;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::env::temp_dir;
+use std::io::{self, Write};
+
+
+#[test]
+fn write_read() {
+    let mut cache = BTreeMap::new();
+    cache.insert("a b\\c\nd".into(), entry(12, Algorithm::SHA1, "8313958F86F7B15D4775D12886D479C1CFAAA111"));
+    cache.insert("e".into(), entry(0, Algorithm::MD5, "D41D8CD98F00B204E9800998ECF8427E"));
+
+    let path = temp_dir().join("checksums-test-cache-write_read.cache");
+    write_cache(&path, &cache).unwrap();
+    assert_eq!(read_cache(&path).unwrap(), cache);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn read_nonexistent() {
+    assert_eq!(read_cache(&temp_dir().join("checksums-test-cache-read_nonexistent.cache")).unwrap(),
+               BTreeMap::new());
+}
+
+#[test]
+fn hashes_for() {
+    let e = entry(12, Algorithm::SHA1, "8313958F86F7B15D4775D12886D479C1CFAAA111");
+
+    assert_eq!(e.hashes_for(&e.stamp, &[Algorithm::SHA1]), Some(e.hashes.clone()));
+    assert_eq!(e.hashes_for(&e.stamp, &[Algorithm::SHA1, Algorithm::MD5]), None);
+    assert_eq!(e.hashes_for(&FileStamp { size: 13, ..e.stamp }, &[Algorithm::SHA1]), None);
+    assert_eq!(e.hashes_for(&FileStamp { mtime: (1, 0), ..e.stamp }, &[Algorithm::SHA1]), None);
+}
+
+#[test]
+fn create_hashes_reuses_cache() {
+    let dir = temp_dir().join("checksums-test-cache-create_hashes_reuses_cache");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    File::create(dir.join("file")).unwrap().write_all(b"checksums").unwrap();
+
+    let filter = Default::default();
+    let mut cache = BTreeMap::new();
+    let (hashes, _) = create_hashes(&dir, &[Algorithm::CRC32], None, WalkOptions { cache: Some(&mut cache), ..WalkOptions::new(&filter) }, io::sink());
+    assert_eq!(hashes[OsStr::new("file")][&Algorithm::CRC32], "0B0ADA8E");
+    assert_eq!(cache[OsStr::new("file")].hashes, hashes[OsStr::new("file")]);
+
+    // Same stamp, so the (bogus) cached hash is used instead of reading the file
+    cache.get_mut(OsStr::new("file")).unwrap().hashes.insert(Algorithm::CRC32, "DEADBEEF".to_string());
+    let (hashes, _) = create_hashes(&dir, &[Algorithm::CRC32], None, WalkOptions { cache: Some(&mut cache), ..WalkOptions::new(&filter) }, io::sink());
+    assert_eq!(hashes[OsStr::new("file")][&Algorithm::CRC32], "DEADBEEF");
+
+    cache.get_mut(OsStr::new("file")).unwrap().stamp.size += 1;
+    let (hashes, _) = create_hashes(&dir, &[Algorithm::CRC32], None, WalkOptions { cache: Some(&mut cache), ..WalkOptions::new(&filter) }, io::sink());
+    assert_eq!(hashes[OsStr::new("file")][&Algorithm::CRC32], "0B0ADA8E");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+
+fn entry(size: u64, algo: Algorithm, hash: &str) -> CacheEntry {
+    let mut hashes = BTreeMap::new();
+    hashes.insert(algo, hash.to_string());
+
+    CacheEntry {
+        stamp: FileStamp {
+            size,
+            mtime: (1600000000, 123456789),
+            ctime: (-1, 999999999),
+            inode: 42,
+        },
+        hashes,
+    }
+}
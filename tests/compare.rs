@@ -0,0 +1,258 @@
+extern crate checksums;
+
+use self::checksums::ops::{CompareResult, CompareFileResult, FileFilter, HashesFormat, HashesHeader, compare_hashes, compare_sizes, create_sizes, read_hashes,
+                           retain_common_algorithms, update_hashes, write_hashes};
+use self::checksums::Algorithm;
+use std::collections::{BTreeSet, BTreeMap};
+use std::ffi::{OsStr, OsString};
+use std::env::temp_dir;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+
+
+#[test]
+fn moved_and_copied() {
+    let loaded = hashes(&[("a", "0B0ADA8E"), ("b", "CFAAA111"), ("c", "12345678"), ("gone", "DEADBEEF"), ("ignored", "--------")]);
+    let current = hashes(&[("a", "0B0ADA8E"), ("b2", "CFAAA111"), ("copy_of_a", "0B0ADA8E"), ("copy_of_b", "CFAAA111"), ("c", "12345678"),
+                           ("new", "FEEDFACE"), ("unrecorded", "--------")]);
+
+    let (mut results, file_results) = compare_hashes(OsStr::new("out.hash"), current, loaded).unwrap();
+    results.sort();
+    assert_eq!(results,
+               vec![CompareResult::FileAdded("new".into()),
+                    CompareResult::FileRemoved("gone".into()),
+                    CompareResult::FileRemoved("ignored".into()),
+                    CompareResult::FileIgnored("unrecorded".into()),
+                    CompareResult::FileMoved {
+                        from: "b".into(),
+                        to: "b2".into(),
+                    },
+                    CompareResult::FileCopied {
+                        from: "a".into(),
+                        to: "copy_of_a".into(),
+                    },
+                    CompareResult::FileCopied {
+                        from: "b".into(),
+                        to: "copy_of_b".into(),
+                    }]);
+    assert_eq!(file_results,
+               vec![CompareFileResult::FileMatches("a".into()), CompareFileResult::FileMatches("c".into())]);
+}
+
+#[test]
+fn moved_common_algorithms() {
+    let mut loaded = BTreeMap::new();
+    loaded.insert("old".into(), single(Algorithm::CRC32, "0B0ADA8E"));
+    let mut current = BTreeMap::new();
+    let mut current_hashes = single(Algorithm::CRC32, "0B0ADA8E");
+    current_hashes.insert(Algorithm::CRC16, "0BC4".to_string());
+    current.insert("new".into(), current_hashes);
+
+    assert_eq!(compare_hashes(OsStr::new("out.hash"), current, loaded).unwrap(),
+               (vec![CompareResult::FileMoved {
+                         from: "old".into(),
+                         to: "new".into(),
+                     }],
+                vec![]));
+}
+
+#[test]
+fn no_common_algorithms() {
+    let mut loaded = hashes(&[("a", "0B0ADA8E")]);
+    loaded.insert("b".into(), single(Algorithm::CRC16, "0BC4"));
+    let mut current = hashes(&[("a", "0B0ADA8E"), ("b", "CFAAA111")]);
+    current.insert("c".into(), BTreeMap::new());
+    loaded.insert("c".into(), BTreeMap::new());
+
+    assert_eq!(compare_hashes(OsStr::new("out.hash"), current, loaded).unwrap().1,
+               vec![CompareFileResult::FileMatches("a".into()),
+                    CompareFileResult::FileDiffers {
+                        file: "b".into(),
+                        was_hash: "CRC16 0BC4".to_string(),
+                        new_hash: "CRC32 CFAAA111".to_string(),
+                    },
+                    CompareFileResult::FileDiffers {
+                        file: "c".into(),
+                        was_hash: "none".to_string(),
+                        new_hash: "none".to_string(),
+                    }]);
+}
+
+#[test]
+fn update() {
+    let loaded = hashes(&[("changed", "0B0ADA8E"), ("gone", "DEADBEEF"), ("ignored", "--------"), ("ignored_gone", "--------"), ("old", "CFAAA111"),
+                          ("same", "12345678")]);
+    let current = hashes(&[("changed", "0BC40BC4"), ("ignored", "FEEDFACE"), ("ignored_new", "--------"), ("new", "CFAAA111"), ("added", "ABCDEF01"),
+                           ("same", "12345678")]);
+
+    let (updated, mut results, file_results) = update_hashes(OsStr::new("out.hash"), current.clone(), loaded.clone(), false).unwrap();
+    results.sort();
+    assert_eq!(updated,
+               hashes(&[("added", "ABCDEF01"), ("changed", "0B0ADA8E"), ("ignored", "--------"), ("ignored_gone", "--------"), ("ignored_new", "--------"),
+                        ("new", "CFAAA111"), ("same", "12345678")]));
+    assert_eq!(results,
+               vec![CompareResult::FileAdded("added".into()),
+                    CompareResult::FileRemoved("gone".into()),
+                    CompareResult::FileIgnored("ignored".into()),
+                    CompareResult::FileIgnored("ignored_gone".into()),
+                    CompareResult::FileIgnored("ignored_new".into()),
+                    CompareResult::FileMoved {
+                        from: "old".into(),
+                        to: "new".into(),
+                    }]);
+    assert_eq!(file_results.len(), 2);
+
+    let (updated, _, _) = update_hashes(OsStr::new("out.hash"), current, loaded, true).unwrap();
+    assert_eq!(updated[OsStr::new("changed")], single(Algorithm::CRC32, "0BC40BC4"));
+}
+
+#[test]
+fn update_keeps_header() {
+    let dir = temp_dir().join("checksums-test-compare-update_keeps_header");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    File::create(dir.join("a.txt")).unwrap().write_all(b"checksums").unwrap();
+    File::create(dir.join("sub").join("b.txt")).unwrap().write_all(b"nested").unwrap();
+    let file = ("update_keeps_header.hash".into(), temp_dir().join("checksums-test-compare-update_keeps_header.hash"));
+    let _ = fs::remove_file(&file.1);
+
+    let checksums = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_checksums")).args(args).arg(&dir).arg("-f").arg(&file.1).output().unwrap().status.code();
+    assert_eq!(checksums(&["-c", "-r", "-a", "CRC32,SHA1"]), Some(0));
+    File::create(dir.join("sub").join("c.txt")).unwrap().write_all(b"added").unwrap();
+    assert_eq!(checksums(&["-u"]), Some(0));
+
+    let (header, _, read) = read_hashes(&mut vec![], &file, &[]).unwrap();
+    let header = header.unwrap();
+    assert_eq!(header.depth, None);
+    assert_eq!(header.algorithms, vec![Algorithm::CRC32, Algorithm::SHA1]);
+    assert!(read.contains_key(Path::new("sub").join("b.txt").as_os_str()));
+    assert!(read.contains_key(Path::new("sub").join("c.txt").as_os_str()));
+
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_file(&file.1).unwrap();
+}
+
+#[test]
+fn verify_keeps_header() {
+    let dir = temp_dir().join("checksums-test-compare-verify_keeps_header");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    File::create(dir.join("a.txt")).unwrap().write_all(b"checksums").unwrap();
+    File::create(dir.join("sub").join("b.txt")).unwrap().write_all(b"nested").unwrap();
+    let file = temp_dir().join("checksums-test-compare-verify_keeps_header.hash");
+    let _ = fs::remove_file(&file);
+
+    let checksums = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_checksums")).args(args).arg(&dir).arg("-f").arg(&file).output().unwrap();
+    assert_eq!(checksums(&["-c", "-r"]).status.code(), Some(0));
+
+    let verified = checksums(&[]);
+    assert_eq!(verified.status.code(), Some(0));
+    assert!(!String::from_utf8_lossy(&verified.stdout).contains("removed"));
+
+    File::create(dir.join("sub").join("b.txt")).unwrap().write_all(b"changed").unwrap();
+    assert_ne!(checksums(&[]).status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&checksums(&["-d", "0"]).stdout).contains("removed"));
+
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn common_algorithms() {
+    let mut lhs = hashes(&[("a", "0B0ADA8E")]);
+    lhs.get_mut(OsStr::new("a")).unwrap().insert(Algorithm::CRC16, "0BC4".to_string());
+    lhs.insert("b".into(), single(Algorithm::CRC16, "0BC4"));
+    let mut rhs = hashes(&[("a", "0B0ADA8E"), ("b", "CFAAA111")]);
+
+    assert_eq!(retain_common_algorithms(&mut lhs, &mut rhs), vec![Algorithm::CRC32]);
+    assert_eq!(lhs[OsStr::new("a")], single(Algorithm::CRC32, "0B0ADA8E"));
+    assert!(lhs[OsStr::new("b")].is_empty());
+    assert_eq!(rhs, hashes(&[("a", "0B0ADA8E"), ("b", "CFAAA111")]));
+
+    let mut other = BTreeMap::new();
+    other.insert("a".into(), single(Algorithm::MD5, "098F6BCD4621D373CADE4E832627B4F6"));
+    assert_eq!(retain_common_algorithms(&mut rhs, &mut other), vec![]);
+}
+
+#[test]
+fn diff_formats() {
+    let old = hashes(&[("a", "0B0ADA8E"), ("b", "CFAAA111"), ("gone", "DEADBEEF")]);
+    let new = hashes(&[("a", "0BC40BC4"), ("b2", "CFAAA111"), ("new", "FEEDFACE")]);
+
+    let mut read = vec![];
+    for (hashes, fmt) in [(old, HashesFormat::Checksums), (new, HashesFormat::Bsd)] {
+        let file = (format!("checksums-test-compare-diff_formats-{:?}.hash", fmt).into(),
+                    temp_dir().join(format!("checksums-test-compare-diff_formats-{:?}.hash", fmt)));
+        write_hashes(&file,
+                     &HashesHeader::new(vec![Algorithm::CRC32], None, true),
+                     fmt,
+                     hashes,
+                     &BTreeSet::new(),
+                     &BTreeMap::new(),
+                     &BTreeMap::new())
+            .unwrap();
+
+        let (_, _, mut file_hashes) = read_hashes(&mut vec![], &file, &[Algorithm::SHA1]).unwrap();
+        file_hashes.remove(&file.0);
+        read.push(file_hashes);
+        fs::remove_file(&file.1).unwrap();
+    }
+    let (mut new, mut old) = (read.pop().unwrap(), read.pop().unwrap());
+
+    assert_eq!(retain_common_algorithms(&mut old, &mut new), vec![Algorithm::CRC32]);
+    let (mut results, file_results) = compare_hashes(OsStr::new(""), new, old).unwrap();
+    results.sort();
+    assert_eq!(results,
+               vec![CompareResult::FileAdded("new".into()),
+                    CompareResult::FileRemoved("gone".into()),
+                    CompareResult::FileMoved {
+                        from: "b".into(),
+                        to: "b2".into(),
+                    }]);
+    assert_eq!(file_results,
+               vec![CompareFileResult::FileDiffers {
+                        file: "a".into(),
+                        was_hash: "0B0ADA8E".to_string(),
+                        new_hash: "0BC40BC4".to_string(),
+                    }]);
+}
+
+
+#[test]
+fn sizes() {
+    let dir = temp_dir().join("checksums-test-compare-sizes");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    for &(fname, contents) in &[("a", &b"checksums"[..]), ("b", b""), ("sub/c", b"check"), ("ignored", b"sums")] {
+        File::create(dir.join(fname)).unwrap().write_all(contents).unwrap();
+    }
+
+    let filter = FileFilter::new(&dir, &["ignored".to_string()], &[], false).unwrap();
+    let loaded = create_sizes(&dir, &filter, None, true);
+    assert_eq!(loaded, vec![("a".into(), 9), ("b".into(), 0), ("sub/c".into(), 5)].into_iter().collect());
+    assert_eq!(create_sizes(&dir, &filter, Some(0), true).len(), 2);
+
+    File::create(dir.join("b")).unwrap().write_all(b"checksums").unwrap();
+    fs::remove_file(dir.join("sub").join("c")).unwrap();
+    let current = create_sizes(&dir, &filter, None, true);
+    assert_eq!(compare_sizes(&current, &loaded),
+               vec![CompareFileResult::SizeDiffers {
+                        file: "b".into(),
+                        was_size: 0,
+                        new_size: 9,
+                    }]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn hashes(files: &[(&str, &str)]) -> BTreeMap<OsString, BTreeMap<Algorithm, String>> {
+    files.iter().map(|&(fname, hash)| (fname.into(), single(Algorithm::CRC32, hash))).collect()
+}
+
+fn single(algo: Algorithm, hash: &str) -> BTreeMap<Algorithm, String> {
+    let mut hashes = BTreeMap::new();
+    hashes.insert(algo, hash.to_string());
+    hashes
+}
@@ -0,0 +1,133 @@
+extern crate checksums;
+
+use self::checksums::ops::{CompareResult, HashesFormat, HashesHeader, TreeDigester, compare_digests, parse_digest_line, read_digests, read_hashes, tree_digests,
+                           write_hashes};
+use self::checksums::{Algorithm, hash_reader};
+use std::collections::{BTreeSet, BTreeMap};
+use std::env::temp_dir;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::Write;
+use std::process::Command;
+
+
+#[test]
+fn single_file() {
+    let digests = tree_digests(&hashes(&[("a.txt", "0B0ADA8E")]), Algorithm::CRC32);
+    assert_eq!(digests.len(), 1);
+    assert_eq!(digests[OsStr::new(".")], hash_reader(&mut &b"a.txt\x000B0ADA8E\n"[..], Algorithm::CRC32).unwrap());
+}
+
+#[test]
+fn nested() {
+    let digests = tree_digests(&hashes(&[("a.txt", "00000001"), ("a/b/x", "00000002"), ("c/y", "00000003")]), Algorithm::CRC32);
+    assert_eq!(digests.keys().collect::<Vec<_>>(), vec![OsStr::new("."), OsStr::new("a"), OsStr::new("a/b"), OsStr::new("c")]);
+
+    let b = hash_reader(&mut &b"x\x0000000002\n"[..], Algorithm::CRC32).unwrap();
+    let a = hash_reader(&mut format!("b/\0{}\n", b).as_bytes(), Algorithm::CRC32).unwrap();
+    let c = hash_reader(&mut &b"y\x0000000003\n"[..], Algorithm::CRC32).unwrap();
+    assert_eq!(digests[OsStr::new("a/b")], b);
+    assert_eq!(digests[OsStr::new("a")], a);
+    assert_eq!(digests[OsStr::new("c")], c);
+    // "a" sorts before "a.txt", even though "a/" doesn't
+    assert_eq!(digests[OsStr::new(".")],
+               hash_reader(&mut format!("a/\0{}\na.txt\000000001\nc/\0{}\n", a, c).as_bytes(), Algorithm::CRC32).unwrap());
+}
+
+#[test]
+fn streamed() {
+    let files = [("a/b/x", "00000002"), ("a.txt", "00000001"), ("c/y", "00000003"), ("c/z", "00000004")];
+
+    let mut digester = TreeDigester::new(Algorithm::CRC32);
+    for &(fname, hash) in &files {
+        digester.add(OsStr::new(fname), &hashes(&[(fname, hash)])[OsStr::new(fname)]);
+    }
+    assert_eq!(digester.finish(), tree_digests(&hashes(&files), Algorithm::CRC32));
+}
+
+#[test]
+fn skipped() {
+    let base = tree_digests(&hashes(&[("a.txt", "00000001")]), Algorithm::CRC32);
+
+    let mut with_ignored = hashes(&[("a.txt", "00000001"), ("dir/ignored", "--------")]);
+    assert_eq!(tree_digests(&with_ignored, Algorithm::CRC32), base);
+
+    with_ignored.insert("other-algo".into(), vec![(Algorithm::MD5, "098F6BCD4621D373CADE4E832627B4F6".to_string())].into_iter().collect());
+    assert_eq!(tree_digests(&with_ignored, Algorithm::CRC32), base);
+}
+
+#[test]
+fn parse_line() {
+    assert_eq!(parse_digest_line("# digest sha1 8313958f86f7b15d4775d12886d479c1cfaaa111 : a\\\\b"),
+               Some(("a\\b".into(), Algorithm::SHA1, "8313958F86F7B15D4775D12886D479C1CFAAA111".to_string())));
+    assert_eq!(parse_digest_line("# digest SHA0 8313958F86F7B15D4775D12886D479C1CFAAA111 : a"), None);
+    assert_eq!(parse_digest_line("# digest SHA1 8313958F86F7B15D4775D12886D479C1CFAAA111"), None);
+}
+
+#[test]
+fn write_read() {
+    let hashes = hashes(&[("a.txt", "00000001"), ("dir/b", "00000002"), ("tab\tdir /c", "00000003")]);
+    let mut digests = BTreeMap::new();
+    digests.insert(Algorithm::CRC32, tree_digests(&hashes, Algorithm::CRC32));
+
+    for fmt in &HashesFormat::ALL {
+        let file = ("checksums-test-digests-write_read.hash".into(), temp_dir().join(format!("checksums-test-digests-write_read-{:?}.hash", fmt)));
+        write_hashes(&file,
+                     &HashesHeader::new(vec![Algorithm::CRC32], None, true),
+                     *fmt,
+                     hashes.clone(),
+                     &BTreeSet::new(),
+                     &digests,
+                     &BTreeMap::new())
+            .unwrap();
+
+        let (_, _, mut read) = read_hashes(&mut vec![], &file, &[Algorithm::CRC32]).unwrap();
+        read.remove(&file.0);
+        assert_eq!(read, hashes);
+        assert_eq!(read_digests(&mut vec![], &file), Ok(digests.clone()));
+
+        fs::remove_file(&file.1).unwrap();
+    }
+}
+
+#[test]
+fn compare() {
+    let old = tree_digests(&hashes(&[("a.txt", "00000001"), ("a/b/x", "00000002"), ("c/y", "00000003")]), Algorithm::CRC32);
+    let new = tree_digests(&hashes(&[("a.txt", "00000001"), ("a/b/x", "00000004"), ("d/y", "00000003")]), Algorithm::CRC32);
+    assert_eq!(compare_digests(&old, &old), vec![]);
+    assert_eq!(compare_digests(&new, &old).into_iter().map(|res| match res {
+                   CompareResult::DigestDiffers { dir, .. } => dir,
+                   res => panic!("{:?}", res),
+               }).collect::<Vec<_>>(),
+               vec![OsString::from("."), "a".into(), "a/b".into()]);
+}
+
+#[test]
+fn verify() {
+    let dir = temp_dir().join("checksums-test-digests-verify");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::create_dir_all(dir.join("other")).unwrap();
+    File::create(dir.join("sub").join("a.txt")).unwrap().write_all(b"checksums").unwrap();
+    File::create(dir.join("other").join("b.txt")).unwrap().write_all(b"unchanged").unwrap();
+    let file = temp_dir().join("checksums-test-digests-verify.hash");
+    let _ = fs::remove_file(&file);
+
+    let checksums = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_checksums")).args(args).arg(&dir).arg("-f").arg(&file).output().unwrap();
+    assert_eq!(checksums(&["-c", "-r", "--tree-digests"]).status.code(), Some(0));
+    assert!(!String::from_utf8_lossy(&checksums(&[]).stdout).contains("digest differs"));
+
+    File::create(dir.join("sub").join("a.txt")).unwrap().write_all(b"changed").unwrap();
+    let verified = String::from_utf8_lossy(&checksums(&[]).stdout).into_owned();
+    assert!(verified.contains("Directory digest differs: \".\""));
+    assert!(verified.contains("Directory digest differs: \"sub\""));
+    assert!(!verified.contains("Directory digest differs: \"other\""));
+
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_file(&file).unwrap();
+}
+
+
+fn hashes(files: &[(&str, &str)]) -> BTreeMap<OsString, BTreeMap<Algorithm, String>> {
+    files.iter().map(|&(fname, hash)| (fname.into(), vec![(Algorithm::CRC32, hash.to_string())].into_iter().collect())).collect()
+}
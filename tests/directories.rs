@@ -0,0 +1,101 @@
+extern crate checksums;
+
+use self::checksums::ops::{CompareResult, FileFilter, HashesFormat, HashesHeader, compare_directories, create_directories, read_directories, read_hashes,
+                           write_hash_comparison_results, write_hashes};
+use self::checksums::{Algorithm, Error};
+use std::collections::{BTreeSet, BTreeMap};
+use std::fs::{self, File};
+use std::env::temp_dir;
+use std::ffi::OsString;
+
+
+#[test]
+fn create() {
+    let dir = temp_dir().join("checksums-test-directories-create");
+    let _ = fs::remove_dir_all(&dir);
+    for sub in &["empty", "full/sub", "tmp/sub"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    File::create(dir.join("full").join("file")).unwrap();
+
+    let filter = FileFilter::new(&dir, &["tmp/".to_string()], &[], false).unwrap();
+    assert_eq!(create_directories(&dir, &filter, None, true), set(&["empty", "full", "full/sub"]));
+    assert_eq!(create_directories(&dir, &filter, Some(0), true), set(&["empty", "full"]));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn create_included() {
+    let dir = temp_dir().join("checksums-test-directories-create_included");
+    let _ = fs::remove_dir_all(&dir);
+    for sub in &["doc", "empty", "src"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    File::create(dir.join("doc").join("r.md")).unwrap();
+    File::create(dir.join("src").join("main.rs")).unwrap();
+
+    let filter = FileFilter::new(&dir, &[], &["*.md".to_string()], false).unwrap();
+    assert_eq!(create_directories(&dir, &filter, None, true), set(&["doc", "empty", "src"]));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compare() {
+    assert_eq!(compare_directories(&set(&["a", "b/c"]), &set(&["a", "b/c"])), vec![]);
+    assert_eq!(compare_directories(&set(&["a", "b", "b/c"]), &set(&["a", "d"])),
+               vec![CompareResult::DirectoryAdded("b".into()), CompareResult::DirectoryAdded("b/c".into()), CompareResult::DirectoryRemoved("d".into())]);
+}
+
+#[test]
+fn header() {
+    let header = HashesHeader { directories: true, ..HashesHeader::new(vec![Algorithm::SHA1], None, true) };
+    assert!(header.to_string().ends_with(" directories=yes"));
+    assert_eq!(HashesHeader::parse(&header.to_string()), Some(header));
+
+    assert!(!HashesHeader::new(vec![Algorithm::SHA1], None, true).to_string().contains("directories"));
+}
+
+#[test]
+fn write_read() {
+    let mut hashes = BTreeMap::new();
+    hashes.insert("a.txt".into(), single(Algorithm::SHA1, "8313958F86F7B15D4775D12886D479C1CFAAA111"));
+    hashes.insert("# directory b".into(), single(Algorithm::SHA1, "8D742C1F2D39434771039E98AD854C72F91FCCA5"));
+    let directories = set(&["empty", "with\nnewline", "back\\slash", "tab\tand space "]);
+
+    for fmt in &HashesFormat::ALL {
+        let file = ("checksums-test-directories-write_read.hash".into(), temp_dir().join(format!("checksums-test-directories-write_read-{:?}.hash", fmt)));
+        let header = HashesHeader { directories: true, ..HashesHeader::new(vec![Algorithm::SHA1], None, true) };
+        write_hashes(&file, &header, *fmt, hashes.clone(), &directories, &BTreeMap::new(), &BTreeMap::new()).unwrap();
+        assert!(fs::read_to_string(&file.1).unwrap().contains("\n# directory with\\nnewline\n"));
+        assert!(fs::read_to_string(&file.1).unwrap().contains("\n# directory tab\\tand space\\x20\n"));
+
+        let (read_header, _, mut read) = read_hashes(&mut vec![], &file, &[Algorithm::SHA1]).unwrap();
+        assert_eq!(read_header, Some(header));
+        read.remove(&file.0);
+        assert_eq!(read, hashes);
+        assert_eq!(read_directories(&mut vec![], &file), Ok(directories.clone()));
+
+        fs::remove_file(&file.1).unwrap();
+    }
+}
+
+#[test]
+fn write_results() {
+    let mut out = vec![];
+    let results = vec![CompareResult::DirectoryRemoved("empty".into()), CompareResult::DirectoryAdded("new".into())];
+    assert_eq!(write_hash_comparison_results(&mut out, &mut vec![], Ok((results, vec![]))), Error::NoError);
+    assert_eq!(String::from_utf8(out).unwrap(), "Directory added: \"new\"\nDirectory removed: \"empty\"\nNo files to verify\n");
+}
+
+
+fn set(names: &[&str]) -> BTreeSet<OsString> {
+    names.iter().map(OsString::from).collect()
+}
+
+fn single(algo: Algorithm, hash: &str) -> BTreeMap<Algorithm, String> {
+    let mut hashes = BTreeMap::new();
+    hashes.insert(algo, hash.to_string());
+    hashes
+}
@@ -0,0 +1,165 @@
+extern crate checksums;
+
+use self::checksums::ops::{DuplicateGroup, LinkKind, PARTIAL_HASH_SIZE, find_duplicates, link_duplicates, write_duplicates, write_duplicates_json};
+use self::checksums::{Algorithm, Error, hash_reader};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::fs::{self, File};
+use std::env::temp_dir;
+use std::str::FromStr;
+use std::path::Path;
+
+
+#[test]
+fn link_kind_from_str() {
+    assert_eq!(LinkKind::from_str("HARDLINK"), Ok(LinkKind::Hard));
+    assert_eq!(LinkKind::from_str("clone"), Ok(LinkKind::Reflink));
+    LinkKind::from_str("soft").unwrap_err();
+}
+
+#[test]
+fn find() {
+    let dir = temp_dir().join("checksums-test-dupes-find");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    let big: Vec<u8> = (0..PARTIAL_HASH_SIZE * 2).map(|i| i as u8).collect();
+    let mut big_tail = big.clone();
+    *big_tail.last_mut().unwrap() ^= 1;
+
+    for &(fname, contents) in &[("a", &b"checksums"[..]),
+                                ("b", b"checksums"),
+                                ("c", b"checksumz"),
+                                ("sub/a", b"checksums"),
+                                ("big", &big),
+                                ("big-copy", &big),
+                                ("big-tail", &big_tail),
+                                ("empty", b""),
+                                ("sub/empty", b"")] {
+        File::create(dir.join(fname)).unwrap().write_all(contents).unwrap();
+    }
+
+    let (groups, errors) = find_duplicates(&dir, &Default::default(), Algorithm::SHA1, None, true, 2, io::sink());
+    assert!(errors.is_empty());
+    assert_eq!(groups,
+               vec![DuplicateGroup {
+                        size: big.len() as u64,
+                        hash: hash_reader(&mut &big[..], Algorithm::SHA1).unwrap(),
+                        files: vec![vec!["big".into()], vec!["big-copy".into()]],
+                    },
+                    DuplicateGroup {
+                        size: 9,
+                        hash: hash_reader(&mut &b"checksums"[..], Algorithm::SHA1).unwrap(),
+                        files: vec![vec!["a".into()], vec!["b".into()], vec!["sub/a".into()]],
+                    }]);
+
+    let (groups, _) = find_duplicates(&dir, &Default::default(), Algorithm::SHA1, Some(0), true, 1, io::sink());
+    assert_eq!(groups.iter().map(|g| g.files.len()).collect::<Vec<_>>(), vec![2, 2]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn hardlinks() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = temp_dir().join("checksums-test-dupes-hardlinks");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    for fname in &["a", "b", "c"] {
+        File::create(dir.join(fname)).unwrap().write_all(b"checksums").unwrap();
+    }
+    fs::hard_link(dir.join("b"), dir.join("b-link")).unwrap();
+
+    let (groups, _) = find_duplicates(&dir, &Default::default(), Algorithm::SHA1, None, true, 1, io::sink());
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].files, vec![vec!["a"], vec!["b", "b-link"], vec!["c"]]);
+    assert_eq!(groups[0].wasted(), 18);
+
+    let results = link_duplicates(&dir, &groups[0], LinkKind::Hard);
+    assert_eq!(results.keys().collect::<Vec<_>>(), vec!["b", "b-link", "c"]);
+    assert!(results.values().all(|r| r.is_ok()));
+    let inode = |fname: &str| fs::metadata(dir.join(fname)).unwrap().ino();
+    assert!(["b", "b-link", "c"].iter().all(|fname| inode(fname) == inode("a")));
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 4);
+
+    let (groups, _) = find_duplicates(&dir, &Default::default(), Algorithm::SHA1, None, true, 1, io::sink());
+    assert_eq!(groups, vec![]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn link_changed() {
+    let dir = temp_dir().join("checksums-test-dupes-link_changed");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    for fname in &["a", "b"] {
+        File::create(dir.join(fname)).unwrap().write_all(b"checksums").unwrap();
+    }
+
+    let (groups, _) = find_duplicates(&dir, &Default::default(), Algorithm::SHA1, None, true, 1, io::sink());
+    File::create(dir.join("b")).unwrap().write_all(b"checksumz").unwrap();
+
+    let results = link_duplicates(&dir, &groups[0], LinkKind::Hard);
+    assert_eq!(results[Path::new("b").as_os_str()].as_ref().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    assert_eq!(fs::read(dir.join("b")).unwrap(), b"checksumz");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn write_results() {
+    let groups = vec![DuplicateGroup {
+                          size: 9,
+                          hash: "8313958F86F7B15D4775D12886D479C1CFAAA111".to_string(),
+                          files: vec![vec!["a".into()], vec!["b".into(), "b-link".into()], vec!["c".into()]],
+                      }];
+    let mut links = BTreeMap::new();
+    links.insert("b".into(), Ok(()));
+    links.insert("b-link".into(), Ok(()));
+    links.insert("c".into(), Err(io::Error::other("read-only")));
+
+    let mut out = vec![];
+    assert_eq!(write_duplicates(&mut out, &groups, &BTreeMap::new()), Error::NoError);
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "Identical files, 9 bytes each:\n  Hash: 8313958F86F7B15D4775D12886D479C1CFAAA111\n  \"a\"\n  \"b\"\n  Same file: \"b-link\"\n  \"c\"\n\n\
+                1 groups, 18 bytes duplicated, 0 files linked\n");
+
+    let mut out = vec![];
+    assert_eq!(write_duplicates(&mut out, &groups, &links), Error::NFilesUnreadable(1));
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("\n  \"a\"\n  Linked: \"b\"\n  Linked: \"b-link\"\n  Couldn't link: \"c\"\n    read-only\n"));
+    assert!(out.ends_with("\n1 groups, 18 bytes duplicated, 2 files linked\n"));
+
+    let mut out = vec![];
+    assert_eq!(write_duplicates(&mut out, &[], &BTreeMap::new()), Error::NoError);
+    assert_eq!(String::from_utf8(out).unwrap(), "No identical files\n");
+}
+
+#[test]
+fn write_results_json() {
+    let groups = vec![DuplicateGroup {
+                          size: 9,
+                          hash: "8313958F86F7B15D4775D12886D479C1CFAAA111".to_string(),
+                          files: vec![vec!["a".into()], vec!["b".into()]],
+                      }];
+    let mut errors = BTreeMap::new();
+    errors.insert("unreadable".into(), io::Error::other("denied"));
+    let mut links = BTreeMap::new();
+    links.insert("b".into(), Ok(()));
+
+    let mut out = vec![];
+    assert_eq!(write_duplicates_json(&mut out, &errors, &groups, &links, true), Error::NFilesUnreadable(1));
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "{\"error\":\"denied\",\"file\":\"unreadable\",\"result\":\"unreadable\"}\n\
+                {\"files\":[[\"a\"],[\"b\"]],\"hash\":\"8313958F86F7B15D4775D12886D479C1CFAAA111\",\"result\":\"duplicates\",\"size\":9}\n\
+                {\"file\":\"b\",\"result\":\"linked\"}\n\
+                {\"summary\":{\"duplicated_bytes\":9,\"groups\":1,\"link_failed\":0,\"linked\":1,\"unreadable\":1}}\n");
+
+    let mut out = vec![];
+    assert_eq!(write_duplicates_json(&mut out, &BTreeMap::new(), &[], &BTreeMap::new(), false), Error::NoError);
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "{\"results\":[],\"summary\":{\"duplicated_bytes\":0,\"groups\":0,\"link_failed\":0,\"linked\":0,\"unreadable\":0}}\n");
+}
@@ -1,6 +1,10 @@
 extern crate checksums;
 
 use checksums::Error;
+use std::fs::{self, File};
+use std::env::temp_dir;
+use std::process::Command;
+use std::io::Write;
 
 
 #[test]
@@ -11,6 +15,12 @@ fn exit_value() {
     assert_eq!(Error::HashesFileParsingFailure.exit_value(), 3);
     assert_eq!(Error::NFilesDiffer(1).exit_value(), 4);
     assert_eq!(Error::NFilesDiffer(10).exit_value(), 13);
+    assert_eq!(Error::NFilesDiffer(252).exit_value(), 99);
+    assert_eq!(Error::HashesFileIoFailure.exit_value(), 100);
+    assert_eq!(Error::NFilesUnreadable(1).exit_value(), 101);
+    assert_eq!(Error::NFilesUnreadable(10).exit_value(), 110);
+    assert_eq!(Error::NFilesUnreadable(255).exit_value(), 199);
+    assert_eq!(Error::SignatureInvalid.exit_value(), 200);
 }
 
 #[test]
@@ -21,4 +31,34 @@ fn from_i32() {
     assert_eq!(Error::from(3), Error::HashesFileParsingFailure);
     assert_eq!(Error::from(4), Error::NFilesDiffer(1));
     assert_eq!(Error::from(13), Error::NFilesDiffer(10));
+    assert_eq!(Error::from(100), Error::HashesFileIoFailure);
+    assert_eq!(Error::from(101), Error::NFilesUnreadable(1));
+    assert_eq!(Error::from(110), Error::NFilesUnreadable(10));
+    assert_eq!(Error::from(200), Error::SignatureInvalid);
+}
+
+#[test]
+fn exit_status() {
+    let dir = temp_dir().join("checksums-test-error-exit_status");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    File::create(dir.join("a.txt")).unwrap().write_all(b"checksums").unwrap();
+    let file = dir.join("exit_status.hash");
+
+    let checksums = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_checksums")).args(args).arg(&dir).arg("-f").arg(&file).output().unwrap().status.code();
+    assert_eq!(checksums(&["-c"]), Some(0));
+
+    File::create(dir.join("a.txt")).unwrap().write_all(b"changed").unwrap();
+    assert_eq!(checksums(&[]), Some(Error::NFilesDiffer(1).exit_value()));
+
+    File::create(&file).unwrap().write_all(b"\xFF\n").unwrap();
+    assert_eq!(checksums(&[]), Some(Error::HashesFileIoFailure.exit_value()));
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(dir.join("gone"), dir.join("dangling")).unwrap();
+        assert_eq!(checksums(&["-c", "--force"]), Some(Error::NFilesUnreadable(1).exit_value()));
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
 }
@@ -0,0 +1,67 @@
+extern crate checksums;
+
+use self::checksums::ops::{FileFilter, WalkOptions, create_hashes};
+use self::checksums::Algorithm;
+use std::fs::{self, File};
+use std::env::temp_dir;
+use std::io::{self, Write};
+
+
+#[test]
+fn create_hashes_filtered() {
+    let dir = temp_dir().join("checksums-test-filter-create_hashes_filtered");
+    let _ = fs::remove_dir_all(&dir);
+    for subdir in &["src/node_modules/pkg", "doc"] {
+        fs::create_dir_all(dir.join(subdir)).unwrap();
+    }
+    for file in &["src/a.rs", "src/a.tmp", "src/keep.tmp", "src/node_modules/pkg/b.rs", "doc/index.html", "README.md"] {
+        File::create(dir.join(file)).unwrap();
+    }
+
+    let filter = FileFilter::new(&dir,
+                                 &["*.tmp".to_string(), "!keep.tmp".to_string(), "node_modules/".to_string()],
+                                 &["src".to_string(), "*.md".to_string()],
+                                 false)
+        .unwrap();
+    let (hashes, errors) = create_hashes(&dir, &[Algorithm::CRC8], None, WalkOptions::new(&filter), io::sink());
+    assert!(errors.is_empty());
+    assert_eq!(hashes.into_iter().map(|(fname, hashes)| (fname, hashes[&Algorithm::CRC8].clone())).collect::<Vec<_>>(),
+               vec![("README.md".into(), "00".to_string()),
+                    ("src/a.rs".into(), "00".to_string()),
+                    ("src/a.tmp".into(), "--".to_string()),
+                    ("src/keep.tmp".into(), "00".to_string())]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn create_hashes_ignore_files() {
+    let dir = temp_dir().join("checksums-test-filter-create_hashes_ignore_files");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub/secret")).unwrap();
+    for &(file, contents) in &[(".gitignore", "*.log\nbuild/\n"),
+                               (".ignore", "!debug.log\n"),
+                               ("sub/.checksumsignore", "!*.log\nsecret\n"),
+                               ("a.log", ""),
+                               ("debug.log", ""),
+                               ("forced.log", ""),
+                               ("sub/b.log", ""),
+                               ("sub/secret/c", "")] {
+        File::create(dir.join(file)).unwrap().write_all(contents.as_bytes()).unwrap();
+    }
+    fs::create_dir(dir.join("build")).unwrap();
+    File::create(dir.join("build/d")).unwrap();
+
+    let filter = FileFilter::new(&dir, &["!forced.log".to_string()], &[], true).unwrap();
+    let (hashes, _) = create_hashes(&dir, &[Algorithm::CRC8], None, WalkOptions::new(&filter), io::sink());
+    assert_eq!(hashes.into_iter()
+                   .filter(|(fname, _)| !fname.to_str().unwrap().rsplit('/').next().unwrap().starts_with('.'))
+                   .map(|(fname, hashes)| (fname, hashes[&Algorithm::CRC8].clone()))
+                   .collect::<Vec<_>>(),
+               vec![("a.log".into(), "--".to_string()),
+                    ("debug.log".into(), "00".to_string()),
+                    ("forced.log".into(), "00".to_string()),
+                    ("sub/b.log".into(), "00".to_string())]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
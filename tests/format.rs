@@ -0,0 +1,337 @@
+extern crate checksums;
+
+use self::checksums::ops::{HashesFormat, HashesHeader, HashesWriter, MetadataKind, hashes_temp_file, read_hashes, read_hashes_file, write_hashes};
+use self::checksums::{Algorithm, Error};
+use std::collections::{BTreeSet, BTreeMap};
+use std::fs::{self, File};
+use std::io::Write;
+use std::env::temp_dir;
+use std::str::FromStr;
+use std::ffi::{OsStr, OsString};
+
+
+#[test]
+fn from_str() {
+    for p in &[("checksums", HashesFormat::Checksums),
+               ("native", HashesFormat::Checksums),
+               ("coreutils", HashesFormat::Coreutils),
+               ("gnu", HashesFormat::Coreutils),
+               ("sha256sum", HashesFormat::Coreutils),
+               ("bsd", HashesFormat::Bsd),
+               ("tag", HashesFormat::Bsd),
+               ("tagged", HashesFormat::Bsd)] {
+        assert_eq!(HashesFormat::from_str(p.0).unwrap(), p.1);
+    }
+}
+
+#[test]
+fn from_str_bad() {
+    for s in &["asdf", "core", "sum", ""] {
+        HashesFormat::from_str(s).unwrap_err();
+    }
+}
+
+#[test]
+fn line_round_trip() {
+    for fmt in &HashesFormat::ALL {
+        for fname in &["file", "dir/file", "with space", "back\\slash", "with\nnewline", "(paren) = thesis", "tab\tbed", "trailing  ", " leading",
+                       "runs  of   spaces", "  ", "trailing\t", "nbsp\u{a0}", "hashlike  0B0ADA8E", "\\", "\\x41 \\t", "#comment",
+                       "# metadata mode=644 : file"] {
+            let mut line = fmt.format_line(OsStr::new(fname), &[(Algorithm::SHA1, "8D742C1F2D39434771039E98AD854C72F91FCCA5")]);
+            if *fmt == HashesFormat::Checksums {
+                // As aligned by a TabWriter
+                line = line.replace('\t', "  ");
+            }
+            let (parsed_fname, parsed_algo, parsed_hashes) = fmt.parse_line(&line, 1).unwrap();
+            assert_eq!(parsed_fname, *fname);
+            assert_eq!(parsed_hashes, vec!["8D742C1F2D39434771039E98AD854C72F91FCCA5".to_string()]);
+            if *fmt == HashesFormat::Bsd {
+                assert_eq!(parsed_algo, Some(Algorithm::SHA1));
+            } else {
+                assert_eq!(parsed_algo, None);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn line_round_trip_non_utf8() {
+    use std::os::unix::ffi::OsStrExt;
+
+    for fmt in &HashesFormat::ALL {
+        for fname in &[&b"caf\xE9"[..], b"\xFF\xFE", b"back\\slash \xC3 \\x41"] {
+            let fname = OsStr::from_bytes(fname);
+            let line = fmt.format_line(fname, &[(Algorithm::CRC32, "0B0ADA8E")]).replace('\t', "  ");
+            assert_eq!(fmt.parse_line(&line, 1).unwrap().0, fname);
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn write_read_non_utf8() {
+    use self::checksums::ops::{WalkOptions, create_hashes};
+    use std::os::unix::ffi::OsStrExt;
+    use std::io;
+
+    let dir = temp_dir().join("checksums-test-format-write_read_non_utf8");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    File::create(dir.join(OsStr::from_bytes(b"latin1-caf\xE9"))).unwrap().write_all(b"checksums").unwrap();
+
+    let (hashes, errors) = create_hashes(&dir, &[Algorithm::CRC32], None, WalkOptions::new(&Default::default()), io::sink());
+    assert!(errors.is_empty());
+    assert_eq!(hashes.keys().collect::<Vec<_>>(), vec![OsStr::from_bytes(b"latin1-caf\xE9")]);
+
+    for fmt in &HashesFormat::ALL {
+        let file = ("checksums-test-format-write_read_non_utf8.hash".into(), temp_dir().join(format!("checksums-test-format-write_read_non_utf8-{:?}.hash", fmt)));
+        write_hashes(&file,
+                     &HashesHeader::new(vec![Algorithm::CRC32], None, true),
+                     *fmt,
+                     hashes.clone(),
+                     &BTreeSet::new(),
+                     &BTreeMap::new(),
+                     &BTreeMap::new())
+            .unwrap();
+        assert!(fs::read_to_string(&file.1).unwrap().contains("latin1-caf\\xE9"));
+
+        let (_, _, mut read) = read_hashes(&mut vec![], &file, &[Algorithm::CRC32]).unwrap();
+        read.remove(&file.0);
+        assert_eq!(read, hashes);
+
+        fs::remove_file(&file.1).unwrap();
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn coreutils_binary_marker() {
+    assert_eq!(HashesFormat::Coreutils.parse_line("8d742c1f2d39434771039e98ad854c72f91fcca5 *file", 1),
+               Some(("file".into(), None, vec!["8D742C1F2D39434771039E98AD854C72F91FCCA5".to_string()])));
+    assert_eq!(HashesFormat::Coreutils.parse_line("8d742c1f2d39434771039e98ad854c72f91fcca5   file", 1),
+               Some((" file".into(), None, vec!["8D742C1F2D39434771039E98AD854C72F91FCCA5".to_string()])));
+    assert_eq!(HashesFormat::Coreutils.parse_line("\\8d742c1f2d39434771039e98ad854c72f91fcca5  bad\\escape", 1), None);
+}
+
+#[test]
+fn placeholders_checksums_only() {
+    assert_eq!(HashesFormat::Checksums.parse_line("ignored  --------", 1), Some(("ignored".into(), None, vec!["--------".to_string()])));
+    assert_eq!(HashesFormat::Coreutils.parse_line("--------  ignored", 1), None);
+    assert_eq!(HashesFormat::Bsd.parse_line("CRC32 (ignored) = --------", 1), None);
+}
+
+#[test]
+fn bsd_openssl() {
+    assert_eq!(HashesFormat::Bsd.parse_line("SHA2-256(file)= 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08", 1),
+               Some(("file".into(), Some(Algorithm::SHA2256), vec!["9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08".to_string()])));
+    assert_eq!(HashesFormat::Bsd.parse_line("MD5 (file) = 098f6bcd4621d373cade4e832627b4f6", 1),
+               Some(("file".into(), Some(Algorithm::MD5), vec!["098F6BCD4621D373CADE4E832627B4F6".to_string()])));
+}
+
+#[test]
+fn write_read() {
+    let mut hashes = BTreeMap::new();
+    hashes.insert("a.txt".into(), single(Algorithm::SHA1, "8313958F86F7B15D4775D12886D479C1CFAAA111"));
+    hashes.insert("dir/b c".into(), single(Algorithm::SHA1, "8D742C1F2D39434771039E98AD854C72F91FCCA5"));
+    hashes.insert("ignored".into(), single(Algorithm::SHA1, "----------------------------------------"));
+
+    for fmt in &HashesFormat::ALL {
+        let file = ("checksums-test-format-write_read.hash".into(), temp_dir().join(format!("checksums-test-format-write_read-{:?}.hash", fmt)));
+        let header = HashesHeader::new(vec![Algorithm::SHA1], Some(0), true);
+        write_hashes(&file, &header, *fmt, hashes.clone(), &BTreeSet::new(), &BTreeMap::new(), &BTreeMap::new()).unwrap();
+
+        let (read_header, read_format, mut read) = read_hashes(&mut vec![], &file, &[Algorithm::SHA1]).unwrap();
+        assert_eq!(read_header, Some(header));
+        assert_eq!(read_format, *fmt);
+        if *fmt == HashesFormat::Checksums {
+            assert_eq!(read.remove(&file.0), Some(single(Algorithm::SHA1, "----------------------------------------")));
+            assert_eq!(read, hashes);
+        } else {
+            // Only the checksums format records ignored files
+            assert!(!fs::read_to_string(&file.1).unwrap().contains("--"));
+            let mut recorded = hashes.clone();
+            recorded.remove(OsStr::new("ignored"));
+            assert_eq!(read, recorded);
+        }
+
+        fs::remove_file(&file.1).unwrap();
+    }
+}
+
+#[test]
+fn write_read_multiple() {
+    let algos = [Algorithm::CRC32, Algorithm::SHA1];
+    let mut hashes = BTreeMap::new();
+    for &(fname, crc, sha) in &[("a.txt", "0B0ADA8E", "8313958F86F7B15D4775D12886D479C1CFAAA111"),
+                                ("dir/b  c", "CFAAA111", "8D742C1F2D39434771039E98AD854C72F91FCCA5"),
+                                ("ignored", "--------", "----------------------------------------")] {
+        let mut file_hashes = BTreeMap::new();
+        file_hashes.insert(Algorithm::CRC32, crc.to_string());
+        file_hashes.insert(Algorithm::SHA1, sha.to_string());
+        hashes.insert(fname.into(), file_hashes);
+    }
+
+    for fmt in &[HashesFormat::Checksums, HashesFormat::Bsd] {
+        let file = ("checksums-test-format-write_read_multiple.hash".into(),
+                    temp_dir().join(format!("checksums-test-format-write_read_multiple-{:?}.hash", fmt)));
+        write_hashes(&file, &HashesHeader::new(algos.to_vec(), None, false), *fmt, hashes.clone(), &BTreeSet::new(), &BTreeMap::new(), &BTreeMap::new())
+            .unwrap();
+
+        // The header's algorithms take precedence
+        let (_, _, mut read) = read_hashes(&mut vec![], &file, &[Algorithm::MD5]).unwrap();
+        read.remove(&file.0);
+        if *fmt == HashesFormat::Checksums {
+            assert_eq!(read, hashes);
+        } else {
+            assert_eq!(read.len(), hashes.len() - 1);
+            assert!(!read.contains_key(OsStr::new("ignored")));
+        }
+
+        fs::remove_file(&file.1).unwrap();
+    }
+}
+
+#[test]
+fn write_read_pathological() {
+    let algos = [Algorithm::CRC16, Algorithm::CRC32];
+    let mut hashes = BTreeMap::new();
+    for fname in &["a\tb", "a  ", "  b", "c\t\t", "d\n  e", "f  0BC4  0B0ADA8E", "\\g ", "# metadata mode=644 : h"] {
+        let mut file_hashes = BTreeMap::new();
+        file_hashes.insert(Algorithm::CRC16, "0BC4".to_string());
+        file_hashes.insert(Algorithm::CRC32, "0B0ADA8E".to_string());
+        hashes.insert(fname.into(), file_hashes);
+    }
+
+    let file = ("checksums-test-format-write_read_pathological.hash".into(), temp_dir().join("checksums-test-format-write_read_pathological.hash"));
+    write_hashes(&file,
+                 &HashesHeader::new(algos.to_vec(), None, false),
+                 HashesFormat::Checksums,
+                 hashes.clone(),
+                 &BTreeSet::new(),
+                 &BTreeMap::new(),
+                 &BTreeMap::new())
+        .unwrap();
+    assert!(!fs::read_to_string(&file.1).unwrap().contains('\t'));
+
+    let (_, format, mut read) = read_hashes(&mut vec![], &file, &[]).unwrap();
+    assert_eq!(format, HashesFormat::Checksums);
+    read.remove(&file.0);
+    assert_eq!(read, hashes);
+
+    fs::remove_file(&file.1).unwrap();
+}
+
+#[test]
+fn writer_replaces_when_finished() {
+    let file = ("checksums-test-format-writer_replaces_when_finished.hash".into(), temp_dir().join("checksums-test-format-writer_replaces_when_finished.hash"));
+    File::create(&file.1).unwrap().write_all(b"previous").unwrap();
+    let header = HashesHeader::new(vec![Algorithm::CRC32], None, true);
+
+    {
+        let mut writer = HashesWriter::create(&file, &header, HashesFormat::Checksums).unwrap();
+        writer.write(OsStr::new("a.txt"), &single(Algorithm::CRC32, "0B0ADA8E")).unwrap();
+        assert!(hashes_temp_file(&file).1.exists());
+    }
+    assert!(!hashes_temp_file(&file).1.exists());
+    assert_eq!(fs::read_to_string(&file.1).unwrap(), "previous");
+
+    let mut writer = HashesWriter::create(&file, &header, HashesFormat::Checksums).unwrap();
+    writer.write(&hashes_temp_file(&file).0, &single(Algorithm::CRC32, "--------")).unwrap();
+    writer.write(OsStr::new("a.txt"), &single(Algorithm::CRC32, "0B0ADA8E")).unwrap();
+    writer.finish().unwrap();
+    assert!(!hashes_temp_file(&file).1.exists());
+    assert_eq!(read_hashes(&mut vec![], &file, &[]).unwrap().2.keys().collect::<Vec<_>>(),
+               vec!["a.txt", "checksums-test-format-writer_replaces_when_finished.hash"]);
+
+    fs::remove_file(&file.1).unwrap();
+}
+
+#[test]
+fn read_coreutils() {
+    let file = ("checksums-test-format-read_coreutils.hash".into(), temp_dir().join("checksums-test-format-read_coreutils.hash"));
+    File::create(&file.1)
+        .unwrap()
+        .write_all(b"8313958f86f7b15d4775d12886d479c1cfaaa111  a.txt\n\\8d742c1f2d39434771039e98ad854c72f91fcca5 *b\\\\c.txt\n")
+        .unwrap();
+
+    let (header, format, read) = read_hashes(&mut vec![], &file, &[Algorithm::SHA1]).unwrap();
+    assert_eq!(header, None);
+    assert_eq!(format, HashesFormat::Coreutils);
+    assert_eq!(read.into_iter().collect::<Vec<_>>(),
+               vec![("a.txt".into(), single(Algorithm::SHA1, "8313958F86F7B15D4775D12886D479C1CFAAA111")),
+                    ("b\\c.txt".into(), single(Algorithm::SHA1, "8D742C1F2D39434771039E98AD854C72F91FCCA5"))]);
+
+    fs::remove_file(&file.1).unwrap();
+}
+
+#[test]
+fn read_coreutils_hex_names() {
+    let file = ("checksums-test-format-read_coreutils_hex_names.hash".into(), temp_dir().join("checksums-test-format-read_coreutils_hex_names.hash"));
+    File::create(&file.1)
+        .unwrap()
+        .write_all(b"8313958f86f7b15d4775d12886d479c1cfaaa111  001\n8d742c1f2d39434771039e98ad854c72f91fcca5  cafe\n")
+        .unwrap();
+
+    let (_, format, read) = read_hashes(&mut vec![], &file, &[Algorithm::SHA1]).unwrap();
+    assert_eq!(format, HashesFormat::Coreutils);
+    assert_eq!(read.into_iter().collect::<Vec<_>>(),
+               vec![("001".into(), single(Algorithm::SHA1, "8313958F86F7B15D4775D12886D479C1CFAAA111")),
+                    ("cafe".into(), single(Algorithm::SHA1, "8D742C1F2D39434771039E98AD854C72F91FCCA5"))]);
+
+    fs::remove_file(&file.1).unwrap();
+}
+
+#[test]
+fn read_bsd_mixed() {
+    let file = ("checksums-test-format-read_bsd_mixed.hash".into(), temp_dir().join("checksums-test-format-read_bsd_mixed.hash"));
+    File::create(&file.1)
+        .unwrap()
+        .write_all(b"SHA1 (a.txt) = 8313958f86f7b15d4775d12886d479c1cfaaa111\nMD5 (b.txt) = 098f6bcd4621d373cade4e832627b4f6\n")
+        .unwrap();
+
+    let (header, format, read) = read_hashes(&mut vec![], &file, &[Algorithm::BLAKE3]).unwrap();
+    assert_eq!(header, None);
+    assert_eq!(format, HashesFormat::Bsd);
+    assert_eq!(read.into_iter().collect::<Vec<_>>(),
+               vec![("a.txt".into(), single(Algorithm::SHA1, "8313958F86F7B15D4775D12886D479C1CFAAA111")),
+                    ("b.txt".into(), single(Algorithm::MD5, "098F6BCD4621D373CADE4E832627B4F6"))]);
+
+    fs::remove_file(&file.1).unwrap();
+}
+
+#[test]
+fn read_whole_file() {
+    let file = ("checksums-test-format-read_whole_file.hash".into(), temp_dir().join("checksums-test-format-read_whole_file.hash"));
+    File::create(&file.1)
+        .unwrap()
+        .write_all(b"# checksums v0.9.0 algorithms=CRC32 depth=-1 follow-symlinks=yes directories=yes\n\
+                     # directory dir\n\
+                     dir/a.txt  0B0ADA8E\n\
+                     # digest CRC32 0B0ADA8E : dir\n\
+                     # metadata mode=644 : dir/a.txt\n")
+        .unwrap();
+
+    let read = read_hashes_file(&mut vec![], &file, &[Algorithm::SHA1]).unwrap();
+    assert_eq!(read.format, HashesFormat::Checksums);
+    assert_eq!(read.header.map(|h| h.directories), Some(true));
+    assert_eq!(read.hashes.into_iter().collect::<Vec<_>>(), vec![("dir/a.txt".into(), single(Algorithm::CRC32, "0B0ADA8E"))]);
+    assert_eq!(read.directories.into_iter().collect::<Vec<_>>(), vec![OsString::from("dir")]);
+    assert_eq!(read.digests[&Algorithm::CRC32][OsStr::new("dir")], "0B0ADA8E");
+    assert_eq!(read.metadata[OsStr::new("dir/a.txt")][&MetadataKind::Mode], "644");
+
+    File::create(&file.1).unwrap().write_all(b"# checksums v0.9.0 algorithms=CRC32\na.txt  0B0ADA8E\nnot a hash line\n").unwrap();
+    let mut err = vec![];
+    assert_eq!(read_hashes_file(&mut err, &file, &[]), Err(Error::HashesFileParsingFailure));
+    assert_eq!(String::from_utf8(err).unwrap(), "checksums-test-format-read_whole_file.hash:2: Line doesn't match accepted pattern\n");
+
+    fs::remove_file(&file.1).unwrap();
+}
+
+
+fn single(algo: Algorithm, hash: &str) -> BTreeMap<Algorithm, String> {
+    let mut hashes = BTreeMap::new();
+    hashes.insert(algo, hash.to_string());
+    hashes
+}
@@ -0,0 +1,47 @@
+extern crate checksums;
+
+use self::checksums::ops::{HashesFormat, hash_input, write_input_hashes};
+use self::checksums::{Algorithm, Error, hash_reader_multiple, hash_reader_multiple_keyed};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::env::temp_dir;
+use std::io::{self, Write};
+
+
+#[test]
+fn file() {
+    let file = temp_dir().join("checksums-test-input-file");
+    File::create(&file).unwrap().write_all(b"checksums").unwrap();
+
+    let algos = [Algorithm::SHA1, Algorithm::BLAKE3];
+    assert_eq!(hash_input(file.as_os_str(), &algos, None).unwrap(), hash_reader_multiple(&mut &b"checksums"[..], &algos).unwrap());
+    assert_eq!(hash_input(file.as_os_str(), &algos, Some(&[0x42; 32])).unwrap(),
+               hash_reader_multiple_keyed(&mut &b"checksums"[..], &algos, &[0x42; 32]).unwrap());
+
+    fs::remove_file(&file).unwrap();
+    assert_eq!(hash_input(file.as_os_str(), &algos, None).unwrap_err().kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn write_formats() {
+    let algos = [Algorithm::CRC16, Algorithm::CRC32];
+    let hashes = || {
+        vec![("a.txt".into(), Ok(single(&[(Algorithm::CRC16, "9B9A"), (Algorithm::CRC32, "CFAAA111")]))),
+             ("gone".into(), Err(io::Error::new(io::ErrorKind::NotFound, "not found"))),
+             ("-".into(), Ok(single(&[(Algorithm::CRC16, "0000"), (Algorithm::CRC32, "00000000")])))]
+    };
+
+    let (mut out, mut err) = (vec![], vec![]);
+    assert_eq!(write_input_hashes(&mut out, &mut err, HashesFormat::Checksums, &algos, hashes()), Error::NFilesUnreadable(1));
+    assert_eq!(String::from_utf8(out).unwrap(), "a.txt  9B9A  CFAAA111\n-      0000  00000000\n");
+    assert_eq!(String::from_utf8(err).unwrap(), "Couldn't read file: \"gone\"\n  not found\n\n");
+
+    let mut out = vec![];
+    assert_eq!(write_input_hashes(&mut out, &mut vec![], HashesFormat::Bsd, &algos[1..], hashes()), Error::NFilesUnreadable(1));
+    assert_eq!(String::from_utf8(out).unwrap(), "CRC32 (a.txt) = cfaaa111\nCRC32 (-) = 00000000\n");
+}
+
+
+fn single(hashes: &[(Algorithm, &str)]) -> BTreeMap<Algorithm, String> {
+    hashes.iter().map(|&(algo, hash)| (algo, hash.to_string())).collect()
+}
@@ -0,0 +1,124 @@
+extern crate checksums;
+
+use self::checksums::ops::{HashesHeader, WalkOptions, create_hashes};
+use self::checksums::{Algorithm, Error, hash_reader_multiple, hash_reader_multiple_keyed, key_id, keyed_hasher};
+use std::process::Command;
+use std::path::Path;
+use std::fs::{self, File};
+use std::env::temp_dir;
+use std::io::{self, Write};
+use std::ffi::OsStr;
+
+
+#[test]
+fn hmac() {
+    let hashes = hash_reader_multiple_keyed(&mut &b"what do ya want for nothing?"[..],
+                                            &[Algorithm::SHA1,
+                                              Algorithm::SHA2224,
+                                              Algorithm::SHA2256,
+                                              Algorithm::SHA2384,
+                                              Algorithm::SHA2512,
+                                              Algorithm::SHA3256,
+                                              Algorithm::SHA3512],
+                                            b"Jefe")
+        .unwrap();
+    assert_eq!(hashes[&Algorithm::SHA1], "EFFCDF6AE5EB2FA2D27416D5F184DF9C259A7C79");
+    assert_eq!(hashes[&Algorithm::SHA2224], "A30E01098BC6DBBF45690F3A7E9E6D0F8BBEA2A39E6148008FD05E44");
+    assert_eq!(hashes[&Algorithm::SHA2256], "5BDCC146BF60754E6A042426089575C75A003F089D2739839DEC58B964EC3843");
+    assert_eq!(hashes[&Algorithm::SHA2384],
+               "AF45D2E376484031617F78D2B58A6B1B9C7EF464F5A01B47E42EC3736322445E8E2240CA5E69E2C78B3239ECFAB21649");
+    assert_eq!(hashes[&Algorithm::SHA2512],
+               "164B7A7BFCF819E2E395FBE73B56E0A387BD64222E831FD610270CD7EA2505549758BF75C05A994A6D034F65F8F0E6FDCAEAB1A34D4A6B4B636E070A38BCE737");
+    assert_eq!(hashes[&Algorithm::SHA3256], "C7D4072E788877AE3596BBB0DA73B887C9171F93095B294AE857FBE2645E1BA5");
+    assert_eq!(hashes[&Algorithm::SHA3512],
+               "5A4BFEAB6166427C7A3647B747292B8384537CDB89AFB3BF5665E4C5E709350B287BAEC921FD7CA0EE7A0C31D022A95E1FC92BA9D77DF883960275BEB4E62024");
+}
+
+#[test]
+fn hmac_long_key() {
+    let hashes = hash_reader_multiple_keyed(&mut &b"Test Using Larger Than Block-Size Key - Hash Key First"[..],
+                                            &[Algorithm::SHA2256, Algorithm::SHA3256, Algorithm::SHA3512],
+                                            &[0xAA; 131])
+        .unwrap();
+    assert_eq!(hashes[&Algorithm::SHA2256], "60E431591EE0B67F0D8A26AACBF5B77F8E0BC6213728C5140546040F0EE37F54");
+    assert_eq!(hashes[&Algorithm::SHA3256], "ED73A374B96C005235F948032F09674A58C0CE555CFC1F223B02356560312C3B");
+    assert_eq!(hashes[&Algorithm::SHA3512],
+               "00F751A9E50695B090ED6911A4B65524951CDC15A73A5D58BB55215EA2CD839AC79D2B44A39BAFAB27E83FDE9E11F6340B11D991B1B91BF2EEE7FC872426C3A4");
+}
+
+#[test]
+fn keyed_blake() {
+    let hashes = hash_reader_multiple_keyed(&mut &b"what do ya want for nothing?"[..], &[Algorithm::BLAKE2B, Algorithm::BLAKE2S], b"Jefe").unwrap();
+    assert_eq!(hashes[&Algorithm::BLAKE2B],
+               "380246F80263DB862B00D41EBB70E6D26FA97C4B42AE7985991DEB963B4317AA33735FF9DC76BD294455731365AB3A9EB67D33F83F98360F2BAE5F7A4356E6B1");
+    assert_eq!(hashes[&Algorithm::BLAKE2S], "D7FE099D889BA98178A934DE6BD36DA084600D7831FF16B8DEAEEFA8F6C00AF4");
+
+    let unkeyed = hash_reader_multiple(&mut &b"checksums"[..], &[Algorithm::BLAKE3]).unwrap();
+    let keyed = hash_reader_multiple_keyed(&mut &b"checksums"[..], &[Algorithm::BLAKE3], &[0; 32]).unwrap();
+    assert_eq!(keyed[&Algorithm::BLAKE3].len(), unkeyed[&Algorithm::BLAKE3].len());
+    assert_ne!(keyed, unkeyed);
+}
+
+#[test]
+fn bad_keys() {
+    assert_eq!(keyed_hasher(Algorithm::BLAKE2B, &[0; 65]).err(), Some("BLAKE2b needs a key of up to 64 bytes, not 65".to_string()));
+    assert_eq!(keyed_hasher(Algorithm::BLAKE2S, &[0; 33]).err(), Some("BLAKE2s needs a key of up to 32 bytes, not 33".to_string()));
+    assert_eq!(keyed_hasher(Algorithm::BLAKE3, &[0; 31]).err(), Some("BLAKE3 needs a 32-byte key, not 31".to_string()));
+    for algo in &[Algorithm::BLAKE, Algorithm::CRC32, Algorithm::MD5, Algorithm::XOR8] {
+        assert_eq!(keyed_hasher(*algo, b"Jefe").err(), Some(format!("{} can't be keyed", algo)));
+    }
+}
+
+#[test]
+fn header_key_id() {
+    let header = HashesHeader { key_id: Some(key_id(b"Jefe")), ..HashesHeader::new(vec![Algorithm::SHA1], None, true) };
+    assert!(header.to_string().ends_with(&format!(" key={}", key_id(b"Jefe"))));
+    assert_eq!(HashesHeader::parse(&header.to_string()), Some(header));
+}
+
+#[test]
+fn create_keyed_hashes() {
+    let dir = temp_dir().join("checksums-test-keyed-create_keyed_hashes");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    File::create(dir.join("file")).unwrap().write_all(b"what do ya want for nothing?").unwrap();
+
+    let (hashes, errors) = create_hashes(&dir, &[Algorithm::SHA1], Some(b"Jefe"), WalkOptions::new(&Default::default()), io::sink());
+    assert!(errors.is_empty());
+    assert_eq!(hashes[OsStr::new("file")][&Algorithm::SHA1], "EFFCDF6AE5EB2FA2D27416D5F184DF9C259A7C79");
+
+    let (_, errors) = create_hashes(&dir, &[Algorithm::CRC32], Some(b"Jefe"), WalkOptions::new(&Default::default()), io::sink());
+    assert_eq!(errors[OsStr::new("file")].kind(), io::ErrorKind::InvalidInput);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn diff_keyed_with_unkeyed() {
+    let dir = temp_dir().join("checksums-test-keyed-diff_keyed_with_unkeyed");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("tree")).unwrap();
+    File::create(dir.join("tree").join("file")).unwrap().write_all(b"what do ya want for nothing?").unwrap();
+    File::create(dir.join("key")).unwrap().write_all(b"Jefe").unwrap();
+
+    let (keyed, unkeyed) = (dir.join("keyed.hash"), dir.join("unkeyed.hash"));
+    let status = Command::new(env!("CARGO_BIN_EXE_checksums"))
+        .args(["-c", "-a", "SHA1", "--key-file"])
+        .arg(dir.join("key"))
+        .arg(dir.join("tree"))
+        .arg("-f")
+        .arg(&keyed)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success());
+    // No header, like a hand-written file
+    File::create(&unkeyed).unwrap().write_all(b"file  8313958F86F7B15D4775D12886D479C1CFAAA111\n").unwrap();
+
+    let diff = |old: &Path, new: &Path| Command::new(env!("CARGO_BIN_EXE_checksums")).arg("diff").arg(old).arg(new).output().unwrap().status.code();
+    assert_eq!(diff(&keyed, &keyed), Some(Error::NoError.exit_value()));
+    assert_eq!(diff(&keyed, &unkeyed), Some(Error::OptionParsingError.exit_value()));
+    assert_eq!(diff(&unkeyed, &keyed), Some(Error::OptionParsingError.exit_value()));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
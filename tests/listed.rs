@@ -0,0 +1,47 @@
+extern crate checksums;
+
+use self::checksums::ops::{FileFilter, create_listed_hashes, find_unlisted_files};
+use self::checksums::Algorithm;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::env::temp_dir;
+use std::io::{self, Write};
+
+
+#[test]
+fn create_listed() {
+    let dir = temp_dir().join("checksums-test-listed-create_listed");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub/tmp")).unwrap();
+    for file in &["a", "unlisted", "placeholder", "sub/b", "sub/tmp/c"] {
+        File::create(dir.join(file)).unwrap().write_all(b"checksums").unwrap();
+    }
+
+    let mut listed = BTreeMap::new();
+    for &(fname, hash) in &[("a", "00000000"), ("gone", "00000000"), ("placeholder", "--------"), ("sub/b", "00000000"), ("sub/tmp/c", "00000000"),
+                            ("../outside", "00000000")] {
+        listed.insert(fname.into(), single(Algorithm::CRC32, hash));
+    }
+
+    let filter = FileFilter::new(&dir, &["tmp/".to_string()], &[], false).unwrap();
+    let (hashes, errors) = create_listed_hashes(&dir, &listed, &filter, &[Algorithm::CRC32], None, 2, io::sink());
+    assert_eq!(errors.keys().collect::<Vec<_>>(), vec!["../outside"]);
+    assert_eq!(hashes.into_iter().map(|(fname, hashes)| (fname, hashes[&Algorithm::CRC32].clone())).collect::<Vec<_>>(),
+               vec![("a".into(), "0B0ADA8E".to_string()),
+                    ("placeholder".into(), "--------".to_string()),
+                    ("sub/b".into(), "0B0ADA8E".to_string()),
+                    ("sub/tmp/c".into(), "--------".to_string())]);
+
+    let filter = FileFilter::new(&dir, &["placeholder".to_string()], &[], false).unwrap();
+    assert_eq!(find_unlisted_files(&dir, &listed, &filter, None, true), vec!["unlisted"]);
+    assert_eq!(find_unlisted_files(&dir, &BTreeMap::new(), &filter, Some(0), true), vec!["a", "unlisted"]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+
+fn single(algo: Algorithm, hash: &str) -> BTreeMap<Algorithm, String> {
+    let mut hashes = BTreeMap::new();
+    hashes.insert(algo, hash.to_string());
+    hashes
+}
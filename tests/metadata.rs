@@ -0,0 +1,163 @@
+extern crate checksums;
+
+use self::checksums::ops::{CompareFileResult, HashesFormat, HashesHeader, MetadataKind, compare_metadata, create_metadata, parse_metadata_line, read_hashes,
+                           read_metadata, write_hash_comparison_results, write_hashes};
+use self::checksums::{Algorithm, Error};
+use std::collections::{BTreeSet, BTreeMap};
+use std::fs::{self, File};
+use std::env::temp_dir;
+use std::str::FromStr;
+use std::ffi::OsStr;
+use std::io::Write;
+
+
+#[test]
+fn kind_from_str() {
+    for kind in &MetadataKind::ALL {
+        assert_eq!(MetadataKind::from_str(&kind.to_string()), Ok(*kind));
+    }
+    for s in &["uid", "xattr", ""] {
+        MetadataKind::from_str(s).unwrap_err();
+    }
+}
+
+#[test]
+fn parse_line() {
+    let (fname, values) = parse_metadata_line("# metadata mode=755 future=param target=a\\x20b : dir/c\\nd").unwrap();
+    assert_eq!(fname, "dir/c\nd");
+    assert_eq!(values, metadata(&[(MetadataKind::Mode, "755"), (MetadataKind::Target, "a b")]));
+
+    assert_eq!(parse_metadata_line("# metadata : file"), Some(("file".into(), BTreeMap::new())));
+    assert_eq!(parse_metadata_line("# metadata mode=755"), None);
+    assert_eq!(parse_metadata_line("# checksums v1.2.3 algorithms=SHA1"), None);
+}
+
+#[test]
+fn write_read() {
+    let mut hashes = BTreeMap::new();
+    hashes.insert("a.txt".into(), single(Algorithm::SHA1, "8313958F86F7B15D4775D12886D479C1CFAAA111"));
+    hashes.insert("#b c".into(), single(Algorithm::SHA1, "8D742C1F2D39434771039E98AD854C72F91FCCA5"));
+    hashes.insert("d\te ".into(), single(Algorithm::SHA1, "6B9B9319A3F0B1A2D8F5B19A8E05D2C3C5A2D14E"));
+    let mut written_metadata = BTreeMap::new();
+    written_metadata.insert("a.txt".into(), metadata(&[(MetadataKind::Mode, "644"), (MetadataKind::Owner, "1000:100")]));
+    written_metadata.insert("#b c".into(), metadata(&[(MetadataKind::Mode, "600")]));
+    written_metadata.insert("d\te ".into(), metadata(&[(MetadataKind::Target, "f g\th\\\\i ")]));
+
+    for fmt in &HashesFormat::ALL {
+        let file = ("checksums-test-metadata-write_read.hash".into(), temp_dir().join(format!("checksums-test-metadata-write_read-{:?}.hash", fmt)));
+        write_hashes(&file,
+                     &HashesHeader::new(vec![Algorithm::SHA1], None, true),
+                     *fmt,
+                     hashes.clone(),
+                     &BTreeSet::new(),
+                     &BTreeMap::new(),
+                     &written_metadata)
+            .unwrap();
+        assert!(fs::read_to_string(&file.1).unwrap().contains("\n# metadata mode=644 owner=1000:100 : a.txt\n"));
+        assert!(fs::read_to_string(&file.1).unwrap().contains("\n# metadata target=f\\x20g\\th\\\\i\\x20 : d\\te\\x20\n"));
+
+        let (_, read_format, mut read) = read_hashes(&mut vec![], &file, &[Algorithm::SHA1]).unwrap();
+        assert_eq!(read_format, *fmt);
+        read.remove(&file.0);
+        assert_eq!(read, hashes);
+        assert_eq!(read_metadata(&mut vec![], &file), Ok(written_metadata.clone()));
+
+        fs::remove_file(&file.1).unwrap();
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn create_compare() {
+    use std::os::unix::fs::{PermissionsExt, symlink};
+
+    let dir = temp_dir().join("checksums-test-metadata-create_compare");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    File::create(dir.join("file")).unwrap().write_all(b"checksums").unwrap();
+    fs::set_permissions(dir.join("file"), fs::Permissions::from_mode(0o640)).unwrap();
+    symlink("file", dir.join("link")).unwrap();
+
+    let (loaded, errors) = create_metadata(&dir, &Default::default(), &[MetadataKind::Mode, MetadataKind::Target], None, false);
+    assert!(errors.is_empty());
+    assert_eq!(loaded[OsStr::new("file")], metadata(&[(MetadataKind::Mode, "640")]));
+    assert_eq!(loaded[OsStr::new("link")][&MetadataKind::Target], "file");
+
+    fs::set_permissions(dir.join("file"), fs::Permissions::from_mode(0o600)).unwrap();
+    fs::remove_file(dir.join("link")).unwrap();
+    symlink("other\\file", dir.join("link")).unwrap();
+
+    let (current, _) = create_metadata(&dir, &Default::default(), &[MetadataKind::Mode, MetadataKind::Target], None, false);
+    assert_eq!(compare_metadata(&current, &loaded),
+               vec![CompareFileResult::MetadataDiffers {
+                        file: "file".into(),
+                        kind: MetadataKind::Mode,
+                        was: "640".to_string(),
+                        is: "600".to_string(),
+                    },
+                    CompareFileResult::MetadataDiffers {
+                        file: "link".into(),
+                        kind: MetadataKind::Target,
+                        was: "file".to_string(),
+                        is: "other\\\\file".to_string(),
+                    }]);
+
+    // Symlinks are followed, so the link has the file's metadata
+    fs::remove_file(dir.join("link")).unwrap();
+    symlink("file", dir.join("link")).unwrap();
+    let (followed, _) = create_metadata(&dir, &Default::default(), &[MetadataKind::Mode], None, true);
+    assert_eq!(followed[OsStr::new("link")], metadata(&[(MetadataKind::Mode, "600")]));
+
+    // Targets with whitespace survive a round-trip through the hashes file
+    fs::remove_file(dir.join("link")).unwrap();
+    symlink("other file ", dir.join("link")).unwrap();
+    let (spaced, _) = create_metadata(&dir, &Default::default(), &[MetadataKind::Target], None, false);
+    assert_eq!(spaced[OsStr::new("link")][&MetadataKind::Target], "other file ");
+    let file = ("create_compare.hash".into(), temp_dir().join("checksums-test-metadata-create_compare.hash"));
+    write_hashes(&file,
+                 &HashesHeader::new(vec![Algorithm::SHA1], None, false),
+                 HashesFormat::Checksums,
+                 BTreeMap::new(),
+                 &BTreeSet::new(),
+                 &BTreeMap::new(),
+                 &spaced)
+        .unwrap();
+    assert_eq!(read_metadata(&mut vec![], &file), Ok(spaced));
+    fs::remove_file(&file.1).unwrap();
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn write_results() {
+    let results = vec![CompareFileResult::FileMatches("file".into()),
+                       CompareFileResult::MetadataDiffers {
+                           file: "file".into(),
+                           kind: MetadataKind::Mode,
+                           was: "644".to_string(),
+                           is: "600".to_string(),
+                       },
+                       CompareFileResult::MetadataDiffers {
+                           file: "file".into(),
+                           kind: MetadataKind::Owner,
+                           was: "0:0".to_string(),
+                           is: "1000:100".to_string(),
+                       }];
+
+    let mut out = vec![];
+    assert_eq!(write_hash_comparison_results(&mut out, &mut vec![], Ok((vec![], results))), Error::NFilesDiffer(1));
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("File \"file\" mode changed\n  Was: 644\n  Is : 600\n"));
+    assert!(out.contains("File \"file\" owner changed\n  Was: 0:0\n  Is : 1000:100\n"));
+}
+
+
+fn single(algo: Algorithm, hash: &str) -> BTreeMap<Algorithm, String> {
+    let mut hashes = BTreeMap::new();
+    hashes.insert(algo, hash.to_string());
+    hashes
+}
+
+fn metadata(values: &[(MetadataKind, &str)]) -> BTreeMap<MetadataKind, String> {
+    values.iter().map(|&(kind, value)| (kind, value.to_string())).collect()
+}
@@ -0,0 +1,86 @@
+extern crate checksums;
+
+use self::checksums::ops::{PublicKey, SecretKey, sign_hashes, signature_file, verify_hashes_signature};
+use self::checksums::Error;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::env::temp_dir;
+use std::ffi::OsString;
+use std::io::Write;
+
+
+static SECRET_KEY: &str = "untrusted comment: minisign encrypted secret key\n\
+                           RWQAAEIyAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA0sdD59j89XvTvrFsonqfwzK1X1JuHI2m2wsvWNUMnSfVnhXiOk41\
+                           qIpxWdc7GBraaNME8PmVpfAd40GXKUifWKb0h7rygRtU5mECT/WGajqb+MIMzOC/+9GgzNx+b9kwssr99eJGJUk=\n";
+static PUBLIC_KEY: &str = "untrusted comment: minisign public key 7BF5FCD8E743C7D2\nRWTSx0Pn2Pz1e4pxWdc7GBraaNME8PmVpfAd40GXKUifWKb0h7rygRtU\n";
+static OTHER_PUBLIC_KEY: &str = "untrusted comment: minisign public key EFCDAB8967452301\nRWQBI0VniavN7+IJaHXCaKCZPyD6S2bhX/ZR6eVc0sCN+7JOH0B5wUW6\n";
+
+
+#[test]
+fn read_keys() {
+    let secret = SecretKey::read(&key_file("read_keys.key", SECRET_KEY)).unwrap();
+    let public = PublicKey::read(&key_file("read_keys.pub", PUBLIC_KEY)).unwrap();
+    assert_eq!(secret.public_key(), public);
+    assert_eq!(public.id(), "7BF5FCD8E743C7D2");
+
+    PublicKey::read(&key_file("read_keys-bad.pub", "untrusted comment: nope\nY2hlY2tzdW1z\n")).unwrap_err();
+    SecretKey::read(&key_file("read_keys-public.key", PUBLIC_KEY)).unwrap_err();
+    SecretKey::read(&key_file("read_keys-corrupted.key", &SECRET_KEY.replace("0sdD", "0sdE"))).unwrap_err();
+}
+
+#[test]
+fn sign_verify() {
+    let file = hashes_file("sign_verify", "a.txt  8313958F86F7B15D4775D12886D479C1CFAAA111\n");
+    let secret = SecretKey::read(&key_file("sign_verify.key", SECRET_KEY)).unwrap();
+    let public = PublicKey::read(&key_file("sign_verify.pub", PUBLIC_KEY)).unwrap();
+    let other = PublicKey::read(&key_file("sign_verify-other.pub", OTHER_PUBLIC_KEY)).unwrap();
+
+    let sig_file = signature_file(&file);
+    assert_eq!(sig_file.0, "checksums-test-sign-sign_verify.hash.minisig");
+    let _ = fs::remove_file(&sig_file.1);
+    assert_eq!(verify_hashes_signature(&mut vec![], &file, &public), Err(Error::SignatureInvalid));
+
+    sign_hashes(&file, &secret).unwrap();
+    assert!(fs::read_to_string(&sig_file.1).unwrap().contains("\ntrusted comment: timestamp:"));
+    assert_eq!(verify_hashes_signature(&mut vec![], &file, &public), Ok(()));
+
+    let mut err = vec![];
+    assert_eq!(verify_hashes_signature(&mut err, &file, &other), Err(Error::SignatureInvalid));
+    assert_eq!(String::from_utf8(err).unwrap(),
+               "checksums-test-sign-sign_verify.hash.minisig: signed with a different key, not EFCDAB8967452301\n");
+
+    File::create(&file.1).unwrap().write_all(b"a.txt  8D742C1F2D39434771039E98AD854C72F91FCCA5\n").unwrap();
+    assert_eq!(verify_hashes_signature(&mut vec![], &file, &public), Err(Error::SignatureInvalid));
+
+    fs::remove_file(&file.1).unwrap();
+    fs::remove_file(&sig_file.1).unwrap();
+}
+
+#[test]
+fn trusted_comment_tampered() {
+    let file = hashes_file("trusted_comment_tampered", "a.txt  8313958F86F7B15D4775D12886D479C1CFAAA111\n");
+    let secret = SecretKey::read(&key_file("trusted_comment_tampered.key", SECRET_KEY)).unwrap();
+
+    sign_hashes(&file, &secret).unwrap();
+    let sig_file = signature_file(&file);
+    let signature = fs::read_to_string(&sig_file.1).unwrap().replace("timestamp:", "timestamp:1");
+    File::create(&sig_file.1).unwrap().write_all(signature.as_bytes()).unwrap();
+    assert_eq!(verify_hashes_signature(&mut vec![], &file, &secret.public_key()), Err(Error::SignatureInvalid));
+
+    fs::remove_file(&file.1).unwrap();
+    fs::remove_file(&sig_file.1).unwrap();
+}
+
+
+fn key_file(name: &str, contents: &str) -> PathBuf {
+    let path = temp_dir().join(format!("checksums-test-sign-{}", name));
+    File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+    path
+}
+
+fn hashes_file(name: &str, contents: &str) -> (OsString, PathBuf) {
+    let name = format!("checksums-test-sign-{}.hash", name);
+    let path = temp_dir().join(&name);
+    File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+    (name.into(), path)
+}
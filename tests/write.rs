@@ -0,0 +1,144 @@
+extern crate checksums;
+extern crate serde_json;
+
+use self::checksums::ops::{CompareResult, CompareFileResult, CompareError, OutputFormat, write_hash_comparison_results, write_hash_comparison_results_json,
+                           write_hash_update_results};
+use self::checksums::Error;
+use std::collections::BTreeMap;
+use serde_json::Value;
+use std::str::FromStr;
+use std::io;
+
+
+#[test]
+fn output_format_from_str() {
+    for p in &[("text", OutputFormat::Text), ("json", OutputFormat::Json), ("ndjson", OutputFormat::Ndjson), ("jsonl", OutputFormat::Ndjson)] {
+        assert_eq!(OutputFormat::from_str(p.0).unwrap(), p.1);
+    }
+    OutputFormat::from_str("xml").unwrap_err();
+}
+
+#[test]
+fn json_object() {
+    let mut out = vec![];
+    let result = write_hash_comparison_results_json(&mut out, &BTreeMap::new(), Ok(results()), false);
+    assert_eq!(result, Error::NFilesDiffer(1));
+
+    let out: Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(out["results"].as_array().unwrap().len(), 4);
+    assert_eq!(out["results"][0], parse(r#"{"result": "added", "file": "added"}"#));
+    assert_eq!(out["results"][3],
+               parse(r#"{"result": "differs", "file": "differs", "was_hash": "8313958F86F7B15D4775D12886D479C1CFAAA111",
+                        "new_hash": "8D742C1F2D39434771039E98AD854C72F91FCCA5"}"#));
+    assert_eq!(out["summary"],
+               parse(r#"{"unreadable": 0, "added": 1, "removed": 1, "moved": 0, "copied": 0, "directories_added": 0, "directories_removed": 0,
+                        "digests_differ": 0, "ignored": 0, "matches": 1, "differs": 1, "metadata_differs": 0}"#));
+}
+
+#[test]
+fn ndjson() {
+    let mut errors = BTreeMap::new();
+    errors.insert("unreadable".into(), io::Error::new(io::ErrorKind::PermissionDenied, "Permission denied"));
+
+    let mut out = vec![];
+    let result = write_hash_comparison_results_json(&mut out, &errors, Ok((vec![], vec![CompareFileResult::FileMatches("matches".into())])), true);
+    assert_eq!(result, Error::NFilesUnreadable(1));
+
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<Value> = out.lines().map(parse).collect();
+    assert_eq!(lines,
+               vec![parse(r#"{"result": "unreadable", "file": "unreadable", "error": "Permission denied"}"#),
+                    parse(r#"{"result": "matches", "file": "matches"}"#),
+                    parse(r#"{"summary": {"unreadable": 1, "added": 0, "removed": 0, "moved": 0, "copied": 0, "directories_added": 0,
+                                  "directories_removed": 0, "digests_differ": 0, "ignored": 0, "matches": 1, "differs": 0,
+                                  "metadata_differs": 0}}"#)]);
+}
+
+#[test]
+fn json_moved_copied() {
+    let mut out = vec![];
+    let result = write_hash_comparison_results_json(&mut out,
+                                                    &BTreeMap::new(),
+                                                    Ok((vec![CompareResult::FileMoved {
+                                                              from: "old".into(),
+                                                              to: "new".into(),
+                                                          },
+                                                          CompareResult::FileCopied {
+                                                              from: "orig".into(),
+                                                              to: "copy".into(),
+                                                          }],
+                                                        vec![CompareFileResult::FileMatches("orig".into())])),
+                                                    false);
+    assert_eq!(result, Error::NoError);
+
+    let out: Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(out["results"],
+               parse(r#"[{"result": "moved", "file": "new", "from": "old"}, {"result": "copied", "file": "copy", "from": "orig"},
+                         {"result": "matches", "file": "orig"}]"#));
+    assert_eq!(out["summary"]["moved"], 1);
+    assert_eq!(out["summary"]["copied"], 1);
+}
+
+#[test]
+fn json_error() {
+    let mut out = vec![];
+    let result = write_hash_comparison_results_json(&mut out,
+                                                    &BTreeMap::new(),
+                                                    Err(CompareError::HashLengthDiffers {
+                                                        previous_len: 40,
+                                                        current_len: 32,
+                                                    }),
+                                                    false);
+    assert_eq!(result, Error::HashLengthDiffers);
+    assert_eq!(serde_json::from_slice::<Value>(&out).unwrap(),
+               parse(r#"{"results": [], "error": {"kind": "hash_length_differs", "previous_len": 40, "current_len": 32}}"#));
+}
+
+#[test]
+fn size_differs() {
+    let results = vec![CompareFileResult::SizeDiffers {
+                           file: "grown".into(),
+                           was_size: 9,
+                           new_size: 10,
+                       }];
+
+    let mut out = vec![];
+    assert_eq!(write_hash_comparison_results(&mut out, &mut vec![], Ok((vec![], results.clone()))), Error::NFilesDiffer(1));
+    assert!(String::from_utf8(out).unwrap().contains("File \"grown\" doesn't match\n  Was: 9 bytes\n  Is : 10 bytes\n"));
+
+    let mut out = vec![];
+    assert_eq!(write_hash_comparison_results_json(&mut out, &BTreeMap::new(), Ok((vec![], results)), false), Error::NFilesDiffer(1));
+    let out: Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(out["results"], parse(r#"[{"result": "differs", "file": "grown", "was_size": 9, "new_size": 10}]"#));
+    assert_eq!(out["summary"]["differs"], 1);
+}
+
+#[test]
+fn update_summary() {
+    let mut out = vec![];
+    assert_eq!(write_hash_update_results(&mut out, &mut vec![], Ok(results()), false), Error::NFilesDiffer(1));
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("File \"differs\" doesn't match\n"));
+    assert!(out.ends_with("\n1 added, 1 removed, 0 moved, 0 copied, 1 changed and kept\n"));
+
+    let mut out = vec![];
+    assert_eq!(write_hash_update_results(&mut out, &mut vec![], Ok(results()), true), Error::NoError);
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("File rehashed: \"differs\"\n"));
+    assert!(out.ends_with("\n1 added, 1 removed, 0 moved, 0 copied, 1 rehashed\n"));
+}
+
+
+fn results() -> (Vec<CompareResult>, Vec<CompareFileResult>) {
+    (vec![CompareResult::FileRemoved("removed".into()), CompareResult::FileAdded("added".into())],
+     vec![CompareFileResult::FileDiffers {
+              file: "differs".into(),
+              was_hash: "8313958F86F7B15D4775D12886D479C1CFAAA111".to_string(),
+              new_hash: "8D742C1F2D39434771039E98AD854C72F91FCCA5".to_string(),
+          },
+          CompareFileResult::FileMatches("matches".into())])
+}
+
+fn parse(s: &str) -> Value {
+    serde_json::from_str(s).unwrap()
+}